@@ -0,0 +1,269 @@
+//! 計算式の候補。
+//!
+//! `12*(3+4)=` のように `=` で終わる読みを四則演算の式として計算し、
+//! 結果（84 / ８４ / 84.0）と、式に結果を続けたもの（12*(3+4)=84）を候補にする。
+//! ローマ字変換で `-` `.` `,` は「ー」「。」「、」になるため、どちらの形も受け付ける。
+
+use crate::candidate::{Candidate, CandidateSource};
+use crate::provider::{CandidateProvider, USER_DICTIONARY_PRIORITY};
+
+/// 計算結果の候補の優先度。式の読みは辞書にないため、辞書より先に並べる。
+pub const CALCULATOR_PRIORITY: i32 = USER_DICTIONARY_PRIORITY + 10;
+
+/// 小数の結果を表示するときの小数点以下の最大桁数。
+const MAX_FRACTION_DIGITS: usize = 10;
+
+/// 整数として表示する結果の絶対値の上限。
+const MAX_EXACT_INTEGER: f64 = 1e15;
+
+/// 計算式の候補の提供元。
+pub struct Calculator;
+
+impl CandidateProvider for Calculator {
+    fn name(&self) -> &str {
+        "calculator"
+    }
+
+    fn lookup(&self, reading: &str) -> Vec<Candidate> {
+        let Some(value) = evaluate(reading) else {
+            return Vec::new();
+        };
+        let result = format_number(value);
+        let mut texts = vec![result.clone(), to_fullwidth(&result)];
+        if !result.contains('.') {
+            texts.push(format!("{result}.0"));
+        }
+        texts.push(format!("{}{result}", normalize(reading)));
+        texts
+            .into_iter()
+            .map(|text| Candidate::new(&text, reading, CandidateSource::Generated))
+            .collect()
+    }
+}
+
+/// 読みが計算式（`=` で終わり、演算子を含む）かどうか。
+pub fn is_expression(reading: &str) -> bool {
+    evaluate(reading).is_some()
+}
+
+/// `=` で終わる計算式を計算する。
+///
+/// 四則演算・括弧・単項の符号に対応する。数字の間の `,` は桁区切りとして読み飛ばす。
+/// 式でない、演算子を含まない、0 で割る、結果が有限でない場合は None。
+pub fn evaluate(reading: &str) -> Option<f64> {
+    let normalized = normalize(reading);
+    let body = normalized.strip_suffix('=')?;
+    if !body.contains(['+', '-', '*', '/']) {
+        return None;
+    }
+    let mut parser = Parser {
+        chars: body.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.expr()?;
+    (parser.pos == parser.chars.len() && value.is_finite()).then_some(value)
+}
+
+/// かな・全角の記号を ASCII の記号にそろえる。
+fn normalize(reading: &str) -> String {
+    reading
+        .chars()
+        .map(|c| match c {
+            'ー' | '−' => '-',
+            '。' => '.',
+            '、' => ',',
+            '×' => '*',
+            '÷' => '/',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// 結果を表示用の文字列にする。整数はそのまま、小数は末尾の 0 を除く。
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < MAX_EXACT_INTEGER {
+        return format!("{}", value as i64);
+    }
+    let text = format!("{value:.MAX_FRACTION_DIGITS$}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// ASCII の文字を全角にする。
+fn to_fullwidth(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '!'..='~' => char::from_u32(c as u32 + 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// 再帰下降の式パーサ。
+///
+/// ```text
+/// expr   = term (('+' | '-') term)*
+/// term   = factor (('*' | '/') factor)*
+/// factor = ('+' | '-') factor | number | '(' expr ')'
+/// ```
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value += self.term()?;
+            } else if self.eat('-') {
+                value -= self.term()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.factor()?;
+        loop {
+            if self.eat('*') {
+                value *= self.factor()?;
+            } else if self.eat('/') {
+                let divisor = self.factor()?;
+                if divisor == 0.0 {
+                    return None;
+                }
+                value /= divisor;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Option<f64> {
+        if self.eat('+') {
+            return self.factor();
+        }
+        if self.eat('-') {
+            return self.factor().map(|v| -v);
+        }
+        if self.eat('(') {
+            let value = self.expr()?;
+            return self.eat(')').then_some(value);
+        }
+        self.number()
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' | '.' => text.push(c),
+                // 桁区切り: 数字に挟まれた `,` だけを読み飛ばす
+                ',' if !text.is_empty()
+                    && self
+                        .chars
+                        .get(self.pos + 1)
+                        .is_some_and(char::is_ascii_digit) => {}
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        if text.starts_with('.') || text.ends_with('.') {
+            return None;
+        }
+        text.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(reading: &str) -> Vec<String> {
+        Calculator
+            .lookup(reading)
+            .into_iter()
+            .map(|c| c.text)
+            .collect()
+    }
+
+    #[test]
+    fn integer_result_candidates() {
+        assert_eq!(texts("12*(3+4)="), ["84", "８４", "84.0", "12*(3+4)=84"]);
+    }
+
+    #[test]
+    fn fractional_result_candidates() {
+        assert_eq!(
+            texts("1000/3="),
+            [
+                "333.3333333333",
+                "３３３．３３３３３３３３３３",
+                "1000/3=333.3333333333"
+            ]
+        );
+    }
+
+    #[test]
+    fn kana_symbols_from_romaji() {
+        // `-` `.` `,` はローマ字変換で「ー」「。」「、」になる
+        assert_eq!(evaluate("1。5ー0。25="), Some(1.25));
+        assert_eq!(evaluate("1、000*3="), Some(3000.0));
+        assert_eq!(texts("10ー4=").last().unwrap(), "10-4=6");
+    }
+
+    #[test]
+    fn operator_precedence_and_sign() {
+        assert_eq!(evaluate("2+3*4="), Some(14.0));
+        assert_eq!(evaluate("(2+3)*4="), Some(20.0));
+        assert_eq!(evaluate("-3*-2="), Some(6.0));
+        assert_eq!(evaluate("10/4/5="), Some(0.5));
+        assert_eq!(evaluate("１２×３="), Some(36.0));
+    }
+
+    #[test]
+    fn negative_result() {
+        assert_eq!(texts("3-5="), ["-2", "－２", "-2.0", "3-5=-2"]);
+    }
+
+    #[test]
+    fn not_an_expression() {
+        assert!(evaluate("12*3").is_none(), "= で終わらない");
+        assert!(evaluate("12=").is_none(), "演算子がない");
+        assert!(evaluate("1/0=").is_none(), "0 で割る");
+        assert!(evaluate("(1+2=").is_none(), "括弧が閉じていない");
+        assert!(evaluate("1+=").is_none());
+        assert!(evaluate("1.+2=").is_none());
+        assert!(evaluate("かんじ=").is_none());
+        assert!(!is_expression("とうきょう"));
+        assert!(texts("12=").is_empty());
+    }
+
+    #[test]
+    fn format_number_trims_zeros() {
+        assert_eq!(format_number(0.5), "0.5");
+        assert_eq!(format_number(-0.0), "0");
+        assert_eq!(format_number(1e-12), "0");
+        assert_eq!(format_number(2.0 / 3.0), "0.6666666667");
+    }
+}
@@ -1,25 +1,127 @@
 //! 変換候補リストの管理。
 //!
 //! 候補の一覧と現在の選択インデックスを保持し、
-//! 前後ナビゲーション・選択を提供する。
+//! 前後ナビゲーション・ページ送り・選択を提供する。
 
+/// 候補の出所。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateSource {
+    /// ユーザー辞書（学習・単語登録）
+    UserDictionary,
+    /// システム辞書
+    SystemDictionary,
+    /// 記号辞書（記号・絵文字・顔文字）
+    Symbol,
+    /// 辞書以外から生成した候補（カタカナ・日付など）
+    Generated,
+}
+
+/// 変換候補。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// 候補の文字列
+    pub text: String,
+    /// 候補の読み（ひらがな）
+    pub reading: String,
+    pub source: CandidateSource,
+    /// 注釈（SKK 辞書の `;` 以降など）
+    pub annotation: Option<String>,
+    /// 並び替えの優先度。大きいほど先頭に並び、同じ値なら元の順序を保つ。
+    pub score: i32,
+    /// 確定後のカーソル位置（text 内の文字単位）。None なら末尾。
+    pub cursor: Option<usize>,
+}
+
+impl Candidate {
+    /// 注釈なし・優先度 0 の候補を作成する。
+    pub fn new(text: &str, reading: &str, source: CandidateSource) -> Self {
+        Self {
+            text: text.to_string(),
+            reading: reading.to_string(),
+            source,
+            annotation: None,
+            score: 0,
+            cursor: None,
+        }
+    }
+
+    /// 注釈を設定する。
+    pub fn with_annotation(mut self, annotation: Option<&str>) -> Self {
+        self.annotation = annotation.map(|a| a.to_string());
+        self
+    }
+
+    /// 確定後のカーソル位置を設定する。
+    pub fn with_cursor(mut self, cursor: Option<usize>) -> Self {
+        self.cursor = cursor;
+        self
+    }
+}
+
+impl AsRef<str> for Candidate {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+/// 候補の文字列との比較。
+impl PartialEq<&str> for Candidate {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+/// デフォルトの1ページあたりの候補数。
+pub const DEFAULT_PAGE_SIZE: usize = 9;
+
+/// 候補ウィンドウに表示する1ページ分の情報。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CandidatePage {
+    /// ページ内の候補
+    pub candidates: Vec<Candidate>,
+    /// 各候補の選択ラベル（`1`〜`9` や `a`, `s`, `d`, ...）
+    pub labels: Vec<char>,
+    /// ページ内で選択中の位置
+    pub selected: usize,
+    /// 現在のページ番号（0 始まり）
+    pub page_index: usize,
+    /// 総ページ数
+    pub page_count: usize,
+}
+
+#[derive(Debug, Clone)]
 pub struct CandidateList {
-    candidates: Vec<String>,
+    candidates: Vec<Candidate>,
     index: usize,
+    page_size: usize,
+    /// 候補を移動したか。変換直後の先頭の候補から動かすと候補ウィンドウを開く。
+    browsed: bool,
 }
 
 impl CandidateList {
     /// 候補リストを作成する。初期選択は先頭。
-    pub fn new(candidates: Vec<String>) -> Self {
+    pub fn new(candidates: Vec<Candidate>) -> Self {
+        Self::with_page_size(candidates, DEFAULT_PAGE_SIZE)
+    }
+
+    /// 1ページあたりの候補数を指定して候補リストを作成する。
+    pub fn with_page_size(candidates: Vec<Candidate>, page_size: usize) -> Self {
         Self {
             candidates,
             index: 0,
+            page_size: page_size.max(1),
+            browsed: false,
         }
     }
 
-    /// 現在選択中の候補を返す。
+    /// 現在選択中の候補の文字列を返す。
     pub fn current(&self) -> Option<&str> {
-        self.candidates.get(self.index).map(|s| s.as_str())
+        self.current_candidate().map(|c| c.text.as_str())
+    }
+
+    /// 現在選択中の候補を返す。
+    pub fn current_candidate(&self) -> Option<&Candidate> {
+        self.candidates.get(self.index)
     }
 
     /// 現在の選択インデックスを返す。
@@ -27,8 +129,14 @@ impl CandidateList {
         self.index
     }
 
+    /// 候補を移動して、候補ウィンドウを開いているか。
+    pub fn is_browsed(&self) -> bool {
+        self.browsed
+    }
+
     /// 次の候補に移動する。末尾の場合は先頭にラップする。
     pub fn next(&mut self) {
+        self.browsed = true;
         if !self.candidates.is_empty() {
             self.index = (self.index + 1) % self.candidates.len();
         }
@@ -36,6 +144,7 @@ impl CandidateList {
 
     /// 前の候補に移動する。先頭の場合は末尾にラップする。
     pub fn prev(&mut self) {
+        self.browsed = true;
         if !self.candidates.is_empty() {
             if self.index == 0 {
                 self.index = self.candidates.len() - 1;
@@ -61,24 +170,95 @@ impl CandidateList {
     }
 
     /// 全候補のスライスを返す。
-    pub fn candidates(&self) -> &[String] {
+    pub fn candidates(&self) -> &[Candidate] {
         &self.candidates
     }
+
+    // === ページ ===
+
+    /// 1ページあたりの候補数を返す。
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// 現在のページ番号（0 始まり）を返す。
+    pub fn page_index(&self) -> usize {
+        self.index / self.page_size
+    }
+
+    /// 総ページ数を返す。候補が空の場合は 0。
+    pub fn page_count(&self) -> usize {
+        self.candidates.len().div_ceil(self.page_size)
+    }
+
+    /// 現在のページの先頭の候補のインデックスを返す。
+    fn page_start(&self) -> usize {
+        self.page_index() * self.page_size
+    }
+
+    /// 現在のページの候補を返す。
+    pub fn page(&self) -> &[Candidate] {
+        let start = self.page_start().min(self.candidates.len());
+        let end = (start + self.page_size).min(self.candidates.len());
+        &self.candidates[start..end]
+    }
+
+    /// 次のページの先頭に移動する。最終ページの場合は先頭ページにラップする。
+    pub fn next_page(&mut self) {
+        self.browsed = true;
+        let count = self.page_count();
+        if count > 0 {
+            self.index = ((self.page_index() + 1) % count) * self.page_size;
+        }
+    }
+
+    /// 前のページの先頭に移動する。先頭ページの場合は最終ページにラップする。
+    pub fn prev_page(&mut self) {
+        self.browsed = true;
+        let count = self.page_count();
+        if count > 0 {
+            self.index = ((self.page_index() + count - 1) % count) * self.page_size;
+        }
+    }
+
+    /// 現在のページ内の位置で候補を選択する。範囲外なら false を返し何もしない。
+    pub fn select_on_page(&mut self, position: usize) -> bool {
+        if position >= self.page().len() {
+            return false;
+        }
+        self.index = self.page_start() + position;
+        true
+    }
+
+    /// 現在のページの表示情報を返す。ラベルはページ内の候補数だけ割り当てる。
+    pub fn current_page(&self, labels: &[char]) -> CandidatePage {
+        let candidates = self.page().to_vec();
+        CandidatePage {
+            labels: labels.iter().take(candidates.len()).copied().collect(),
+            candidates,
+            selected: self.index - self.page_start(),
+            page_index: self.page_index(),
+            page_count: self.page_count(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn texts(texts: &[&str]) -> Vec<Candidate> {
+        texts
+            .iter()
+            .map(|t| Candidate::new(t, "", CandidateSource::SystemDictionary))
+            .collect()
+    }
+
     // === 基本操作 ===
 
     #[test]
     fn new_with_candidates() {
-        let cl = CandidateList::new(vec![
-            "漢字".to_string(),
-            "感じ".to_string(),
-            "幹事".to_string(),
-        ]);
+        let cl = CandidateList::new(texts(&["漢字", "感じ", "幹事"]));
         assert_eq!(cl.current(), Some("漢字"));
         assert_eq!(cl.index(), 0);
         assert_eq!(cl.len(), 3);
@@ -96,11 +276,7 @@ mod tests {
 
     #[test]
     fn next_moves_forward() {
-        let mut cl = CandidateList::new(vec![
-            "漢字".to_string(),
-            "感じ".to_string(),
-            "幹事".to_string(),
-        ]);
+        let mut cl = CandidateList::new(texts(&["漢字", "感じ", "幹事"]));
         cl.next();
         assert_eq!(cl.current(), Some("感じ"));
         assert_eq!(cl.index(), 1);
@@ -108,11 +284,7 @@ mod tests {
 
     #[test]
     fn prev_moves_backward() {
-        let mut cl = CandidateList::new(vec![
-            "漢字".to_string(),
-            "感じ".to_string(),
-            "幹事".to_string(),
-        ]);
+        let mut cl = CandidateList::new(texts(&["漢字", "感じ", "幹事"]));
         cl.next();
         cl.next();
         cl.prev();
@@ -124,7 +296,7 @@ mod tests {
 
     #[test]
     fn next_at_end_wraps() {
-        let mut cl = CandidateList::new(vec!["漢字".to_string(), "感じ".to_string()]);
+        let mut cl = CandidateList::new(texts(&["漢字", "感じ"]));
         cl.next(); // index=1
         cl.next(); // index=0 (ラップ)
         assert_eq!(cl.current(), Some("漢字"));
@@ -133,7 +305,7 @@ mod tests {
 
     #[test]
     fn prev_at_start_wraps() {
-        let mut cl = CandidateList::new(vec!["漢字".to_string(), "感じ".to_string()]);
+        let mut cl = CandidateList::new(texts(&["漢字", "感じ"]));
         cl.prev(); // index=1 (ラップ)
         assert_eq!(cl.current(), Some("感じ"));
         assert_eq!(cl.index(), 1);
@@ -157,7 +329,7 @@ mod tests {
 
     #[test]
     fn select_returns_current() {
-        let mut cl = CandidateList::new(vec!["漢字".to_string(), "感じ".to_string()]);
+        let mut cl = CandidateList::new(texts(&["漢字", "感じ"]));
         cl.next();
         assert_eq!(cl.select(), Some("感じ".to_string()));
     }
@@ -172,7 +344,113 @@ mod tests {
 
     #[test]
     fn candidates_returns_all() {
-        let cl = CandidateList::new(vec!["漢字".to_string(), "感じ".to_string()]);
+        let cl = CandidateList::new(texts(&["漢字", "感じ"]));
         assert_eq!(cl.candidates(), &["漢字", "感じ"]);
     }
+
+    // === ページ ===
+
+    fn numbered(n: usize) -> Vec<Candidate> {
+        (1..=n)
+            .map(|i| Candidate::new(&format!("候補{i}"), "", CandidateSource::SystemDictionary))
+            .collect()
+    }
+
+    #[test]
+    fn page_count_and_first_page() {
+        let cl = CandidateList::with_page_size(numbered(12), 5);
+        assert_eq!(cl.page_count(), 3);
+        assert_eq!(cl.page_index(), 0);
+        assert_eq!(cl.page(), &numbered(5)[..]);
+    }
+
+    #[test]
+    fn page_follows_next() {
+        let mut cl = CandidateList::with_page_size(numbered(12), 5);
+        for _ in 0..5 {
+            cl.next();
+        }
+        assert_eq!(cl.page_index(), 1);
+        assert_eq!(cl.page()[0], "候補6");
+    }
+
+    #[test]
+    fn next_page_and_wrap() {
+        let mut cl = CandidateList::with_page_size(numbered(12), 5);
+        cl.next_page();
+        assert_eq!(cl.current(), Some("候補6"));
+        cl.next_page();
+        assert_eq!(cl.current(), Some("候補11"));
+        // 最終ページは2件のみ
+        assert_eq!(cl.page().len(), 2);
+        cl.next_page();
+        assert_eq!(cl.current(), Some("候補1"));
+    }
+
+    #[test]
+    fn prev_page_wraps_to_last() {
+        let mut cl = CandidateList::with_page_size(numbered(12), 5);
+        cl.prev_page();
+        assert_eq!(cl.page_index(), 2);
+        assert_eq!(cl.current(), Some("候補11"));
+    }
+
+    #[test]
+    fn select_on_page() {
+        let mut cl = CandidateList::with_page_size(numbered(12), 5);
+        cl.next_page();
+        assert!(cl.select_on_page(1));
+        assert_eq!(cl.current(), Some("候補7"));
+        // 範囲外は無視
+        cl.next_page();
+        assert!(!cl.select_on_page(3));
+        assert_eq!(cl.current(), Some("候補11"));
+    }
+
+    #[test]
+    fn current_page_with_labels() {
+        let mut cl = CandidateList::with_page_size(numbered(7), 4);
+        cl.next_page();
+        cl.next();
+        let page = cl.current_page(&['a', 's', 'd', 'f']);
+        assert_eq!(page.candidates, vec!["候補5", "候補6", "候補7"]);
+        assert_eq!(page.labels, vec!['a', 's', 'd']);
+        assert_eq!(page.selected, 1);
+        assert_eq!(page.page_index, 1);
+        assert_eq!(page.page_count, 2);
+    }
+
+    #[test]
+    fn empty_list_pages() {
+        let mut cl = CandidateList::new(vec![]);
+        assert_eq!(cl.page_count(), 0);
+        assert!(cl.page().is_empty());
+        cl.next_page();
+        cl.prev_page();
+        assert!(!cl.select_on_page(0));
+    }
+
+    // === Candidate ===
+
+    #[test]
+    fn candidate_with_annotation() {
+        let c = Candidate::new("日本", "にほん", CandidateSource::SystemDictionary)
+            .with_annotation(Some("country"));
+        assert_eq!(c.text, "日本");
+        assert_eq!(c.reading, "にほん");
+        assert_eq!(c.annotation.as_deref(), Some("country"));
+        assert_eq!(c.score, 0);
+        assert_eq!(c, "日本");
+    }
+
+    #[test]
+    fn current_candidate_keeps_source() {
+        let mut cl = CandidateList::new(vec![
+            Candidate::new("漢字", "かんじ", CandidateSource::UserDictionary),
+            Candidate::new("感じ", "かんじ", CandidateSource::SystemDictionary),
+        ]);
+        cl.next();
+        let c = cl.current_candidate().unwrap();
+        assert_eq!(c.source, CandidateSource::SystemDictionary);
+    }
 }
@@ -5,8 +5,19 @@
 
 use std::path::Path;
 
-use crate::engine::EngineCommand;
+use crate::calculator::{CALCULATOR_PRIORITY, Calculator};
+use crate::context_priority::ContextPriority;
+use crate::date_provider::{DATE_PRIORITY, DateFormats, DateProvider, Era};
+use crate::engine::{
+    ConversionEngine, EngineCommand, EngineOptions, ImeOffAction, InputStyle, NUMBER_LABELS,
+    SKK_LABELS,
+};
+pub use crate::key_mapping::ToggleKey;
 use crate::key_mapping::{CtrlKeyConfig, KeybindPreset};
+use crate::provider::MergeRule;
+use crate::symbol_dict::{SYMBOL_PRIORITY, SymbolDictionary};
+use crate::typo_correction::TypoCorrection;
+use crate::user_dictionary::{UserDictLimits, UserDictionary};
 
 /// 設定エラー。
 #[derive(Debug)]
@@ -32,20 +43,75 @@ impl std::fmt::Display for ConfigError {
     }
 }
 
-/// 入力モード切り替えキー。
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum ToggleKey {
-    ZenkakuHankaku,
-    CtrlSpace,
-    AltTilde,
+/// 候補の選択ラベルの種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateLabelStyle {
+    /// `1`〜`9`（MS-IME 風）
+    Number,
+    /// `a`, `s`, `d`, `f`, `j`, `k`, `l`（SKK 風）
+    Skk,
+}
+
+impl CandidateLabelStyle {
+    /// ラベル文字の並びを返す。
+    pub fn labels(&self) -> &'static str {
+        match self {
+            CandidateLabelStyle::Number => NUMBER_LABELS,
+            CandidateLabelStyle::Skk => SKK_LABELS,
+        }
+    }
 }
 
 /// アプリケーション設定。
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Config {
     pub toggle_key: ToggleKey,
+    pub input_style: InputStyle,
+    /// abbrev モードを始めるキー（None なら使わない）
+    pub abbrev_key: Option<char>,
     pub system_dict_path: Option<String>,
+    /// コンテキスト優先テーブルのパス（`[context_priority]` の `file`）
+    pub context_priority_path: Option<String>,
+    /// 提供元の名前ごとのマージ規則（`[merge]` セクション）
+    pub merge_rules: Vec<(String, MergeRule)>,
+    /// 記号・絵文字・顔文字の辞書を使うか
+    pub symbol_dict: bool,
+    /// 記号辞書のパス（None なら同梱の辞書）
+    pub symbol_dict_path: Option<String>,
+    /// 記号辞書の絵文字の候補を出すか
+    pub emoji: bool,
+    /// ローマ字の打ち間違いを補正した読みの候補を出すか
+    pub typo_correction: bool,
+    /// タイポ補正テーブルのパス（None なら同梱のテーブル）
+    pub typo_corrections_path: Option<String>,
+    /// スニペットのファイルのパス（None なら設定ディレクトリの snippets.txt）
+    pub snippets_path: Option<String>,
+    /// ユーザー辞書に保持する読みの上限（0 は無制限）
+    pub user_dict_max_readings: usize,
+    /// ユーザー辞書の1つの読みあたりの候補数の上限（0 は無制限）
+    pub user_dict_max_candidates: usize,
     pub auto_learn: bool,
+    /// IME をオフにするときに入力中の内容を確定するか破棄するか
+    pub ime_off_action: ImeOffAction,
+    /// 「、」「。」の入力で、その前までを変換して確定するか
+    pub auto_commit_on_punctuation: bool,
+    /// 入力中の読みを変換して表示するか（ライブ変換）
+    pub live_conversion: bool,
+    /// 候補ウィンドウの1ページあたりの候補数（ラベルの数が上限）
+    pub candidate_page_size: usize,
+    pub candidate_label_style: CandidateLabelStyle,
+    /// 入力中に予測候補を出すか
+    pub prediction: bool,
+    /// 予測候補を探すときに走査する辞書の読みの数の上限
+    pub prediction_scan_limit: usize,
+    /// 確定直後に次の語の候補を出すか
+    pub next_word_suggestion: bool,
+    /// 「=」で終わる計算式を変換したとき計算結果を候補に出すか
+    pub calculator: bool,
+    /// 「きょう」「いま」などで日付・時刻の候補を出すか
+    pub date_candidates: bool,
+    /// 日付・時刻の候補の書式・時差・元号
+    pub date_formats: DateFormats,
     pub keybind_preset: KeybindPreset,
     pub keybind: CtrlKeyConfig,
 }
@@ -55,8 +121,31 @@ impl Config {
     pub fn default_config() -> Self {
         Self {
             toggle_key: ToggleKey::ZenkakuHankaku,
+            input_style: InputStyle::Standard,
+            abbrev_key: None,
             system_dict_path: None,
+            context_priority_path: None,
+            merge_rules: Vec::new(),
+            symbol_dict: true,
+            symbol_dict_path: None,
+            emoji: true,
+            typo_correction: true,
+            typo_corrections_path: None,
+            snippets_path: None,
+            user_dict_max_readings: 0,
+            user_dict_max_candidates: 0,
             auto_learn: true,
+            ime_off_action: ImeOffAction::Commit,
+            auto_commit_on_punctuation: false,
+            live_conversion: false,
+            candidate_page_size: 9,
+            candidate_label_style: CandidateLabelStyle::Number,
+            prediction: true,
+            prediction_scan_limit: 200,
+            next_word_suggestion: true,
+            calculator: true,
+            date_candidates: true,
+            date_formats: DateFormats::default(),
             keybind_preset: KeybindPreset::None,
             keybind: CtrlKeyConfig::default(),
         }
@@ -75,10 +164,16 @@ impl Config {
     pub fn parse(text: &str) -> Result<Self, ConfigError> {
         let mut config = Self::default_config();
         let mut keybind_overrides: Vec<(&str, &str)> = Vec::new();
+        let mut abbrev_key = None;
+        let mut section = "";
 
         for line in text.lines() {
             let line = line.trim();
-            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                section = name.trim_end_matches(']').trim();
                 continue;
             }
             let Some((key, value)) = line.split_once('=') else {
@@ -87,9 +182,29 @@ impl Config {
             let key = key.trim();
             let value = value.trim().trim_matches('"');
             match key {
+                name if section == "merge" => {
+                    config
+                        .merge_rules
+                        .push((name.to_string(), parse_merge_rule(name, value)?));
+                }
                 "toggle_key" => {
                     config.toggle_key = parse_toggle_key(value)?;
                 }
+                "input_style" => {
+                    config.input_style = parse_input_style(value)?;
+                }
+                "abbrev_key" => {
+                    abbrev_key = Some(parse_abbrev_key(value)?);
+                }
+                "ime_off_action" => {
+                    config.ime_off_action = parse_ime_off_action(value)?;
+                }
+                "auto_commit_on_punctuation" => {
+                    config.auto_commit_on_punctuation = value == "true";
+                }
+                "live_conversion" => {
+                    config.live_conversion = value == "true";
+                }
                 "system_dict_path" => {
                     config.system_dict_path = if value.is_empty() {
                         None
@@ -97,9 +212,97 @@ impl Config {
                         Some(value.to_string())
                     };
                 }
+                "file" if section == "context_priority" => {
+                    config.context_priority_path = if value.is_empty() {
+                        None
+                    } else {
+                        Some(value.to_string())
+                    };
+                }
+                "symbol_dict" => {
+                    config.symbol_dict = value == "true";
+                }
+                "symbol_dict_path" => {
+                    config.symbol_dict_path = if value.is_empty() {
+                        None
+                    } else {
+                        Some(value.to_string())
+                    };
+                }
+                "emoji" => {
+                    config.emoji = value == "true";
+                }
+                "typo_correction" => {
+                    config.typo_correction = value == "true";
+                }
+                "snippets_path" => {
+                    config.snippets_path = if value.is_empty() {
+                        None
+                    } else {
+                        Some(value.to_string())
+                    };
+                }
+                "typo_corrections_path" => {
+                    config.typo_corrections_path = if value.is_empty() {
+                        None
+                    } else {
+                        Some(value.to_string())
+                    };
+                }
+                "user_dict_max_readings" => {
+                    config.user_dict_max_readings = parse_usize(key, value)?;
+                }
+                "user_dict_max_candidates" => {
+                    config.user_dict_max_candidates = parse_usize(key, value)?;
+                }
                 "auto_learn" => {
                     config.auto_learn = value == "true";
                 }
+                "candidate_page_size" => {
+                    config.candidate_page_size = parse_usize(key, value)?;
+                    if config.candidate_page_size == 0 {
+                        return Err(ConfigError::Parse(
+                            "candidate_page_size には 1 以上を指定してください".to_string(),
+                        ));
+                    }
+                }
+                "candidate_labels" => {
+                    config.candidate_label_style = parse_label_style(value)?;
+                }
+                "prediction" => {
+                    config.prediction = value == "true";
+                }
+                "prediction_scan_limit" => {
+                    config.prediction_scan_limit = parse_usize(key, value)?;
+                }
+                "next_word_suggestion" => {
+                    config.next_word_suggestion = value == "true";
+                }
+                "calculator" => {
+                    config.calculator = value == "true";
+                }
+                "date_candidates" => {
+                    config.date_candidates = value == "true";
+                }
+                "date_formats" => {
+                    config.date_formats.date = split_list(value);
+                }
+                "time_formats" => {
+                    config.date_formats.time = split_list(value);
+                }
+                "utc_offset" => {
+                    config.date_formats.utc_offset_minutes = parse_utc_offset(value)?;
+                }
+                "eras" => {
+                    for item in split_list(value) {
+                        let era = Era::parse(&item).ok_or_else(|| {
+                            ConfigError::Parse(format!(
+                                "不正な元号: {item} (名前:YYYY-MM-DD の形式)"
+                            ))
+                        })?;
+                        config.date_formats.eras.push(era);
+                    }
+                }
                 "keybind_preset" => {
                     config.keybind_preset = parse_preset(value)?;
                 }
@@ -110,11 +313,23 @@ impl Config {
             }
         }
 
-        // プリセットからベースを生成し、個別の上書きを適用
+        // abbrev モードは SKK 方式でだけ既定で `/` から始める。
+        // 通常の入力方式では `/` をそのまま入力できるよう、指定がなければ使わない
+        config.abbrev_key = abbrev_key.unwrap_or(match config.input_style {
+            InputStyle::Skk => Some('/'),
+            InputStyle::Standard => None,
+        });
+
+        // プリセットからベースを生成し、個別の上書きを適用。
+        // SKK 方式では Ctrl+J をかなモードへの切り替え（確定を兼ねる）にする
         config.keybind = CtrlKeyConfig::from_preset(&config.keybind_preset);
+        if config.input_style == InputStyle::Skk {
+            config.keybind.ctrl_j = Some(EngineCommand::KanaMode);
+        }
         for (key, value) in keybind_overrides {
             let cmd = parse_command(value)?;
             match key {
+                "ctrl_backspace" => config.keybind.ctrl_backspace = cmd,
                 "ctrl_g" => config.keybind.ctrl_g = cmd,
                 "ctrl_h" => config.keybind.ctrl_h = cmd,
                 "ctrl_j" => config.keybind.ctrl_j = cmd,
@@ -128,6 +343,104 @@ impl Config {
         Ok(config)
     }
 
+    /// ユーザー辞書の上限を返す。0 は無制限として扱う。
+    pub fn user_dict_limits(&self) -> UserDictLimits {
+        let non_zero = |n: usize| if n == 0 { None } else { Some(n) };
+        UserDictLimits {
+            max_candidates_per_reading: non_zero(self.user_dict_max_candidates),
+            max_readings: non_zero(self.user_dict_max_readings),
+        }
+    }
+
+    /// 変換エンジンの動作設定を返す。
+    pub fn engine_options(&self) -> EngineOptions {
+        EngineOptions {
+            page_size: self.candidate_page_size,
+            candidate_labels: self.candidate_label_style.labels().chars().collect(),
+            prediction: self.prediction,
+            prediction_scan_limit: self.prediction_scan_limit,
+            next_word_suggestion: self.next_word_suggestion,
+            ime_off_action: self.ime_off_action,
+            input_style: self.input_style,
+            abbrev_key: self.abbrev_key,
+            auto_commit_on_punctuation: self.auto_commit_on_punctuation,
+            live_conversion: self.live_conversion,
+            emoji: self.emoji,
+        }
+    }
+
+    /// 記号辞書を返す。無効、またはファイルを読み込めなければ None。
+    pub fn symbol_dictionary(&self) -> Option<SymbolDictionary> {
+        if !self.symbol_dict {
+            return None;
+        }
+        let dict = match self.symbol_dict_path {
+            Some(ref path) => SymbolDictionary::load(std::path::Path::new(path)).ok()?,
+            None => SymbolDictionary::bundled(),
+        };
+        Some(dict)
+    }
+
+    /// コンテキスト優先テーブルを返す。指定がない、またはファイルを読み込めなければ None。
+    pub fn context_priority(&self) -> Option<ContextPriority> {
+        let path = self.context_priority_path.as_ref()?;
+        ContextPriority::load_from_file(Path::new(path)).ok()
+    }
+
+    /// タイポ補正テーブルを返す。無効、またはファイルを読み込めなければ None。
+    pub fn typo_correction_table(&self) -> Option<TypoCorrection> {
+        if !self.typo_correction {
+            return None;
+        }
+        match self.typo_corrections_path {
+            Some(ref path) => TypoCorrection::load_from_file(std::path::Path::new(path)).ok(),
+            None => Some(TypoCorrection::bundled()),
+        }
+    }
+
+    /// 日付・時刻の候補の提供元を返す。無効なら None。
+    pub fn date_provider(&self) -> Option<DateProvider> {
+        self.date_candidates
+            .then(|| DateProvider::new(self.date_formats.clone()))
+    }
+
+    /// 設定に従って変換エンジンの動作設定・テーブル・候補の提供元を組み立てる。
+    ///
+    /// 読み込めないファイルは使わない。スニペットはファイルの既定の場所が
+    /// フロントエンドごとに異なるため、呼び出し側で追加する。
+    pub fn configure_engine(&self, engine: &mut ConversionEngine) {
+        engine.set_options(self.engine_options());
+        for (name, rule) in &self.merge_rules {
+            engine.set_merge_rule(name, *rule);
+        }
+        if let Some(table) = self.context_priority() {
+            engine.set_context_priority(table);
+        }
+        if let Some(table) = self.typo_correction_table() {
+            engine.set_typo_correction(table);
+        }
+        if let Some(symbols) = self.symbol_dictionary() {
+            engine.add_provider(Box::new(symbols), MergeRule::new(SYMBOL_PRIORITY));
+        }
+        if self.calculator {
+            engine.add_provider(Box::new(Calculator), MergeRule::new(CALCULATOR_PRIORITY));
+        }
+        if let Some(provider) = self.date_provider() {
+            engine.add_provider(Box::new(provider), MergeRule::new(DATE_PRIORITY));
+        }
+        if let Some(ud) = engine.user_dict_mut() {
+            self.configure_user_dictionary(ud);
+        }
+    }
+
+    /// ユーザー辞書に上限などの設定を適用する。
+    ///
+    /// `configure_engine` からも呼ばれる。エンジンを使わずにユーザー辞書を
+    /// 編集する場合（`userdict import` など）はこれを直接呼ぶ。
+    pub fn configure_user_dictionary(&self, ud: &mut UserDictionary) {
+        ud.set_limits(self.user_dict_limits());
+    }
+
     /// デフォルト設定ファイルの内容を生成する。
     pub fn default_toml() -> String {
         r#"# japinput 設定ファイル
@@ -135,22 +448,94 @@ impl Config {
 [general]
 # 入力モード切り替えキー: "zenkaku-hankaku" | "ctrl-space" | "alt-tilde"
 toggle_key = "zenkaku-hankaku"
+# 入力方式: "standard" (読み全体を Space で変換) | "skk" (大文字で変換の開始を指定)
+input_style = "standard"
+# abbrev モード（英字をそのまま読みとして変換）を始めるキー。"" で無効
+# 既定は SKK 方式では "/"、通常の入力方式では無効
+# abbrev_key = "/"
 # キーバインドプリセット: "none" | "minimal" | "emacs"
 keybind_preset = "none"
 
 [dictionary]
 # システム辞書パス（空の場合は DLL と同じディレクトリの dict/ を使用）
 system_dict_path = ""
+# ユーザー辞書の上限（0 は無制限）。単語を記録したときに、超えた分を
+# 最も長く使われていないものから削除する。固定 (pin) された候補は削除されない。
+user_dict_max_readings = 0
+user_dict_max_candidates = 0
+# 記号・絵文字・顔文字の辞書を使うか
+symbol_dict = true
+# 記号辞書のパス（空の場合は同梱の辞書を使用）
+symbol_dict_path = ""
+# 絵文字の候補を出すか（絵文字を表示できないアプリ向けに無効化できる）
+emoji = true
+# 入力どおりの読みに候補がないとき、ローマ字の打ち間違いを補正した読みを試すか
+typo_correction = true
+# タイポ補正テーブルのパス（空の場合は同梱のテーブルを使用）
+# 形式: 誤入力<TAB>正しい入力
+typo_corrections_path = ""
+# スニペット（定型文）のファイルのパス（空の場合は設定ファイルと同じディレクトリの snippets.txt）
+# 形式: 読み<TAB>本文<TAB>説明(省略可)。本文の \n は改行
+snippets_path = ""
+
+[context_priority]
+# コンテキスト優先テーブルのパス（空の場合は使用しない）
+# 形式: キーワード(カンマ区切り)<TAB>読み<TAB>優先候補(カンマ区切り)
+file = ""
+
+[merge]
+# 候補の提供元ごとのマージ規則: 名前 = "優先度" または "優先度,最大件数"
+# 優先度の大きい提供元の候補から並ぶ。既定の優先度:
+# snippet 220, calculator 210, user (ユーザー辞書) 200, system (システム辞書) 100,
+# date 90, symbol 80
+# system = "100"
+# symbol = "80,10"
 
 [behavior]
 # 候補選択後に自動的に学習するか
 auto_learn = true
+# IME をオフにするときの入力中の内容: "commit" (確定) | "cancel" (破棄)
+ime_off_action = "commit"
+# 「、」「。」を入力したとき、その前までを先頭の候補で変換して句読点と一緒に確定するか
+auto_commit_on_punctuation = false
+# 入力中の読みを先頭の候補で変換して表示し、Enter でそのまま確定するか（ライブ変換）
+live_conversion = false
+# 「=」で終わる計算式を変換したとき計算結果を候補に出すか
+calculator = true
+
+[candidate]
+# 候補ウィンドウの1ページあたりの候補数（ラベルの数を超える分は無視）
+candidate_page_size = 9
+# 候補の選択ラベル: "number" (1〜9) | "skk" (asdfjkl)
+candidate_labels = "number"
+
+[prediction]
+# 入力中に読みの続きを予測した候補を出すか（Tab で選択）
+prediction = true
+# 予測候補を探すときに走査する辞書の読みの数の上限
+prediction_scan_limit = 200
+# 確定直後に、続けて確定したことのある語を候補に出すか（Tab で選択）
+next_word_suggestion = true
+
+[date]
+# 「きょう」「あした」「いま」などで日付・時刻の候補を出すか
+date_candidates = true
+# 書式（| 区切り）: {year} {month} {month2} {day} {day2} {weekday} {era} {era_year}
+date_formats = "{year}-{month2}-{day2}|{year}/{month2}/{day2}|{year}年{month}月{day}日|{era}{era_year}年{month}月{day}日({weekday})"
+# 書式（| 区切り）: {hour} {hour2} {minute} {minute2} {second2}
+time_formats = "{hour2}:{minute2}|{hour}時{minute}分"
+# UTC からの時差
+utc_offset = "+09:00"
+# 追加の元号（| 区切り、名前:開始日）
+# eras = "新元号:2040-01-01"
 
 # [keybind]
 # プリセットをベースに個別のキーを上書きする。
-# 値: commit, cancel, next, prev, backspace, convert, none
+# 値: commit, cancel, next, prev, next_page, prev_page, predict, backspace, convert, kana_mode,
+#     undo_commit, none
 # ctrl_j = "commit"
 # ctrl_g = "cancel"
+# ctrl_backspace = "undo_commit"
 "#
         .to_string()
     }
@@ -165,6 +550,104 @@ fn parse_toggle_key(value: &str) -> Result<ToggleKey, ConfigError> {
     }
 }
 
+fn parse_input_style(value: &str) -> Result<InputStyle, ConfigError> {
+    match value {
+        "standard" => Ok(InputStyle::Standard),
+        "skk" => Ok(InputStyle::Skk),
+        _ => Err(ConfigError::Parse(format!(
+            "不正な input_style: {value} (standard, skk のいずれか)"
+        ))),
+    }
+}
+
+/// `|` 区切りのリストを分割する。空の要素は除く。
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split('|')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `+09:00` 形式の時差を分にする。
+fn parse_utc_offset(value: &str) -> Result<i64, ConfigError> {
+    let error = || {
+        ConfigError::Parse(format!(
+            "utc_offset は +HH:MM の形式で指定してください: {value}"
+        ))
+    };
+    let (sign, rest) = match value.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(error()),
+    };
+    let (hours, minutes) = rest.split_once(':').ok_or_else(error)?;
+    let hours: i64 = hours.parse().map_err(|_| error())?;
+    let minutes: i64 = minutes.parse().map_err(|_| error())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(error());
+    }
+    Ok(sign * (hours * 60 + minutes))
+}
+
+fn parse_abbrev_key(value: &str) -> Result<Option<char>, ConfigError> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Ok(None),
+        (Some(ch), None) if ch.is_ascii_graphic() => Ok(Some(ch)),
+        _ => Err(ConfigError::Parse(format!(
+            "abbrev_key には1文字を指定してください: {value}"
+        ))),
+    }
+}
+
+fn parse_ime_off_action(value: &str) -> Result<ImeOffAction, ConfigError> {
+    match value {
+        "commit" => Ok(ImeOffAction::Commit),
+        "cancel" => Ok(ImeOffAction::Cancel),
+        _ => Err(ConfigError::Parse(format!(
+            "不正な ime_off_action: {value} (commit, cancel のいずれか)"
+        ))),
+    }
+}
+
+fn parse_usize(key: &str, value: &str) -> Result<usize, ConfigError> {
+    value.parse().map_err(|_| {
+        ConfigError::Parse(format!(
+            "{key} には 0 以上の整数を指定してください: {value}"
+        ))
+    })
+}
+
+/// マージ規則 `"優先度"` または `"優先度,最大件数"` をパースする。
+fn parse_merge_rule(name: &str, value: &str) -> Result<MergeRule, ConfigError> {
+    let invalid = || {
+        ConfigError::Parse(format!(
+            "不正なマージ規則: {name} = {value} (\"優先度\" または \"優先度,最大件数\")"
+        ))
+    };
+    let (priority, max_count) = match value.split_once(',') {
+        Some((p, m)) => (p, Some(m)),
+        None => (value, None),
+    };
+    let rule = MergeRule::new(priority.trim().parse().map_err(|_| invalid())?);
+    match max_count {
+        Some(m) => Ok(rule.with_max_count(m.trim().parse().map_err(|_| invalid())?)),
+        None => Ok(rule),
+    }
+}
+
+fn parse_label_style(value: &str) -> Result<CandidateLabelStyle, ConfigError> {
+    match value {
+        "number" => Ok(CandidateLabelStyle::Number),
+        "skk" => Ok(CandidateLabelStyle::Skk),
+        _ => Err(ConfigError::Parse(format!(
+            "不正な candidate_labels: {value} (number, skk のいずれか)"
+        ))),
+    }
+}
+
 fn parse_preset(value: &str) -> Result<KeybindPreset, ConfigError> {
     match value {
         "none" => Ok(KeybindPreset::None),
@@ -182,8 +665,13 @@ fn parse_command(value: &str) -> Result<Option<EngineCommand>, ConfigError> {
         "cancel" => Ok(Some(EngineCommand::Cancel)),
         "next" => Ok(Some(EngineCommand::NextCandidate)),
         "prev" => Ok(Some(EngineCommand::PrevCandidate)),
+        "next_page" => Ok(Some(EngineCommand::NextPage)),
+        "predict" => Ok(Some(EngineCommand::Predict)),
+        "prev_page" => Ok(Some(EngineCommand::PrevPage)),
         "backspace" => Ok(Some(EngineCommand::Backspace)),
         "convert" => Ok(Some(EngineCommand::Convert)),
+        "kana_mode" => Ok(Some(EngineCommand::KanaMode)),
+        "undo_commit" => Ok(Some(EngineCommand::UndoCommit)),
         "none" => Ok(None),
         _ => Err(ConfigError::Parse(format!("不正なコマンド名: {value}"))),
     }
@@ -203,6 +691,8 @@ mod tests {
         assert!(config.auto_learn);
         assert_eq!(config.keybind_preset, KeybindPreset::None);
         assert_eq!(config.keybind, CtrlKeyConfig::default());
+        assert_eq!(config.user_dict_max_readings, 0);
+        assert_eq!(config.user_dict_max_candidates, 0);
     }
 
     // === TOML パース ===
@@ -418,4 +908,327 @@ ctrl_j = "commit"
         assert_eq!(config.keybind.ctrl_j, Some(EngineCommand::Commit));
         assert_eq!(config.keybind.ctrl_g, None);
     }
+
+    // === コンテキスト優先テーブル ===
+
+    #[test]
+    fn parse_context_priority_file() {
+        let toml = r#"
+[context_priority]
+file = "C:\dict\context_priority.txt"
+"#;
+        let config = Config::parse(toml).unwrap();
+        assert_eq!(
+            config.context_priority_path,
+            Some(r"C:\dict\context_priority.txt".to_string())
+        );
+        assert_eq!(Config::default_config().context_priority_path, None);
+        // 他のセクションの file は無視する
+        let config = Config::parse("[other]\nfile = \"x.txt\"").unwrap();
+        assert_eq!(config.context_priority_path, None);
+    }
+
+    #[test]
+    fn configure_engine_loads_context_priority() {
+        use crate::dictionary::Dictionary;
+        let path = std::env::temp_dir().join("japinput_config_context_priority.txt");
+        std::fs::write(&path, "会議\tかんじ\t幹事\n").unwrap();
+        let toml = format!("[context_priority]\nfile = \"{}\"", path.display());
+        let config = Config::parse(&toml).unwrap();
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine = ConversionEngine::new(Some(dict));
+        config.configure_engine(&mut engine);
+        std::fs::remove_file(&path).ok();
+
+        let mut convert = |romaji: &str| {
+            for ch in romaji.chars() {
+                engine.process(EngineCommand::InsertChar(ch));
+            }
+            engine.process(EngineCommand::Convert);
+            engine.process(EngineCommand::Commit).committed
+        };
+        assert_eq!(convert("kaigi"), "会議");
+        assert_eq!(convert("kanji"), "幹事");
+    }
+
+    // === マージ規則 ===
+
+    #[test]
+    fn parse_merge_rules() {
+        let toml = r#"
+[merge]
+system = "250"
+symbol = "80, 3"
+"#;
+        let config = Config::parse(toml).unwrap();
+        assert_eq!(
+            config.merge_rules,
+            vec![
+                ("system".to_string(), MergeRule::new(250)),
+                ("symbol".to_string(), MergeRule::new(80).with_max_count(3)),
+            ]
+        );
+        assert!(Config::default_config().merge_rules.is_empty());
+        assert!(Config::parse("[merge]\nsystem = \"high\"").is_err());
+        assert!(Config::parse("[merge]\nsystem = \"1,x\"").is_err());
+    }
+
+    // === ユーザー辞書の上限 ===
+
+    #[test]
+    fn parse_user_dict_limits() {
+        let toml = r#"
+[dictionary]
+user_dict_max_readings = 100
+user_dict_max_candidates = 0
+"#;
+        let config = Config::parse(toml).unwrap();
+        assert_eq!(config.user_dict_max_readings, 100);
+        let limits = config.user_dict_limits();
+        assert_eq!(limits.max_readings, Some(100));
+        // 0 は無制限
+        assert_eq!(limits.max_candidates_per_reading, None);
+    }
+
+    #[test]
+    fn parse_user_dict_limits_invalid_errors() {
+        let toml = r#"
+[dictionary]
+user_dict_max_readings = -1
+"#;
+        assert!(Config::parse(toml).is_err());
+    }
+
+    #[test]
+    fn configure_engine_applies_user_dict_limits() {
+        let toml = r#"
+[dictionary]
+user_dict_max_readings = 1
+"#;
+        let config = Config::parse(toml).unwrap();
+        let mut engine = ConversionEngine::new_with_user_dict(None, Some(UserDictionary::new()));
+        config.configure_engine(&mut engine);
+        let ud = engine.user_dict_mut().unwrap();
+        assert_eq!(ud.limits(), config.user_dict_limits());
+        ud.record("かんじ", "漢字");
+        ud.record("にほん", "日本");
+        assert!(ud.lookup("かんじ").is_none());
+    }
+
+    #[test]
+    fn user_dict_limits_keep_pinned_words() {
+        let toml = r#"
+[dictionary]
+user_dict_max_readings = 1
+user_dict_max_candidates = 1
+"#;
+        let config = Config::parse(toml).unwrap();
+        let mut ud = UserDictionary::new();
+        ud.set_limits(config.user_dict_limits());
+        ud.pin("かいしゃ", "会社");
+        ud.pin("かんじ", "漢字");
+        for word in ["感じ", "幹事"] {
+            ud.record("かんじ", word);
+        }
+        ud.record("にほん", "日本");
+        // 上限を超えても固定された候補は残る
+        assert_eq!(ud.lookup("かいしゃ").unwrap(), &["会社"]);
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["漢字"]);
+        assert_eq!(ud.lookup("にほん").unwrap(), &["日本"]);
+    }
+
+    // === 候補ウィンドウ ===
+
+    #[test]
+    fn parse_candidate_paging() {
+        let toml = r#"
+[candidate]
+candidate_page_size = 5
+candidate_labels = "skk"
+"#;
+        let config = Config::parse(toml).unwrap();
+        assert_eq!(config.candidate_page_size, 5);
+        assert_eq!(config.candidate_label_style, CandidateLabelStyle::Skk);
+        let options = config.engine_options();
+        assert_eq!(options.page_size, 5);
+        assert_eq!(
+            options.candidate_labels,
+            vec!['a', 's', 'd', 'f', 'j', 'k', 'l']
+        );
+    }
+
+    #[test]
+    fn parse_candidate_paging_invalid_errors() {
+        assert!(Config::parse("candidate_page_size = 0").is_err());
+        assert!(Config::parse("candidate_labels = \"qwerty\"").is_err());
+    }
+
+    #[test]
+    fn parse_prediction() {
+        let toml = r#"
+[prediction]
+prediction = false
+prediction_scan_limit = 50
+next_word_suggestion = false
+"#;
+        let config = Config::parse(toml).unwrap();
+        assert!(!config.prediction);
+        assert!(!config.next_word_suggestion);
+        let options = config.engine_options();
+        assert!(!options.prediction);
+        assert_eq!(options.prediction_scan_limit, 50);
+        assert!(Config::parse("prediction_scan_limit = -1").is_err());
+    }
+
+    #[test]
+    fn parse_ime_off_action() {
+        let config = Config::parse("[behavior]\nime_off_action = \"cancel\"").unwrap();
+        assert_eq!(config.ime_off_action, ImeOffAction::Cancel);
+        assert_eq!(config.engine_options().ime_off_action, ImeOffAction::Cancel);
+        assert_eq!(
+            Config::default_config().ime_off_action,
+            ImeOffAction::Commit
+        );
+        assert!(Config::parse("ime_off_action = \"keep\"").is_err());
+    }
+
+    #[test]
+    fn parse_auto_commit_on_punctuation() {
+        assert!(!Config::default_config().auto_commit_on_punctuation);
+        let config = Config::parse("[behavior]\nauto_commit_on_punctuation = true").unwrap();
+        assert!(config.engine_options().auto_commit_on_punctuation);
+    }
+
+    #[test]
+    fn parse_live_conversion() {
+        assert!(!Config::default_config().live_conversion);
+        let config = Config::parse("[behavior]\nlive_conversion = true").unwrap();
+        assert!(config.engine_options().live_conversion);
+    }
+
+    #[test]
+    fn calculator_can_be_disabled() {
+        let convert = |config: &Config| {
+            let mut engine = ConversionEngine::new(None);
+            config.configure_engine(&mut engine);
+            for ch in "1+2=".chars() {
+                engine.process(EngineCommand::InsertChar(ch));
+            }
+            // 候補がなければ変換した時点で確定する
+            let converted = engine.process(EngineCommand::Convert).committed;
+            converted + &engine.process(EngineCommand::Commit).committed
+        };
+        assert!(Config::default_config().calculator);
+        assert_eq!(convert(&Config::default_config()), "3");
+        let config = Config::parse("[behavior]\ncalculator = false").unwrap();
+        assert_eq!(convert(&config), "1+2=");
+    }
+
+    #[test]
+    fn parse_input_style_skk() {
+        let config = Config::parse("[general]\ninput_style = \"skk\"").unwrap();
+        assert_eq!(config.input_style, InputStyle::Skk);
+        assert_eq!(config.engine_options().input_style, InputStyle::Skk);
+        assert_eq!(config.keybind.ctrl_j, Some(EngineCommand::KanaMode));
+        assert_eq!(config.keybind.ctrl_g, None);
+
+        let config = Config::parse("input_style = \"skk\"\nctrl_j = \"commit\"").unwrap();
+        assert_eq!(config.keybind.ctrl_j, Some(EngineCommand::Commit));
+
+        assert_eq!(Config::default_config().input_style, InputStyle::Standard);
+        assert!(Config::parse("input_style = \"azik\"").is_err());
+    }
+
+    #[test]
+    fn parse_abbrev_key() {
+        assert_eq!(Config::default_config().abbrev_key, None);
+        let config = Config::parse("input_style = \"skk\"").unwrap();
+        assert_eq!(config.abbrev_key, Some('/'));
+        let config = Config::parse("input_style = \"skk\"\nabbrev_key = \"\"").unwrap();
+        assert_eq!(config.abbrev_key, None);
+        let config = Config::parse("abbrev_key = \";\"").unwrap();
+        assert_eq!(config.engine_options().abbrev_key, Some(';'));
+        let config = Config::parse("abbrev_key = \"\"").unwrap();
+        assert_eq!(config.abbrev_key, None);
+        assert!(Config::parse("abbrev_key = \"//\"").is_err());
+    }
+
+    #[test]
+    fn parse_symbol_dict_settings() {
+        let config = Config::default_config();
+        assert!(config.symbol_dictionary().is_some());
+        assert!(config.engine_options().emoji);
+
+        let config = Config::parse("emoji = false").unwrap();
+        assert!(!config.emoji);
+        assert!(!config.engine_options().emoji);
+
+        let config = Config::parse("symbol_dict = false").unwrap();
+        assert!(config.symbol_dictionary().is_none());
+
+        let config = Config::parse("symbol_dict_path = \"/nonexistent/symbols.txt\"").unwrap();
+        assert_eq!(
+            config.symbol_dict_path.as_deref(),
+            Some("/nonexistent/symbols.txt")
+        );
+        assert!(config.symbol_dictionary().is_none());
+    }
+
+    #[test]
+    fn parse_typo_correction_settings() {
+        let config = Config::default_config();
+        assert!(!config.typo_correction_table().unwrap().is_empty());
+
+        let config = Config::parse("typo_correction = false").unwrap();
+        assert!(config.typo_correction_table().is_none());
+
+        let config = Config::parse("typo_corrections_path = \"/nonexistent/typo.txt\"").unwrap();
+        assert!(config.typo_correction_table().is_none());
+    }
+
+    #[test]
+    fn parse_date_settings() {
+        let toml = r#"
+[date]
+date_formats = "{month}/{day} | {era}{era_year}年"
+time_formats = "{hour}:{minute2}"
+utc_offset = "-05:30"
+eras = "新元号:2040-01-01"
+"#;
+        let config = Config::parse(toml).unwrap();
+        assert_eq!(
+            config.date_formats.date,
+            vec!["{month}/{day}", "{era}{era_year}年"]
+        );
+        assert_eq!(config.date_formats.time, vec!["{hour}:{minute2}"]);
+        assert_eq!(config.date_formats.utc_offset_minutes, -330);
+        assert_eq!(
+            config.date_formats.eras.last(),
+            Some(&Era::new("新元号", (2040, 1, 1)))
+        );
+        assert!(config.date_provider().is_some());
+
+        let config = Config::parse("date_candidates = false").unwrap();
+        assert!(config.date_provider().is_none());
+        assert!(Config::parse("utc_offset = \"9\"").is_err());
+        assert!(Config::parse("eras = \"新元号\"").is_err());
+    }
+
+    #[test]
+    fn parse_keybind_page_commands() {
+        let config = Config::parse("ctrl_n = \"next_page\"\nctrl_p = \"prev_page\"").unwrap();
+        assert_eq!(config.keybind.ctrl_n, Some(EngineCommand::NextPage));
+        assert_eq!(config.keybind.ctrl_p, Some(EngineCommand::PrevPage));
+    }
+
+    #[test]
+    fn parse_ctrl_backspace_override() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(
+            config.keybind.ctrl_backspace,
+            Some(EngineCommand::UndoCommit)
+        );
+        let config = Config::parse("ctrl_backspace = \"none\"").unwrap();
+        assert_eq!(config.keybind.ctrl_backspace, None);
+    }
 }
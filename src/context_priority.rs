@@ -0,0 +1,151 @@
+//! コンテキスト優先テーブル。
+//!
+//! 直前に確定したテキストに含まれるキーワードに基づいて、
+//! 同音異義語の候補の並び順を変える。
+//!
+//! ファイル形式（1行1ルール、`#` で始まる行はコメント）:
+//!
+//! ```text
+//! キーワード(カンマ区切り)<TAB>読み<TAB>優先候補(カンマ区切り)
+//! 新聞,取材,報道<TAB>きしゃ<TAB>記者
+//! 駅,電車,鉄道<TAB>きしゃ<TAB>汽車
+//! ```
+
+use std::path::Path;
+
+use crate::dictionary::DictionaryError;
+
+/// コンテキスト優先テーブルの1ルール。
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    keywords: Vec<String>,
+    reading: String,
+    preferred: Vec<String>,
+}
+
+/// コンテキスト優先テーブル。
+#[derive(Debug, Clone, Default)]
+pub struct ContextPriority {
+    rules: Vec<Rule>,
+}
+
+impl ContextPriority {
+    /// 空のテーブルを作成する。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ファイルからテーブルを読み込む。
+    pub fn load_from_file(path: &Path) -> Result<Self, DictionaryError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text))
+    }
+
+    /// テキストからテーブルをパースする。列が足りない行は無視する。
+    pub fn parse(text: &str) -> Self {
+        let split_list = |s: &str| -> Vec<String> {
+            s.split(',')
+                .map(|w| w.trim())
+                .filter(|w| !w.is_empty())
+                .map(|w| w.to_string())
+                .collect()
+        };
+        let rules = text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let keywords = split_list(fields.next()?);
+                let reading = fields.next()?.trim().to_string();
+                let preferred = split_list(fields.next()?);
+                if keywords.is_empty() || reading.is_empty() || preferred.is_empty() {
+                    return None;
+                }
+                Some(Rule {
+                    keywords,
+                    reading,
+                    preferred,
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// ルール数を返す。
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// ルールが空かどうか。
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// コンテキストにキーワードが含まれるルールの優先候補を先頭に移動する。
+    ///
+    /// 最初に一致したルールのみを適用する。一致しない場合は元の順序を維持する。
+    pub fn reorder<T: AsRef<str>>(&self, reading: &str, candidates: &mut Vec<T>, context: &str) {
+        let Some(rule) = self.rules.iter().find(|r| {
+            r.reading == reading && r.keywords.iter().any(|k| context.contains(k.as_str()))
+        }) else {
+            return;
+        };
+        for word in rule.preferred.iter().rev() {
+            if let Some(pos) = candidates.iter().position(|c| c.as_ref() == word) {
+                let c = candidates.remove(pos);
+                candidates.insert(0, c);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "# コンテキスト優先テーブル
+技術,開発,コード\tくらす\tクラス
+新聞,取材,報道\tきしゃ\t記者
+駅,電車,鉄道\tきしゃ\t汽車
+不正な行
+";
+
+    fn kisha() -> Vec<String> {
+        vec!["貴社".to_string(), "記者".to_string(), "汽車".to_string()]
+    }
+
+    #[test]
+    fn parse_skips_comments_and_invalid_lines() {
+        let table = ContextPriority::parse(TABLE);
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn reorder_by_keyword() {
+        let table = ContextPriority::parse(TABLE);
+        let mut cands = kisha();
+        table.reorder("きしゃ", &mut cands, "今日の新聞の");
+        assert_eq!(cands, vec!["記者", "貴社", "汽車"]);
+
+        let mut cands = kisha();
+        table.reorder("きしゃ", &mut cands, "駅で");
+        assert_eq!(cands, vec!["汽車", "貴社", "記者"]);
+    }
+
+    #[test]
+    fn reorder_without_match_keeps_order() {
+        let table = ContextPriority::parse(TABLE);
+        let mut cands = kisha();
+        table.reorder("きしゃ", &mut cands, "明日の会議");
+        assert_eq!(cands, kisha());
+        // 読みが違えばキーワードが一致しても適用しない
+        table.reorder("くらす", &mut cands, "新聞");
+        assert_eq!(cands, kisha());
+    }
+
+    #[test]
+    fn load_nonexistent_file_errors() {
+        assert!(ContextPriority::load_from_file(Path::new("nonexistent_context.txt")).is_err());
+    }
+}
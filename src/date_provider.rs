@@ -0,0 +1,430 @@
+//! 日付・時刻の候補。
+//!
+//! `きょう` `あした` `いま` などの読みに対して、現在の日付・時刻から
+//! 書式に従って候補を生成する `CandidateProvider`。
+//! 現在時刻は `Clock` から取得するため、テストでは固定の時刻を使える。
+//! 和暦の元号は開始日の表で判定し、設定で元号を追加できる。
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::candidate::{Candidate, CandidateSource};
+use crate::provider::{CandidateProvider, SYSTEM_DICTIONARY_PRIORITY};
+
+/// 日付・時刻の候補の優先度。システム辞書の候補（今日・明日など）の後に並ぶ。
+pub const DATE_PRIORITY: i32 = SYSTEM_DICTIONARY_PRIORITY - 10;
+
+/// デフォルトの日付の書式。
+pub const DEFAULT_DATE_FORMATS: &[&str] = &[
+    "{year}-{month2}-{day2}",
+    "{year}/{month2}/{day2}",
+    "{year}年{month}月{day}日",
+    "{era}{era_year}年{month}月{day}日({weekday})",
+];
+
+/// デフォルトの時刻の書式。
+pub const DEFAULT_TIME_FORMATS: &[&str] = &["{hour2}:{minute2}", "{hour}時{minute}分"];
+
+/// 日付の読みと、今日からの日数。
+const DATE_READINGS: &[(&str, i64)] = &[
+    ("おととい", -2),
+    ("きのう", -1),
+    ("きょう", 0),
+    ("ほんじつ", 0),
+    ("あした", 1),
+    ("あす", 1),
+    ("あさって", 2),
+];
+
+/// 時刻の読み。
+const TIME_READINGS: &[&str] = &["いま", "げんざい"];
+
+const WEEKDAYS: [&str; 7] = ["日", "月", "火", "水", "木", "金", "土"];
+
+/// 現在時刻の取得元。
+pub trait Clock: Send {
+    /// 1970-01-01T00:00:00Z からの秒数を返す。
+    fn now(&self) -> i64;
+}
+
+/// システムの時計。
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64)
+    }
+}
+
+/// 元号。開始日以降の日付にこの元号を使う。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Era {
+    pub name: String,
+    /// 開始日 (年, 月, 日)
+    pub start: (i64, u32, u32),
+}
+
+impl Era {
+    pub fn new(name: &str, start: (i64, u32, u32)) -> Self {
+        Self {
+            name: name.to_string(),
+            start,
+        }
+    }
+
+    /// `令和:2019-05-01` の形式の文字列から元号を作る。
+    pub fn parse(text: &str) -> Option<Self> {
+        let (name, date) = text.split_once(':')?;
+        let mut parts = date.trim().splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        let name = name.trim();
+        if name.is_empty() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        Some(Self::new(name, (year, month, day)))
+    }
+}
+
+/// 組み込みの元号の表（明治以降）。
+pub fn default_eras() -> Vec<Era> {
+    vec![
+        Era::new("明治", (1868, 1, 25)),
+        Era::new("大正", (1912, 7, 30)),
+        Era::new("昭和", (1926, 12, 25)),
+        Era::new("平成", (1989, 1, 8)),
+        Era::new("令和", (2019, 5, 1)),
+    ]
+}
+
+/// 日付・時刻の候補の設定。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateFormats {
+    /// 日付の書式
+    pub date: Vec<String>,
+    /// 時刻の書式
+    pub time: Vec<String>,
+    /// UTC からの時差（分）
+    pub utc_offset_minutes: i64,
+    /// 元号の表。開始日の順でなくてもよい。
+    pub eras: Vec<Era>,
+}
+
+impl Default for DateFormats {
+    fn default() -> Self {
+        Self {
+            date: DEFAULT_DATE_FORMATS.iter().map(|s| s.to_string()).collect(),
+            time: DEFAULT_TIME_FORMATS.iter().map(|s| s.to_string()).collect(),
+            utc_offset_minutes: 9 * 60,
+            eras: default_eras(),
+        }
+    }
+}
+
+/// 日付・時刻の候補の提供元。
+pub struct DateProvider {
+    clock: Box<dyn Clock>,
+    formats: DateFormats,
+}
+
+impl DateProvider {
+    /// システムの時計を使う提供元を作成する。
+    pub fn new(formats: DateFormats) -> Self {
+        Self::with_clock(formats, Box::new(SystemClock))
+    }
+
+    /// 時計を指定して提供元を作成する。
+    pub fn with_clock(formats: DateFormats, clock: Box<dyn Clock>) -> Self {
+        Self { clock, formats }
+    }
+
+    /// 今日の日付を最初の日付の書式で返す（スニペットの `{date}` 用）。
+    pub fn today(&self) -> Option<String> {
+        let format = self.formats.date.first()?;
+        self.format(format, &self.local_now())
+    }
+
+    /// 現在の時刻を最初の時刻の書式で返す（スニペットの `{time}` 用）。
+    pub fn current_time(&self) -> Option<String> {
+        let format = self.formats.time.first()?;
+        self.format(format, &self.local_now())
+    }
+
+    /// 現地時刻を返す。
+    fn local_now(&self) -> DateTime {
+        DateTime::from_timestamp(self.clock.now() + self.formats.utc_offset_minutes * 60)
+    }
+
+    /// 日付の元号と元号での年を返す。最初の元号より前なら None。
+    fn era(&self, date: &DateTime) -> Option<(&str, i64)> {
+        self.formats
+            .eras
+            .iter()
+            .filter(|e| e.start <= (date.year, date.month, date.day))
+            .max_by_key(|e| e.start)
+            .map(|e| (e.name.as_str(), date.year - e.start.0 + 1))
+    }
+
+    /// 書式の `{...}` を日時の値で置き換える。未知の名前はそのまま残す。
+    fn format(&self, format: &str, date: &DateTime) -> Option<String> {
+        let era = self.era(date);
+        let mut out = String::new();
+        let mut rest = format;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let Some(close) = rest[open..].find('}') else {
+                out.push_str(&rest[open..]);
+                return Some(out);
+            };
+            let name = &rest[open + 1..open + close];
+            let value = match name {
+                "year" => date.year.to_string(),
+                "month" => date.month.to_string(),
+                "month2" => format!("{:02}", date.month),
+                "day" => date.day.to_string(),
+                "day2" => format!("{:02}", date.day),
+                "weekday" => WEEKDAYS[date.weekday].to_string(),
+                // 元号のない日付ではこの書式の候補を出さない
+                "era" => era?.0.to_string(),
+                "era_year" => match era?.1 {
+                    1 => "元".to_string(),
+                    n => n.to_string(),
+                },
+                "hour" => date.hour.to_string(),
+                "hour2" => format!("{:02}", date.hour),
+                "minute" => date.minute.to_string(),
+                "minute2" => format!("{:02}", date.minute),
+                "second2" => format!("{:02}", date.second),
+                _ => rest[open..=open + close].to_string(),
+            };
+            out.push_str(&value);
+            rest = &rest[open + close + 1..];
+        }
+        out.push_str(rest);
+        Some(out)
+    }
+}
+
+impl CandidateProvider for DateProvider {
+    fn name(&self) -> &str {
+        "date"
+    }
+
+    fn lookup(&self, reading: &str) -> Vec<Candidate> {
+        let now = self.local_now();
+        let (formats, date) =
+            if let Some(&(_, offset)) = DATE_READINGS.iter().find(|(r, _)| *r == reading) {
+                (&self.formats.date, now.add_days(offset))
+            } else if TIME_READINGS.contains(&reading) {
+                (&self.formats.time, now)
+            } else {
+                return Vec::new();
+            };
+        let mut candidates: Vec<Candidate> = Vec::new();
+        for text in formats.iter().filter_map(|f| self.format(f, &date)) {
+            if !candidates.iter().any(|c| c.text == text) {
+                candidates.push(Candidate::new(&text, reading, CandidateSource::Generated));
+            }
+        }
+        candidates
+    }
+}
+
+/// 現地時刻の日時。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    /// 曜日（0 が日曜）
+    weekday: usize,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl DateTime {
+    /// 1970-01-01T00:00:00 からの秒数から日時を求める。
+    fn from_timestamp(seconds: i64) -> Self {
+        let days = seconds.div_euclid(86400);
+        let secs = seconds.rem_euclid(86400) as u32;
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            weekday: (days + 4).rem_euclid(7) as usize,
+            hour: secs / 3600,
+            minute: secs / 60 % 60,
+            second: secs % 60,
+        }
+    }
+
+    /// `days` 日後の同じ時刻を返す。
+    fn add_days(&self, days: i64) -> Self {
+        let seconds = (days_from_civil(self.year, self.month, self.day) + days) * 86400
+            + i64::from(self.hour * 3600 + self.minute * 60 + self.second);
+        Self::from_timestamp(seconds)
+    }
+}
+
+/// 1970-01-01 からの日数をグレゴリオ暦の (年, 月, 日) にする。
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// グレゴリオ暦の (年, 月, 日) を 1970-01-01 からの日数にする。
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 固定の時刻を返す時計。
+    struct FixedClock(i64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> i64 {
+            self.0
+        }
+    }
+
+    /// 2026-10-17 14:05:30 (JST) の UTC 秒。
+    const NOW: i64 = 1_792_213_530;
+
+    fn provider() -> DateProvider {
+        DateProvider::with_clock(DateFormats::default(), Box::new(FixedClock(NOW)))
+    }
+
+    #[test]
+    fn today_and_current_time_use_first_format() {
+        let p = provider();
+        assert_eq!(p.today().as_deref(), Some("2026-10-17"));
+        assert_eq!(p.current_time().as_deref(), Some("14:05"));
+        let empty = DateFormats {
+            date: Vec::new(),
+            ..DateFormats::default()
+        };
+        let p = DateProvider::with_clock(empty, Box::new(FixedClock(NOW)));
+        assert_eq!(p.today(), None);
+    }
+
+    #[test]
+    fn today_candidates() {
+        let cands = provider().lookup("きょう");
+        assert_eq!(
+            cands,
+            vec![
+                "2026-10-17",
+                "2026/10/17",
+                "2026年10月17日",
+                "令和8年10月17日(土)"
+            ]
+        );
+        assert_eq!(cands[0].source, CandidateSource::Generated);
+        assert_eq!(cands[0].reading, "きょう");
+    }
+
+    #[test]
+    fn relative_days() {
+        let p = provider();
+        assert_eq!(p.lookup("あした")[0], "2026-10-18");
+        assert_eq!(p.lookup("きのう")[0], "2026-10-16");
+        assert_eq!(p.lookup("あさって")[3], "令和8年10月19日(月)");
+    }
+
+    #[test]
+    fn time_candidates() {
+        assert_eq!(provider().lookup("いま"), vec!["14:05", "14時5分"]);
+    }
+
+    #[test]
+    fn unrelated_reading_is_empty() {
+        assert!(provider().lookup("かんじ").is_empty());
+    }
+
+    #[test]
+    fn utc_offset_changes_date() {
+        let formats = DateFormats {
+            utc_offset_minutes: -10 * 60,
+            ..DateFormats::default()
+        };
+        let p = DateProvider::with_clock(formats, Box::new(FixedClock(NOW)));
+        assert_eq!(p.lookup("きょう")[0], "2026-10-16");
+        assert_eq!(p.lookup("いま")[0], "19:05");
+    }
+
+    #[test]
+    fn era_boundaries() {
+        let p = provider();
+        let date = |y, m, d| DateTime::from_timestamp(days_from_civil(y, m, d) * 86400);
+        assert_eq!(p.era(&date(2019, 4, 30)), Some(("平成", 31)));
+        assert_eq!(p.era(&date(2019, 5, 1)), Some(("令和", 1)));
+        assert_eq!(p.era(&date(1989, 1, 7)), Some(("昭和", 64)));
+        assert_eq!(p.era(&date(1800, 1, 1)), None);
+        let first = p.format("{era}{era_year}年", &date(2019, 5, 1));
+        assert_eq!(first.as_deref(), Some("令和元年"));
+    }
+
+    #[test]
+    fn added_era_takes_over() {
+        let mut formats = DateFormats::default();
+        formats.eras.push(Era::parse("新元号:2026-10-01").unwrap());
+        let p = DateProvider::with_clock(formats, Box::new(FixedClock(NOW)));
+        assert_eq!(p.lookup("きょう")[3], "新元号元年10月17日(土)");
+    }
+
+    #[test]
+    fn parse_era() {
+        assert_eq!(
+            Era::parse("令和:2019-05-01"),
+            Some(Era::new("令和", (2019, 5, 1)))
+        );
+        assert_eq!(Era::parse("令和"), None);
+        assert_eq!(Era::parse("令和:2019-13-01"), None);
+    }
+
+    #[test]
+    fn custom_formats_and_unknown_placeholder() {
+        let formats = DateFormats {
+            date: vec![
+                "{month}/{day} {foo}".to_string(),
+                "{month}/{day} {foo}".to_string(),
+            ],
+            ..DateFormats::default()
+        };
+        let p = DateProvider::with_clock(formats, Box::new(FixedClock(NOW)));
+        assert_eq!(p.lookup("きょう"), vec!["10/17 {foo}"]);
+        let unclosed = p.format("{month}月{day", &p.local_now());
+        assert_eq!(unclosed.as_deref(), Some("10月{day"));
+    }
+
+    #[test]
+    fn civil_round_trip() {
+        for days in [-719468, -1, 0, 59, 10_957, 20_743] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+}
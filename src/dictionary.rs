@@ -3,7 +3,7 @@
 //! SKK 辞書ファイルをパースし、ひらがなの読みから
 //! 変換候補（漢字）を検索する。
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
 /// 辞書操作で発生するエラー。
@@ -27,9 +27,167 @@ impl From<std::io::Error> for DictionaryError {
     }
 }
 
+/// 辞書ファイルの文字コード。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DictEncoding {
+    /// UTF-8
+    #[default]
+    Utf8,
+    /// EUC-JP (SKK 辞書の伝統的な文字コード)
+    EucJp,
+}
+
+impl DictEncoding {
+    /// バイト列をデコードする。UTF-8 として解釈できなければ EUC-JP とみなす。
+    pub fn detect_and_decode(bytes: &[u8]) -> (String, DictEncoding) {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => (s.to_string(), DictEncoding::Utf8),
+            Err(_) => {
+                let (cow, _, _) = encoding_rs::EUC_JP.decode(bytes);
+                (cow.into_owned(), DictEncoding::EucJp)
+            }
+        }
+    }
+
+    /// 文字列をこの文字コードでエンコードする。
+    /// この文字コードで表せない文字（EUC-JP の絵文字など）があれば None。
+    pub fn encode(&self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            DictEncoding::Utf8 => Some(text.as_bytes().to_vec()),
+            DictEncoding::EucJp => {
+                let (cow, _, unmappable) = encoding_rs::EUC_JP.encode(text);
+                (!unmappable).then(|| cow.into_owned())
+            }
+        }
+    }
+
+    /// SKK 辞書の coding 宣言 (`-*- coding: ... -*-`) に書く名前。
+    pub fn coding_name(&self) -> &'static str {
+        match self {
+            DictEncoding::Utf8 => "utf-8",
+            DictEncoding::EucJp => "euc-jp",
+        }
+    }
+}
+
+/// 候補と注釈のペア。
+pub(crate) type AnnotatedCandidate = (String, Option<String>);
+
+/// SKK 辞書の候補部分 (`/候補;注釈/[送り/候補/]/`) をパースした結果。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SkkCandidates {
+    /// 候補と注釈のペア。
+    pub candidates: Vec<AnnotatedCandidate>,
+    /// 送り仮名ごとのサブエントリ (`[る/来/]`)。
+    pub okuri_blocks: Vec<(String, Vec<String>)>,
+}
+
+/// SKK 辞書の候補部分をパースする。
+///
+/// 注釈（`;` 以降）は候補から分離し、`[送り/候補/]` 形式の
+/// 送りありサブエントリは `okuri_blocks` に格納する。
+pub(crate) fn parse_skk_candidates(rest: &str) -> SkkCandidates {
+    let mut result = SkkCandidates::default();
+    let mut remaining = rest.trim();
+    while !remaining.is_empty() {
+        remaining = remaining.trim_start_matches('/');
+        if remaining.is_empty() {
+            break;
+        }
+        if let Some(block) = remaining.strip_prefix('[') {
+            // "[る/来/]" の形式。閉じ括弧までを1ブロックとして扱う
+            let end = block.find(']').unwrap_or(block.len());
+            let mut parts = block[..end].split('/').filter(|s| !s.is_empty());
+            if let Some(okuri) = parts.next() {
+                let cands: Vec<String> = parts.map(strip_annotation).collect();
+                if !cands.is_empty() {
+                    result.okuri_blocks.push((okuri.to_string(), cands));
+                }
+            }
+            remaining = block.get(end + 1..).unwrap_or("");
+            continue;
+        }
+        let end = remaining.find('/').unwrap_or(remaining.len());
+        let token = &remaining[..end];
+        remaining = &remaining[end..];
+        let (word, annotation) = match token.split_once(';') {
+            Some((w, a)) => (w, Some(unescape(a)).filter(|a| !a.is_empty())),
+            None => (token, None),
+        };
+        if !word.is_empty() {
+            result.candidates.push((unescape(word), annotation));
+        }
+    }
+    result
+}
+
+/// 候補から注釈（`;` 以降）を取り除く。
+fn strip_annotation(s: &str) -> String {
+    match s.find(';') {
+        Some(pos) => unescape(&s[..pos]),
+        None => unescape(s),
+    }
+}
+
+/// 区切り文字の `/` `;` を含む文字列を SKK の `(concat "...")` 形式にする。
+/// `/` は `\057`、`;` は `\073` と8進数で書く。区切り文字を含まなければそのまま返す。
+pub(crate) fn escape(s: &str) -> String {
+    if !s.contains(['/', ';']) {
+        return s.to_string();
+    }
+    let mut out = String::from("(concat \"");
+    for c in s.chars() {
+        match c {
+            '/' => out.push_str("\\057"),
+            ';' => out.push_str("\\073"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            _ => out.push(c),
+        }
+    }
+    out.push_str("\")");
+    out
+}
+
+/// `(concat "...")` 形式の文字列を元に戻す。その形式でなければそのまま返す。
+fn unescape(s: &str) -> String {
+    let Some(body) = s
+        .strip_prefix("(concat \"")
+        .and_then(|b| b.strip_suffix("\")"))
+    else {
+        return s.to_string();
+    };
+    let mut out = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        // \NNN は8進数の文字コード、それ以外は次の文字そのもの
+        let mut code = 0;
+        let mut digits = 0;
+        while digits < 3
+            && let Some(d) = chars.peek().and_then(|d| d.to_digit(8))
+        {
+            code = code * 8 + d;
+            digits += 1;
+            chars.next();
+        }
+        if digits > 0 {
+            out.extend(char::from_u32(code));
+        } else if let Some(next) = chars.next() {
+            out.push(next);
+        }
+    }
+    out
+}
+
 /// 読みから候補リストへのマッピングを保持する辞書。
 pub struct Dictionary {
     entries: BTreeMap<String, Vec<String>>,
+    /// 読み → 候補 → 注釈
+    annotations: HashMap<String, HashMap<String, String>>,
 }
 
 impl Default for Dictionary {
@@ -43,6 +201,7 @@ impl Dictionary {
     pub fn new() -> Self {
         Self {
             entries: BTreeMap::new(),
+            annotations: HashMap::new(),
         }
     }
 
@@ -51,27 +210,31 @@ impl Dictionary {
         let bytes = std::fs::read(path)?;
 
         // UTF-8 として解釈を試み、失敗したら EUC-JP でデコード
-        let text = match std::str::from_utf8(&bytes) {
-            Ok(s) => s.to_string(),
-            Err(_) => {
-                let (cow, _, _) = encoding_rs::EUC_JP.decode(&bytes);
-                cow.into_owned()
-            }
-        };
+        let (text, _) = DictEncoding::detect_and_decode(&bytes);
+        Ok(Self::parse(&text))
+    }
 
+    /// SKK 辞書形式のテキストから辞書を作成する。
+    pub fn parse(text: &str) -> Self {
         let mut dict = Self::new();
         for line in text.lines() {
             if let Some((reading, candidates)) = parse_line(line) {
-                let entry = dict.entries.entry(reading).or_default();
-                for c in candidates {
+                let entry = dict.entries.entry(reading.clone()).or_default();
+                for (c, annotation) in candidates {
+                    if let Some(a) = annotation {
+                        dict.annotations
+                            .entry(reading.clone())
+                            .or_default()
+                            .entry(c.clone())
+                            .or_insert(a);
+                    }
                     if !entry.contains(&c) {
                         entry.push(c);
                     }
                 }
             }
         }
-
-        Ok(dict)
+        dict
     }
 
     /// 読みから候補を検索する。
@@ -79,10 +242,23 @@ impl Dictionary {
         self.entries.get(reading).map(|v| v.as_slice())
     }
 
+    /// 候補の注釈を返す。
+    pub fn annotation(&self, reading: &str, candidate: &str) -> Option<&str> {
+        self.annotations
+            .get(reading)
+            .and_then(|m| m.get(candidate))
+            .map(|s| s.as_str())
+    }
+
     /// 前方一致検索。指定のプレフィクスで始まる読みとその候補を返す。
     ///
     /// BTreeMap の range を使い、プレフィクスに一致する範囲のみを走査する。
     pub fn lookup_prefix(&self, prefix: &str) -> Vec<(&str, &[String])> {
+        self.lookup_prefix_with_limit(prefix, usize::MAX)
+    }
+
+    /// 前方一致検索。読みの辞書順で最大 `limit` 件までを返す。
+    pub fn lookup_prefix_with_limit(&self, prefix: &str, limit: usize) -> Vec<(&str, &[String])> {
         // BTreeMap は sorted なので range で効率的に前方一致検索できる。
         // prefix の末尾文字をインクリメントして上限を作る。
         let start = prefix.to_string();
@@ -92,6 +268,7 @@ impl Dictionary {
             None => Box::new(self.entries.range(start..)),
         };
         iter.filter(|(reading, _)| reading.starts_with(prefix))
+            .take(limit)
             .map(|(reading, candidates)| (reading.as_str(), candidates.as_slice()))
             .collect()
     }
@@ -101,7 +278,7 @@ impl Dictionary {
 ///
 /// 末尾の文字をインクリメントして上限を作る。
 /// インクリメントできない場合（char::MAX）は None を返す。
-fn prefix_end_bound(prefix: &str) -> Option<String> {
+pub(crate) fn prefix_end_bound(prefix: &str) -> Option<String> {
     let mut chars: Vec<char> = prefix.chars().collect();
     // 末尾の文字をインクリメント
     while let Some(last) = chars.pop() {
@@ -116,8 +293,8 @@ fn prefix_end_bound(prefix: &str) -> Option<String> {
 
 /// SKK 辞書の1行をパースする。
 ///
-/// 読みと候補リストを返す。コメント行・空行は None。
-fn parse_line(line: &str) -> Option<(String, Vec<String>)> {
+/// 読みと（候補, 注釈）のリストを返す。コメント行・空行は None。
+fn parse_line(line: &str) -> Option<(String, Vec<AnnotatedCandidate>)> {
     let line = line.trim();
     if line.is_empty() || line.starts_with(';') {
         return None;
@@ -131,16 +308,9 @@ fn parse_line(line: &str) -> Option<(String, Vec<String>)> {
         return None;
     }
 
-    // '/' で区切って候補を抽出し、アノテーション（';' 以降）を除去
-    let candidates: Vec<String> = rest
-        .split('/')
-        .filter(|s| !s.is_empty())
-        .map(|s| match s.find(';') {
-            Some(pos) => s[..pos].to_string(),
-            None => s.to_string(),
-        })
-        .filter(|s| !s.is_empty())
-        .collect();
+    // '/' で区切って候補を抽出し、アノテーション（';' 以降）を分離。
+    // 送りありの "[る/来/]" ブロックはシステム辞書では使わない。
+    let candidates = parse_skk_candidates(rest).candidates;
 
     if candidates.is_empty() {
         return None;
@@ -155,26 +325,32 @@ mod tests {
 
     // === 行パーサー ===
 
+    fn words(candidates: &[AnnotatedCandidate]) -> Vec<&str> {
+        candidates.iter().map(|(w, _)| w.as_str()).collect()
+    }
+
     #[test]
     fn parse_normal_entry() {
         let result = parse_line("かんじ /漢字/感じ/幹事/").unwrap();
         assert_eq!(result.0, "かんじ");
-        assert_eq!(result.1, vec!["漢字", "感じ", "幹事"]);
+        assert_eq!(words(&result.1), vec!["漢字", "感じ", "幹事"]);
     }
 
     #[test]
     fn parse_single_candidate() {
         let result = parse_line("にほん /日本/").unwrap();
         assert_eq!(result.0, "にほん");
-        assert_eq!(result.1, vec!["日本"]);
+        assert_eq!(words(&result.1), vec!["日本"]);
     }
 
     #[test]
     fn parse_annotation() {
-        // アノテーション（;以降）は除去して候補のみ返す
+        // アノテーション（;以降）は候補から分離して返す
         let result = parse_line("にほん /日本;country/二本/").unwrap();
         assert_eq!(result.0, "にほん");
-        assert_eq!(result.1, vec!["日本", "二本"]);
+        assert_eq!(words(&result.1), vec!["日本", "二本"]);
+        assert_eq!(result.1[0].1.as_deref(), Some("country"));
+        assert_eq!(result.1[1].1, None);
     }
 
     #[test]
@@ -192,7 +368,7 @@ mod tests {
         // タブ区切りの辞書行もパースできること
         let result = parse_line("かんじ\t/漢字/感じ/").unwrap();
         assert_eq!(result.0, "かんじ");
-        assert_eq!(result.1, vec!["漢字", "感じ"]);
+        assert_eq!(words(&result.1), vec!["漢字", "感じ"]);
     }
 
     #[test]
@@ -200,7 +376,55 @@ mod tests {
         // 送り仮名付きエントリもそのまま保持
         let result = parse_line("おおきi /大き/").unwrap();
         assert_eq!(result.0, "おおきi");
-        assert_eq!(result.1, vec!["大き"]);
+        assert_eq!(words(&result.1), vec!["大き"]);
+    }
+
+    #[test]
+    fn parse_okuri_blocks_not_candidates() {
+        // 送りありブロックは候補として扱わない
+        let result = parse_line("くr /来/[る/来/]/[れ/来/]/").unwrap();
+        assert_eq!(words(&result.1), vec!["来"]);
+    }
+
+    #[test]
+    fn parse_skk_candidates_with_blocks_and_annotations() {
+        let parsed = parse_skk_candidates("/来;come/繰/[る/来/繰/]/[れ/来/]/");
+        assert_eq!(
+            parsed.candidates,
+            vec![
+                ("来".to_string(), Some("come".to_string())),
+                ("繰".to_string(), None)
+            ]
+        );
+        assert_eq!(
+            parsed.okuri_blocks,
+            vec![
+                ("る".to_string(), vec!["来".to_string(), "繰".to_string()]),
+                ("れ".to_string(), vec!["来".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn concat_escape_roundtrip() {
+        let escaped = escape("東京都/中野区;駅");
+        assert_eq!(escaped, r#"(concat "東京都\057中野区\073駅")"#);
+        assert_eq!(unescape(&escaped), "東京都/中野区;駅");
+        assert_eq!(escape("中野"), "中野");
+        assert_eq!(unescape(r#"(concat "a\"b\\c")"#), "a\"b\\c");
+    }
+
+    #[test]
+    fn parse_concat_candidates_and_annotations() {
+        let parsed =
+            parse_skk_candidates(r#"/(concat "http\072\057\057");URL/中野;(concat "a\057b")/"#);
+        assert_eq!(
+            parsed.candidates,
+            vec![
+                ("http://".to_string(), Some("URL".to_string())),
+                ("中野".to_string(), Some("a/b".to_string())),
+            ]
+        );
     }
 
     // === Dictionary 構造体 ===
@@ -259,6 +483,8 @@ mod tests {
         // "にほん /日本;country/二本/" → アノテーション除去
         let result = dict.lookup("にほん").unwrap();
         assert_eq!(result, &["日本", "二本"]);
+        assert_eq!(dict.annotation("にほん", "日本"), Some("country"));
+        assert_eq!(dict.annotation("にほん", "二本"), None);
     }
 
     #[test]
@@ -281,6 +507,14 @@ mod tests {
         assert_eq!(results.len(), 3);
     }
 
+    #[test]
+    fn lookup_prefix_with_limit() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let result = dict.lookup_prefix_with_limit("とうきょう", 2);
+        let readings: Vec<&str> = result.iter().map(|(r, _)| *r).collect();
+        assert_eq!(readings, vec!["とうきょう", "とうきょうえき"]);
+    }
+
     #[test]
     fn lookup_prefix_no_match() {
         let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
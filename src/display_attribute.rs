@@ -0,0 +1,215 @@
+//! TSF の表示属性。入力中のテキストの下線・強調を部分ごとに描き分ける。
+//!
+//! `PreeditAttribute` ごとに表示属性の GUID と `TF_DISPLAYATTRIBUTE` を定め、
+//! `ITfDisplayAttributeProvider` を通じてアプリケーションに提供する。
+
+use std::sync::Mutex;
+
+use windows::Win32::Foundation::*;
+use windows::Win32::System::Com::*;
+use windows::Win32::UI::TextServices::*;
+use windows::core::*;
+
+use crate::engine::PreeditAttribute;
+use crate::guids;
+
+/// 表示属性を提供する属性（列挙する順）。
+const ATTRIBUTES: [PreeditAttribute; 4] = [
+    PreeditAttribute::Input,
+    PreeditAttribute::PendingRomaji,
+    PreeditAttribute::Converted,
+    PreeditAttribute::Focused,
+];
+
+/// 属性の表示属性 GUID を返す。
+fn attribute_guid(attribute: PreeditAttribute) -> GUID {
+    match attribute {
+        PreeditAttribute::Input => guids::guid_display_attribute_input(),
+        PreeditAttribute::PendingRomaji => guids::guid_display_attribute_pending(),
+        PreeditAttribute::Converted => guids::guid_display_attribute_converted(),
+        PreeditAttribute::Focused => guids::guid_display_attribute_focused(),
+    }
+}
+
+/// 表示属性の GUID から属性を引く。
+fn find_attribute(guid: &GUID) -> Option<PreeditAttribute> {
+    ATTRIBUTES.into_iter().find(|&a| attribute_guid(a) == *guid)
+}
+
+/// 属性の説明（設定画面などに表示される）。
+fn description(attribute: PreeditAttribute) -> &'static str {
+    match attribute {
+        PreeditAttribute::Input => "japinput 入力中",
+        PreeditAttribute::PendingRomaji => "japinput ローマ字",
+        PreeditAttribute::Converted => "japinput 変換済み",
+        PreeditAttribute::Focused => "japinput 選択中の文節",
+    }
+}
+
+/// 属性の描き方。入力中は点線、未確定のローマ字は破線、
+/// 変換済みの文節は実線、選択中の文節は太い実線の下線にする。
+fn display_attribute(attribute: PreeditAttribute) -> TF_DISPLAYATTRIBUTE {
+    let (style, bold, attr) = match attribute {
+        PreeditAttribute::Input => (TF_LS_DOT, FALSE, TF_ATTR_INPUT),
+        PreeditAttribute::PendingRomaji => (TF_LS_DASH, FALSE, TF_ATTR_INPUT),
+        PreeditAttribute::Converted => (TF_LS_SOLID, FALSE, TF_ATTR_CONVERTED),
+        PreeditAttribute::Focused => (TF_LS_SOLID, TRUE, TF_ATTR_TARGET_CONVERTED),
+    };
+    TF_DISPLAYATTRIBUTE {
+        crText: TF_DA_COLOR::default(),
+        crBk: TF_DA_COLOR::default(),
+        lsStyle: style,
+        fBoldLine: bold,
+        crLine: TF_DA_COLOR::default(),
+        bAttr: attr,
+    }
+}
+
+/// 各属性の GUID を TSF に登録して得た atom。範囲の属性プロパティに設定する。
+///
+/// 登録できなかった属性は 0 (`TF_INVALID_GUIDATOM`) のままになる。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisplayAtoms {
+    input: u32,
+    pending: u32,
+    converted: u32,
+    focused: u32,
+}
+
+impl DisplayAtoms {
+    /// カテゴリマネージャに各属性の GUID を登録する。
+    pub fn register() -> Result<Self> {
+        let category_mgr: ITfCategoryMgr =
+            unsafe { CoCreateInstance(&CLSID_TF_CategoryMgr, None, CLSCTX_INPROC_SERVER)? };
+        let register = |attribute| unsafe { category_mgr.RegisterGUID(&attribute_guid(attribute)) };
+        Ok(Self {
+            input: register(PreeditAttribute::Input)?,
+            pending: register(PreeditAttribute::PendingRomaji)?,
+            converted: register(PreeditAttribute::Converted)?,
+            focused: register(PreeditAttribute::Focused)?,
+        })
+    }
+
+    /// 属性の atom を返す。登録されていなければ None。
+    pub fn atom(&self, attribute: PreeditAttribute) -> Option<u32> {
+        let atom = match attribute {
+            PreeditAttribute::Input => self.input,
+            PreeditAttribute::PendingRomaji => self.pending,
+            PreeditAttribute::Converted => self.converted,
+            PreeditAttribute::Focused => self.focused,
+        };
+        (atom != 0).then_some(atom)
+    }
+}
+
+/// 属性の GUID から表示属性の情報を返す。未知の GUID なら None。
+pub fn info_for_guid(guid: &GUID) -> Option<ITfDisplayAttributeInfo> {
+    find_attribute(guid).map(|a| DisplayAttributeInfo { attribute: a }.into())
+}
+
+// === ITfDisplayAttributeInfo ===
+
+/// 1つの属性の表示属性の情報。
+#[implement(ITfDisplayAttributeInfo)]
+struct DisplayAttributeInfo {
+    attribute: PreeditAttribute,
+}
+
+impl ITfDisplayAttributeInfo_Impl for DisplayAttributeInfo_Impl {
+    fn GetGUID(&self) -> Result<GUID> {
+        Ok(attribute_guid(self.attribute))
+    }
+
+    fn GetDescription(&self) -> Result<BSTR> {
+        Ok(BSTR::from(description(self.attribute)))
+    }
+
+    fn GetAttributeInfo(&self, pda: *mut TF_DISPLAYATTRIBUTE) -> Result<()> {
+        if pda.is_null() {
+            return Err(E_INVALIDARG.into());
+        }
+        unsafe {
+            *pda = display_attribute(self.attribute);
+        }
+        Ok(())
+    }
+
+    fn SetAttributeInfo(&self, _pda: *const TF_DISPLAYATTRIBUTE) -> Result<()> {
+        // 描き方の変更には対応しない
+        Err(E_NOTIMPL.into())
+    }
+
+    fn Reset(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// === IEnumTfDisplayAttributeInfo ===
+
+/// 全属性の表示属性の情報を列挙する。
+#[implement(IEnumTfDisplayAttributeInfo)]
+#[derive(Default)]
+pub struct EnumDisplayAttributeInfo {
+    /// 次に返す属性の位置
+    index: Mutex<usize>,
+}
+
+impl IEnumTfDisplayAttributeInfo_Impl for EnumDisplayAttributeInfo_Impl {
+    fn Clone(&self) -> Result<IEnumTfDisplayAttributeInfo> {
+        let index = *self.index.lock().unwrap();
+        Ok(EnumDisplayAttributeInfo {
+            index: Mutex::new(index),
+        }
+        .into())
+    }
+
+    fn Next(
+        &self,
+        ulcount: u32,
+        rginfo: *mut Option<ITfDisplayAttributeInfo>,
+        pcfetched: *mut u32,
+    ) -> Result<()> {
+        if rginfo.is_null() {
+            return Err(E_INVALIDARG.into());
+        }
+        let mut index = self.index.lock().unwrap();
+        let mut fetched = 0;
+        while fetched < ulcount as usize && *index < ATTRIBUTES.len() {
+            let info: ITfDisplayAttributeInfo = DisplayAttributeInfo {
+                attribute: ATTRIBUTES[*index],
+            }
+            .into();
+            unsafe {
+                rginfo.add(fetched).write(Some(info));
+            }
+            fetched += 1;
+            *index += 1;
+        }
+        if !pcfetched.is_null() {
+            unsafe {
+                *pcfetched = fetched as u32;
+            }
+        }
+        // 要求より少なければ S_FALSE を返す
+        if fetched < ulcount as usize {
+            return Err(S_FALSE.into());
+        }
+        Ok(())
+    }
+
+    fn Reset(&self) -> Result<()> {
+        *self.index.lock().unwrap() = 0;
+        Ok(())
+    }
+
+    fn Skip(&self, ulcount: u32) -> Result<()> {
+        let mut index = self.index.lock().unwrap();
+        let skipped = (*index + ulcount as usize).min(ATTRIBUTES.len());
+        let all = skipped - *index == ulcount as usize;
+        *index = skipped;
+        if !all {
+            return Err(S_FALSE.into());
+        }
+        Ok(())
+    }
+}
@@ -2,11 +2,33 @@
 //!
 //! ローマ字入力 → ひらがな変換 → 辞書検索 → 候補選択 → 確定
 //! の一連の変換パイプラインを管理する。
+//! SKK 方式の入力 (`InputStyle::Skk`) は `skk` モジュールで処理する。
 
-use crate::candidate::CandidateList;
+mod skk;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+use crate::calculator;
+use crate::candidate::{
+    Candidate, CandidateList, CandidatePage, CandidateSource, DEFAULT_PAGE_SIZE,
+};
+use crate::context_priority::ContextPriority;
 use crate::dictionary::Dictionary;
 use crate::input_state::InputState;
-use crate::user_dictionary::UserDictionary;
+use crate::katakana;
+use crate::provider::{
+    self, CandidateProvider, MergeRule, SYSTEM_DICTIONARY_PRIORITY, USER_DICTIONARY_PRIORITY,
+};
+use crate::reverse_index::{self, Piece, PieceKind, ReverseIndex};
+use crate::segmenter::{self, Bunsetsu, CostModel};
+use crate::symbol_dict::EMOJI_CATEGORY;
+use crate::typo_correction::{CORRECTED_ANNOTATION, TypoCorrection};
+use crate::user_dictionary::{Checkpoint, UserDictionary};
+
+pub use skk::SkkMode;
+use skk::SkkState;
 
 /// エンジンの状態。
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,19 +56,381 @@ pub enum EngineCommand {
     Commit,
     /// キャンセル (Escape)
     Cancel,
-    /// 1文字削除
+    /// カーソルの前の1文字を削除
     Backspace,
+    /// カーソルの後ろの1文字を削除 (Delete)
+    Delete,
+    /// 予測候補の選択を開始する (Tab)。確定直後は次の語の候補の選択を開始する。
+    Predict,
+    /// 直前の確定を取り消し、同じ候補で変換中に戻る (Ctrl+Backspace)
+    UndoCommit,
+    /// IME のオン/オフを切り替える (半角/全角など)
+    ToggleIme,
+    /// 確定してかなモードに戻る (SKK の Ctrl+J)。SKK 方式以外では確定と同じ。
+    KanaMode,
+    /// 表示中のページ内の位置で候補を選択して確定する
+    SelectCandidate(usize),
+    /// 次の候補ページ (PageDown)
+    NextPage,
+    /// 前の候補ページ (PageUp)
+    PrevPage,
+    /// 左へ移動 (←)。入力中はカーソルを、変換中は前の文節を選択する。
+    MoveLeft,
+    /// 右へ移動 (→)。入力中はカーソルを、変換中は次の文節を選択する。
+    MoveRight,
+    /// 先頭へ移動 (Home)。変換中は最初の文節を選択する。
+    MoveToStart,
+    /// 末尾へ移動 (End)。変換中は最後の文節を選択する。
+    MoveToEnd,
+    /// 選択中の文節を1文字伸ばす (Shift+→)
+    ExtendSegment,
+    /// 選択中の文節を1文字縮める (Shift+←)
+    ShrinkSegment,
 }
 
 /// エンジンの処理結果。
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct EngineOutput {
-    /// 確定してアプリケーションに渡すテキスト
+    /// 確定してアプリケーションに渡すテキスト（スニペットでは複数行になる）
     pub committed: String,
+    /// 確定したテキスト内のカーソル位置（文字単位）。None なら末尾。
+    pub commit_caret: Option<usize>,
+    /// 取り消した確定テキスト（UndoCommit のとき）。
+    /// フロントエンドはカーソルの前からこの文字列を削除する。
+    pub undone_commit: String,
     /// 現在の表示用テキスト（未確定文字列 or 選択中の候補）
     pub display: String,
+    /// 表示用テキストを属性ごとに分けたもの。連結すると display と一致する。
+    pub preedit: Vec<PreeditSpan>,
+    /// display 内のカーソル位置（文字単位）。
+    /// Composing 状態では入力位置、Converting 状態では選択中の文節の末尾。
+    pub caret: Option<usize>,
     /// 候補リスト内の選択インデックス
     pub candidate_index: Option<usize>,
+    /// 候補ウィンドウに表示するページ（Converting 状態のとき Some）
+    pub page: Option<CandidatePage>,
+    /// 各文節（Converting 状態のとき）
+    pub segments: Vec<SegmentView>,
+    /// 候補を選択中の文節の位置（Converting 状態のとき Some）
+    pub focused_segment: Option<usize>,
+    /// 入力中の読みから予測した候補（Composing 状態のとき）、
+    /// または確定した語に続く語の候補（確定直後の Direct 状態のとき）
+    pub predictions: Vec<Candidate>,
+}
+
+/// 出力用の文節の情報。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SegmentView {
+    /// 選択中の候補
+    pub text: String,
+    /// 文節の読み
+    pub reading: String,
+}
+
+/// 表示用テキストの部分の属性。フロントエンドの下線・強調の描き分けに使う。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreeditAttribute {
+    /// 入力中のかな
+    Input,
+    /// まだかなになっていないローマ字
+    PendingRomaji,
+    /// 変換済みの文節
+    Converted,
+    /// 候補を選択中の文節
+    Focused,
+}
+
+/// 同じ属性を持つ表示用テキストの部分。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreeditSpan {
+    pub text: String,
+    pub attribute: PreeditAttribute,
+}
+
+impl PreeditSpan {
+    fn new(text: &str, attribute: PreeditAttribute) -> Self {
+        Self {
+            text: text.to_string(),
+            attribute,
+        }
+    }
+}
+
+/// MS-IME 風の候補選択ラベル。
+pub const NUMBER_LABELS: &str = "123456789";
+
+/// SKK 風（ホームポジション）の候補選択ラベル。
+pub const SKK_LABELS: &str = "asdfjkl";
+
+/// IME をオフにするときの入力中・変換中の内容の扱い。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImeOffAction {
+    /// 確定する（変換中は選択中の候補、入力中はひらがな）
+    Commit,
+    /// 破棄する
+    Cancel,
+}
+
+/// 入力方式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputStyle {
+    /// 入力した読み全体を Space で変換する（MS-IME 風）
+    Standard,
+    /// 大文字で変換する読みの始まりを指定する SKK 方式
+    Skk,
+}
+
+/// エンジンの動作設定。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOptions {
+    /// 候補ウィンドウの1ページあたりの候補数
+    pub page_size: usize,
+    /// 候補の選択ラベル。ページ内の位置の順に1文字ずつ割り当てる。
+    pub candidate_labels: Vec<char>,
+    /// 入力中に予測候補を出すか
+    pub prediction: bool,
+    /// 予測候補を探すときに走査する辞書の読みの数の上限
+    pub prediction_scan_limit: usize,
+    /// 確定直後に、続けて確定したことのある語を候補として出すか
+    pub next_word_suggestion: bool,
+    /// IME をオフにするときの入力中・変換中の内容の扱い
+    pub ime_off_action: ImeOffAction,
+    /// 入力方式
+    pub input_style: InputStyle,
+    /// abbrev モード（英字をそのまま読みとして辞書を引く）を始めるキー。None なら使わない（既定）。
+    pub abbrev_key: Option<char>,
+    /// 「、」「。」を入力したとき、その前までを先頭の候補で変換して句読点と一緒に確定するか
+    pub auto_commit_on_punctuation: bool,
+    /// 入力中の読みを、その時点の先頭の候補で変換して表示するか（ライブ変換）
+    pub live_conversion: bool,
+    /// 絵文字の候補を出すか。無効ならユーザー辞書に学習済みの絵文字も出さない。
+    pub emoji: bool,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            candidate_labels: NUMBER_LABELS.chars().collect(),
+            prediction: true,
+            prediction_scan_limit: 200,
+            next_word_suggestion: true,
+            ime_off_action: ImeOffAction::Commit,
+            input_style: InputStyle::Standard,
+            abbrev_key: None,
+            auto_commit_on_punctuation: false,
+            live_conversion: false,
+            emoji: true,
+        }
+    }
+}
+
+impl EngineOptions {
+    /// 実際の1ページあたりの候補数。ラベルの数を超えないようにする。
+    fn effective_page_size(&self) -> usize {
+        if self.candidate_labels.is_empty() {
+            self.page_size.max(1)
+        } else {
+            self.page_size.clamp(1, self.candidate_labels.len())
+        }
+    }
+}
+
+/// 連文節変換の文節。読みと、その文節の候補リストを持つ。
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// 文節の読み（自立語 + 付属語）
+    reading: String,
+    /// 付属語の読み。候補の末尾にも同じ文字列が付く。
+    suffix: String,
+    /// 自立語が辞書にあるか（false なら候補はひらがな・カタカナ）
+    known: bool,
+    candidates: CandidateList,
+}
+
+impl Segment {
+    /// 文節の読みを返す。
+    pub fn reading(&self) -> &str {
+        &self.reading
+    }
+
+    /// 文節の候補を返す。
+    pub fn candidates(&self) -> &[Candidate] {
+        self.candidates.candidates()
+    }
+
+    /// 選択中の候補の文字列を返す。
+    pub fn current(&self) -> &str {
+        self.candidates.current().unwrap_or(&self.reading)
+    }
+
+    /// 確定履歴に残す単語（辞書にある文節は付属語を除いた候補）を返す。
+    fn context_word(&self) -> &str {
+        match self.stem() {
+            Some((_, word)) => word,
+            None => self.current(),
+        }
+    }
+
+    /// 選択中の候補を学習するか。辞書以外から生成した候補（日付・計算結果・
+    /// スニペットなど）は確定のたびに変わるため学習しない。記号辞書の候補も、
+    /// 学習するとユーザー辞書の候補として絵文字の設定と関係なく出てしまうので学習しない。
+    fn learnable(&self) -> bool {
+        self.candidates.current_candidate().is_some_and(|c| {
+            !matches!(
+                c.source,
+                CandidateSource::Generated | CandidateSource::Symbol
+            )
+        })
+    }
+
+    /// 自立語の読みと辞書にあるかが `b` と同じか（付属語は問わない）。
+    fn has_stem(&self, b: &Bunsetsu) -> bool {
+        self.known == b.known
+            && self.reading.strip_suffix(self.suffix.as_str()) == Some(b.stem.as_str())
+    }
+
+    /// 文節の区切りを返す。
+    fn bunsetsu(&self) -> Bunsetsu {
+        let stem = self
+            .reading
+            .strip_suffix(self.suffix.as_str())
+            .unwrap_or(&self.reading);
+        Bunsetsu {
+            stem: stem.to_string(),
+            suffix: self.suffix.clone(),
+            known: self.known,
+        }
+    }
+
+    /// 選択中の候補が辞書以外から生成したものか。
+    fn generated(&self) -> bool {
+        self.candidates
+            .current_candidate()
+            .is_some_and(|c| c.source == CandidateSource::Generated)
+    }
+
+    /// 学習に使う自立語の読みと選択中の候補を返す。辞書にない文節は None。
+    ///
+    /// 読みは選択中の候補のもの（予測候補では入力より長い）を使う。
+    fn stem(&self) -> Option<(&str, &str)> {
+        if !self.known {
+            return None;
+        }
+        let candidate = self.candidates.current_candidate()?;
+        let reading = candidate.reading.strip_suffix(self.suffix.as_str())?;
+        let word = candidate.text.strip_suffix(self.suffix.as_str())?;
+        Some((reading, word))
+    }
+
+    /// 打ち間違いを補正した読みの文節として、候補に `CORRECTED_ANNOTATION` の注釈を付ける。
+    fn mark_corrected(&mut self) {
+        let candidates = self
+            .candidates
+            .candidates()
+            .iter()
+            .cloned()
+            .map(|c| {
+                let annotation = match c.annotation {
+                    Some(ref a) => format!("{CORRECTED_ANNOTATION}: {a}"),
+                    None => CORRECTED_ANNOTATION.to_string(),
+                };
+                c.with_annotation(Some(&annotation))
+            })
+            .collect();
+        self.candidates = CandidateList::with_page_size(candidates, self.candidates.page_size());
+    }
+}
+
+/// 補正した読み `fixed` のうち、元の読み `reading` と異なる範囲（文字単位）を返す。
+///
+/// 先頭と末尾の一致する部分を除いた範囲。文字を削った補正でも、`fixed` が空でなければ
+/// 削った位置の1文字を含める。
+fn changed_range(reading: &str, fixed: &str) -> Range<usize> {
+    let prefix = reading
+        .chars()
+        .zip(fixed.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = reading
+        .chars()
+        .rev()
+        .zip(fixed.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let len = fixed.chars().count();
+    let start = prefix.min(len.saturating_sub(1));
+    let end = len.saturating_sub(suffix).max(start + 1).min(len);
+    start..end
+}
+
+/// 直前の確定。確定の取り消し (UndoCommit) に使う。
+struct LastCommit {
+    /// 確定したテキスト
+    committed: String,
+    /// 確定前の文節（選択していた候補を含む）
+    segments: Vec<Segment>,
+    focus: usize,
+    /// 確定前の入力
+    input: InputState,
+    reading: String,
+    /// 確定前の確定履歴
+    history: VecDeque<String>,
+    /// 確定時の学習を取り消すための記録
+    checkpoint: Option<Checkpoint>,
+}
+
+/// 予測候補を出すのに必要な入力の文字数。
+const PREDICTION_MIN_CHARS: usize = 2;
+
+/// 文脈として保持する直近の確定テキストの数。
+const HISTORY_SIZE: usize = 8;
+
+/// 読みに候補があるかのキャッシュに保持する読みの数の上限。超えたら全て捨てる。
+const LOOKUP_CACHE_SIZE: usize = 4096;
+
+/// 入力のたびに変換し直すライブ変換のための検索結果のキャッシュ。
+///
+/// 辞書・提供元・動作設定が変わったら捨てる。
+#[derive(Default)]
+struct LookupCache {
+    /// 読み → いずれかの提供元に候補があるか
+    known: HashMap<String, bool>,
+    /// 直前のライブ変換の結果
+    live: Option<LiveConversion>,
+    /// ユーザー辞書の逆引き索引（再変換のときに作り、ユーザー辞書が変わったら捨てる）
+    user_reverse_index: Option<ReverseIndex>,
+}
+
+/// ライブ変換の結果と、それを求めたときの読みと文脈。
+struct LiveConversion {
+    reading: String,
+    typed: Option<String>,
+    prev: Option<String>,
+    /// 先頭の候補だけを持つ文節。どの文節にも候補がなければ None。
+    segments: Option<Vec<LiveSegment>>,
+}
+
+impl LiveConversion {
+    /// 表示するテキスト。文節がなければ読みのまま。
+    fn text(&self, reading: &str) -> String {
+        match self.segments {
+            Some(ref segments) => segments.iter().map(|l| l.segment.current()).collect(),
+            None => reading.to_string(),
+        }
+    }
+}
+
+/// ライブ変換の文節と、それを引いたときの文脈。
+struct LiveSegment {
+    /// 先頭の候補だけを持つ文節
+    segment: Segment,
+    /// 付属語を付ける前の先頭の候補
+    stem: Candidate,
+    /// 直前の文節の単語（先頭の文節なら直前の確定テキスト）
+    prev: Option<String>,
+    /// 打ち間違いを補正した読みの文節か
+    corrected: bool,
 }
 
 /// 変換エンジン。
@@ -55,22 +439,43 @@ pub struct ConversionEngine {
     input: InputState,
     dict: Option<Dictionary>,
     user_dict: Option<UserDictionary>,
-    candidates: Option<CandidateList>,
+    /// 変換中の文節（Converting 状態のとき1つ以上）
+    segments: Vec<Segment>,
+    /// 候補を選択中の文節の位置
+    focus: usize,
     /// 変換時の読み（ひらがな）を保持する。
     reading: String,
+    /// 確定直後に出した次の語の候補。Direct 状態で次のキーを処理するまで保持する。
+    suggestions: Vec<Candidate>,
+    /// 直前の確定。Direct 状態で次のキーを処理するまで保持する。
+    last_commit: Option<LastCommit>,
+    /// 直近の確定テキスト（古い順）。候補の並び替えの文脈に使う。
+    history: VecDeque<String>,
+    /// キーワードによる候補の並び替えテーブル
+    context_priority: Option<ContextPriority>,
+    /// ローマ字の打ち間違いの補正テーブル
+    typo_correction: Option<TypoCorrection>,
+    /// 文節区切りのコストモデル
+    cost_model: CostModel,
+    /// システム辞書の逆引き索引（最初の再変換のときに作る）
+    reverse_index: Option<ReverseIndex>,
+    /// 辞書以外の候補の提供元（登録順）
+    providers: Vec<(Box<dyn CandidateProvider>, MergeRule)>,
+    /// 提供元の名前 → 登録時の規則に代えて使うマージ規則
+    merge_rules: HashMap<String, MergeRule>,
+    options: EngineOptions,
+    /// IME がオンか。オフの間は ToggleIme 以外のコマンドを処理しない。
+    enabled: bool,
+    /// SKK 方式の入力の状態
+    skk: SkkState,
+    /// 検索結果のキャッシュ
+    cache: RefCell<LookupCache>,
 }
 
 impl ConversionEngine {
     /// 新しい変換エンジンを作成する。
     pub fn new(dict: Option<Dictionary>) -> Self {
-        Self {
-            state: EngineState::Direct,
-            input: InputState::new(),
-            dict,
-            user_dict: None,
-            candidates: None,
-            reading: String::new(),
-        }
+        Self::new_with_user_dict(dict, None)
     }
 
     /// ユーザー辞書付きの変換エンジンを作成する。
@@ -80,24 +485,124 @@ impl ConversionEngine {
             input: InputState::new(),
             dict,
             user_dict,
-            candidates: None,
+            segments: Vec::new(),
+            focus: 0,
             reading: String::new(),
+            suggestions: Vec::new(),
+            last_commit: None,
+            history: VecDeque::new(),
+            context_priority: None,
+            typo_correction: None,
+            cost_model: CostModel::default(),
+            reverse_index: None,
+            providers: Vec::new(),
+            merge_rules: HashMap::new(),
+            options: EngineOptions::default(),
+            enabled: true,
+            skk: SkkState::default(),
+            cache: RefCell::default(),
         }
     }
 
+    /// 候補の提供元を追加する。
+    ///
+    /// ユーザー辞書の優先度は `USER_DICTIONARY_PRIORITY`、システム辞書は
+    /// `SYSTEM_DICTIONARY_PRIORITY`。これらとの大小で候補の並び位置が決まる。
+    pub fn add_provider(&mut self, provider: Box<dyn CandidateProvider>, rule: MergeRule) {
+        self.providers.push((provider, rule));
+        self.clear_cache();
+    }
+
+    /// 提供元のマージ規則を上書きする。
+    ///
+    /// `name` は提供元の名前（ユーザー辞書は `user`、システム辞書は `system`）。
+    /// 提供元の追加の前後どちらで呼んでもよい。
+    pub fn set_merge_rule(&mut self, name: &str, rule: MergeRule) {
+        self.merge_rules.insert(name.to_string(), rule);
+        self.clear_cache();
+    }
+
+    /// 動作設定を変更する。
+    pub fn set_options(&mut self, options: EngineOptions) {
+        self.options = options;
+        self.clear_cache();
+    }
+
+    /// 現在の動作設定を返す。
+    pub fn options(&self) -> &EngineOptions {
+        &self.options
+    }
+
+    /// コンテキスト優先テーブルを設定する。
+    pub fn set_context_priority(&mut self, table: ContextPriority) {
+        self.context_priority = Some(table);
+        self.clear_cache();
+    }
+
+    /// タイポ補正テーブルを設定する。
+    pub fn set_typo_correction(&mut self, table: TypoCorrection) {
+        self.typo_correction = Some(table);
+        self.clear_cache();
+    }
+
+    /// 直近の確定テキストを古い順に返す。
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().map(|s| s.as_str())
+    }
+
+    /// 確定履歴を消去する（フォーカス移動などで文脈が途切れたとき）。
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.forget_last_commit();
+    }
+
+    /// 確定直後に出した次の語の候補と、確定の取り消しに使う直前の確定を破棄する。
+    ///
+    /// エンジンが処理しないキー（Enter・矢印キーなど）をアプリケーションに渡したときに呼ぶ。
+    pub fn forget_last_commit(&mut self) {
+        self.suggestions.clear();
+        self.last_commit = None;
+    }
+
     /// ユーザー辞書の可変参照を返す。
     pub fn user_dict_mut(&mut self) -> Option<&mut UserDictionary> {
+        self.clear_cache();
         self.user_dict.as_mut()
     }
 
+    /// IME がオンかどうかを返す。
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// IME のオン/オフを設定する。状態が変わるときだけ ToggleIme と同じく処理する。
+    ///
+    /// フロントエンドが OS 側のオン/オフ（言語バーなど）に合わせるのに使う。
+    pub fn set_enabled(&mut self, enabled: bool) -> EngineOutput {
+        if self.enabled == enabled {
+            return self.empty_output();
+        }
+        self.toggle_ime()
+    }
+
     /// 現在の状態を返す。
     pub fn state(&self) -> EngineState {
         self.state
     }
 
-    /// 変換候補リストを返す（Converting 状態のとき Some）。
-    pub fn candidates(&self) -> Option<&[String]> {
-        self.candidates.as_ref().map(|cl| cl.candidates())
+    /// 選択中の文節の変換候補リストを返す（Converting 状態のとき Some）。
+    pub fn candidates(&self) -> Option<&[Candidate]> {
+        self.segments.get(self.focus).map(|seg| seg.candidates())
+    }
+
+    /// 変換中の文節を返す（Converting 状態以外では空）。
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// 文節区切りのコストモデルを変更する。
+    pub fn set_cost_model(&mut self, model: CostModel) {
+        self.cost_model = model;
     }
 
     /// 変換時の読み（ひらがな）を返す。
@@ -105,262 +610,1249 @@ impl ConversionEngine {
         &self.reading
     }
 
+    /// 現在の状態でコマンドを処理するか。
+    ///
+    /// Direct 状態では文字入力以外のキー（Enter・矢印キーなど）を
+    /// アプリケーションに渡すため false を返す。ただし確定直後に次の語の候補が
+    /// あれば、その選択を開始する Predict を、直前の確定があれば UndoCommit を処理する。
+    /// IME がオフの間は ToggleIme だけを処理する。
+    pub fn accepts(&self, command: &EngineCommand) -> bool {
+        if *command == EngineCommand::ToggleIme {
+            return true;
+        }
+        if !self.enabled {
+            return false;
+        }
+        if self.options.input_style == InputStyle::Skk {
+            return self.skk_accepts(command);
+        }
+        match self.state {
+            EngineState::Direct => match command {
+                EngineCommand::InsertChar(_) => true,
+                EngineCommand::Predict => !self.suggestions.is_empty(),
+                EngineCommand::UndoCommit => self.last_commit.is_some(),
+                _ => false,
+            },
+            EngineState::Composing | EngineState::Converting => true,
+        }
+    }
+
     /// コマンドを処理し、結果を返す。
     pub fn process(&mut self, command: EngineCommand) -> EngineOutput {
+        if !self.enabled && command != EngineCommand::ToggleIme {
+            return self.empty_output();
+        }
+        if self.options.input_style == InputStyle::Skk && command != EngineCommand::ToggleIme {
+            return self.process_skk(command);
+        }
         match (&self.state, &command) {
+            (_, EngineCommand::ToggleIme) => self.toggle_ime(),
+
             // === Direct ===
             (EngineState::Direct, EngineCommand::InsertChar(ch)) => {
-                self.input.feed_char(*ch);
-                self.state = EngineState::Composing;
-                self.composing_output()
+                self.forget_last_commit();
+                self.start_input(*ch);
+                self.inserted_output()
+            }
+            (EngineState::Direct, EngineCommand::Predict) => self.do_suggest(),
+            (EngineState::Direct, EngineCommand::UndoCommit) => self.undo_commit(),
+            (EngineState::Direct, _) => {
+                self.forget_last_commit();
+                self.empty_output()
             }
-            (EngineState::Direct, _) => self.empty_output(),
 
             // === Composing ===
             (EngineState::Composing, EngineCommand::InsertChar(ch)) => {
                 self.input.feed_char(*ch);
-                self.composing_output()
+                self.inserted_output()
             }
             (EngineState::Composing, EngineCommand::Convert) => self.do_convert(),
-            (EngineState::Composing, EngineCommand::Commit) => {
+            (EngineState::Composing, EngineCommand::Predict) => self.do_predict(),
+            (EngineState::Composing, EngineCommand::Commit)
+            | (EngineState::Composing, EngineCommand::KanaMode)
+                if self.live_conversion_active() =>
+            {
+                self.commit_live_conversion()
+            }
+            (EngineState::Composing, EngineCommand::Commit)
+            | (EngineState::Composing, EngineCommand::KanaMode) => {
                 self.input.flush();
                 let committed = self.input.output().to_string();
+                self.push_history(&committed);
                 self.input.reset();
                 self.state = EngineState::Direct;
-                EngineOutput {
-                    committed,
-                    display: String::new(),
-                    candidate_index: None,
-                }
+                self.committed_output(committed)
             }
             (EngineState::Composing, EngineCommand::Cancel) => {
                 self.input.reset();
                 self.state = EngineState::Direct;
                 self.empty_output()
             }
-            (EngineState::Composing, EngineCommand::Backspace) => {
-                self.input.backspace();
+            (EngineState::Composing, EngineCommand::Backspace)
+            | (EngineState::Composing, EngineCommand::Delete) => {
+                if command == EngineCommand::Backspace {
+                    self.input.backspace();
+                } else {
+                    self.input.delete();
+                }
                 if self.input.is_empty() {
+                    self.input.reset();
                     self.state = EngineState::Direct;
+                    return self.empty_output();
                 }
                 self.composing_output()
             }
+            (EngineState::Composing, EngineCommand::MoveLeft) => {
+                self.input.move_left();
+                self.composing_output()
+            }
+            (EngineState::Composing, EngineCommand::MoveRight) => {
+                self.input.move_right();
+                self.composing_output()
+            }
+            (EngineState::Composing, EngineCommand::MoveToStart) => {
+                self.input.move_to_start();
+                self.composing_output()
+            }
+            (EngineState::Composing, EngineCommand::MoveToEnd) => {
+                self.input.move_to_end();
+                self.composing_output()
+            }
             (EngineState::Composing, _) => self.composing_output(),
 
             // === Converting ===
             (EngineState::Converting, EngineCommand::NextCandidate)
-            | (EngineState::Converting, EngineCommand::Convert) => {
-                if let Some(ref mut cl) = self.candidates {
+            | (EngineState::Converting, EngineCommand::Convert)
+            | (EngineState::Converting, EngineCommand::Predict) => {
+                if let Some(cl) = self.focused_candidates_mut() {
                     cl.next();
                 }
                 self.converting_output()
             }
             (EngineState::Converting, EngineCommand::PrevCandidate) => {
-                if let Some(ref mut cl) = self.candidates {
+                if let Some(cl) = self.focused_candidates_mut() {
                     cl.prev();
                 }
                 self.converting_output()
             }
-            (EngineState::Converting, EngineCommand::Commit) => {
-                let committed = self
-                    .candidates
-                    .as_ref()
-                    .and_then(|cl| cl.select())
-                    .unwrap_or_default();
-                // ユーザー辞書に学習データを記録
-                if let Some(ref mut ud) = self.user_dict
-                    && !committed.is_empty()
-                    && !self.reading.is_empty()
-                {
-                    ud.record(&self.reading, &committed);
+            (EngineState::Converting, EngineCommand::NextPage) => {
+                if let Some(cl) = self.focused_candidates_mut() {
+                    cl.next_page();
                 }
-                self.candidates = None;
-                self.input.reset();
-                self.state = EngineState::Direct;
-                EngineOutput {
-                    committed,
-                    display: String::new(),
-                    candidate_index: None,
+                self.converting_output()
+            }
+            (EngineState::Converting, EngineCommand::PrevPage) => {
+                if let Some(cl) = self.focused_candidates_mut() {
+                    cl.prev_page();
+                }
+                self.converting_output()
+            }
+            (EngineState::Converting, EngineCommand::MoveLeft) => {
+                self.focus = self.focus.saturating_sub(1);
+                self.converting_output()
+            }
+            (EngineState::Converting, EngineCommand::MoveRight) => {
+                if self.focus + 1 < self.segments.len() {
+                    self.focus += 1;
                 }
+                self.converting_output()
+            }
+            (EngineState::Converting, EngineCommand::MoveToStart) => {
+                self.focus = 0;
+                self.converting_output()
             }
-            (EngineState::Converting, EngineCommand::Cancel) => {
-                self.candidates = None;
+            (EngineState::Converting, EngineCommand::MoveToEnd) => {
+                self.focus = self.segments.len().saturating_sub(1);
+                self.converting_output()
+            }
+            (EngineState::Converting, EngineCommand::Delete)
+            | (EngineState::Converting, EngineCommand::UndoCommit) => self.converting_output(),
+            (EngineState::Converting, EngineCommand::ExtendSegment) => self.resize_segment(true),
+            (EngineState::Converting, EngineCommand::ShrinkSegment) => self.resize_segment(false),
+            (EngineState::Converting, EngineCommand::SelectCandidate(position)) => {
+                let position = *position;
+                self.select_on_page(position)
+            }
+            (EngineState::Converting, EngineCommand::Commit)
+            | (EngineState::Converting, EngineCommand::KanaMode) => self.commit_candidate(),
+            (EngineState::Converting, EngineCommand::Cancel)
+            | (EngineState::Converting, EngineCommand::Backspace) => {
+                // 変換をキャンセルして Composing に戻る。次の語の候補の選択中なら Direct に戻る
+                self.segments.clear();
+                if self.input.is_empty() {
+                    self.state = EngineState::Direct;
+                    return self.empty_output();
+                }
                 self.state = EngineState::Composing;
                 self.composing_output()
             }
+            (EngineState::Converting, EngineCommand::InsertChar(ch))
+                if self.label_position(*ch).is_some() =>
+            {
+                // 表示中のページのラベル → その候補を選択して確定する
+                let position = self.label_position(*ch).unwrap_or_default();
+                self.select_on_page(position)
+            }
             (EngineState::Converting, EngineCommand::InsertChar(ch)) => {
                 // 現在の候補を確定し、新しい文字で Composing を開始する
-                let committed = self
-                    .candidates
-                    .as_ref()
-                    .and_then(|cl| cl.select())
-                    .unwrap_or_default();
-                // ユーザー辞書に学習データを記録
-                if let Some(ref mut ud) = self.user_dict
-                    && !committed.is_empty()
-                    && !self.reading.is_empty()
-                {
-                    ud.record(&self.reading, &committed);
-                }
-                self.candidates = None;
-                self.input.reset();
-                self.input.feed_char(*ch);
-                self.state = EngineState::Composing;
-                let composing = self.composing_output();
+                let mut committed = self.commit_candidate().committed;
+                self.forget_last_commit();
+                self.start_input(*ch);
+                let output = self.inserted_output();
+                committed.push_str(&output.committed);
                 EngineOutput {
                     committed,
-                    display: composing.display,
-                    candidate_index: None,
+                    ..output
                 }
             }
-            (EngineState::Converting, EngineCommand::Backspace) => {
-                // 変換をキャンセルして Composing に戻る（Cancel と同じ動作）
-                self.candidates = None;
-                self.state = EngineState::Composing;
-                self.composing_output()
-            }
         }
     }
 
-    /// 変換を実行する。候補があれば Converting へ、なければひらがな確定。
-    fn do_convert(&mut self) -> EngineOutput {
-        self.input.flush();
-        let hiragana = self.input.output().to_string();
-        self.reading = hiragana.clone();
-
-        // ユーザー辞書とシステム辞書の候補をマージ
-        let merged = self.merge_candidates(&hiragana);
-
-        if merged.is_empty() {
-            // 候補なし → ひらがなを確定
-            let committed = hiragana;
-            self.input.reset();
-            self.state = EngineState::Direct;
-            EngineOutput {
-                committed,
-                display: String::new(),
-                candidate_index: None,
-            }
+    /// 入力を始める。abbrev モードのキーなら、英字をそのまま読みとする入力を始める。
+    fn start_input(&mut self, ch: char) {
+        if Some(ch) == self.options.abbrev_key {
+            self.input.start_literal();
         } else {
-            let cl = CandidateList::new(merged);
-            let display = cl.current().unwrap_or("").to_string();
-            let idx = cl.index();
-            self.candidates = Some(cl);
-            self.state = EngineState::Converting;
-            EngineOutput {
-                committed: String::new(),
-                display,
-                candidate_index: Some(idx),
-            }
-        }
-    }
-
-    /// ユーザー辞書とシステム辞書の候補をマージする。
-    /// ユーザー辞書の候補を先頭に配置し、システム辞書の候補のうち
-    /// ユーザー辞書に含まれないものを後ろに追加する。
-    fn merge_candidates(&self, reading: &str) -> Vec<String> {
-        let user_cands: Vec<String> = self
-            .user_dict
-            .as_ref()
-            .and_then(|ud| ud.lookup(reading))
-            .map(|c| c.to_vec())
-            .unwrap_or_default();
-
-        let system_cands: Vec<String> = self
-            .dict
-            .as_ref()
-            .and_then(|d| d.lookup(reading))
-            .map(|c| c.iter().map(|s| s.to_string()).collect())
-            .unwrap_or_default();
-
-        let mut merged = user_cands;
-        for c in system_cands {
-            if !merged.contains(&c) {
-                merged.push(c);
-            }
+            self.input.feed_char(ch);
         }
-        merged
+        self.state = EngineState::Composing;
     }
 
-    /// Composing 状態の EngineOutput を組み立てる。
-    fn composing_output(&self) -> EngineOutput {
-        let display = format!("{}{}", self.input.output(), self.input.pending());
-        EngineOutput {
-            committed: String::new(),
-            display,
-            candidate_index: None,
+    /// 文字を入力した後の EngineOutput を返す。
+    /// `auto_commit_on_punctuation` が有効で句読点を入力したときは、その前までを変換して確定する。
+    fn inserted_output(&mut self) -> EngineOutput {
+        if self.punctuation_typed() {
+            return self.commit_on_punctuation();
         }
+        self.composing_output()
     }
 
-    /// Converting 状態の EngineOutput を組み立てる。
-    fn converting_output(&self) -> EngineOutput {
-        match &self.candidates {
-            Some(cl) => EngineOutput {
-                committed: String::new(),
-                display: cl.current().unwrap_or("").to_string(),
-                candidate_index: Some(cl.index()),
-            },
-            None => self.empty_output(),
+    /// カーソルの直前に、いま入力した句読点があるか。
+    /// 数字に続く「。」「、」は小数点・桁区切りとみなす（計算式の入力のため）。
+    fn punctuation_typed(&self) -> bool {
+        if !self.options.auto_commit_on_punctuation
+            || self.input.is_literal()
+            || !self.input.pending().is_empty()
+        {
+            return false;
         }
+        let (before, _) = self.input.split_output();
+        let mut chars = before.chars().rev();
+        matches!(chars.next(), Some('、' | '。'))
+            && !chars.next().is_some_and(|c| c.is_ascii_digit())
     }
 
-    /// 空の EngineOutput を返す。
-    fn empty_output(&self) -> EngineOutput {
+    /// カーソルの前の句読点までを確定し、カーソルの後ろの入力で入力を続ける。
+    ///
+    /// 句読点の前の読みは文節に区切って各文節の先頭の候補で変換する。
+    /// 確定した内容は取り消せない。
+    fn commit_on_punctuation(&mut self) -> EngineOutput {
+        let (before, after) = self.input.split_output();
+        let (mut before, after) = (before.to_string(), after.to_string());
+        let punctuation = before.pop().unwrap_or_default();
+        let mut committed = String::new();
+        if !before.is_empty() {
+            self.input.set_text(&before);
+            let output = self.do_convert();
+            committed = if self.state == EngineState::Converting {
+                self.commit_candidate().committed
+            } else {
+                output.committed
+            };
+        }
+        committed.push(punctuation);
+        self.forget_last_commit();
+        if after.is_empty() {
+            self.input.reset();
+            self.state = EngineState::Direct;
+            return self.committed_output(committed);
+        }
+        self.input.set_text(&after);
+        self.input.move_to_start();
+        self.state = EngineState::Composing;
         EngineOutput {
-            committed: String::new(),
-            display: String::new(),
-            candidate_index: None,
+            committed,
+            ..self.composing_output()
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::Path;
+    /// 変換を実行する。読みを文節に区切り、辞書にある文節があれば Converting へ、
+    /// なければひらがな確定。abbrev モードでは英字の読み全体を1つの文節として辞書を引く。
+    fn do_convert(&mut self) -> EngineOutput {
+        self.input.flush();
+        let reading = self.input.output().to_string();
+        self.reading = reading.clone();
+
+        let correct = !self.input.is_literal();
+        let Some(segments) = self.conversion_segments(&reading, correct) else {
+            // 候補なし → 読みをそのまま確定
+            return self.commit_reading(reading);
+        };
+        self.segments = segments;
+        self.focus = 0;
+        self.state = EngineState::Converting;
+        self.converting_output()
+    }
 
-    fn test_engine() -> ConversionEngine {
-        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
-        ConversionEngine::new(Some(dict))
+    /// 読みをそのまま確定し、Direct に戻る。
+    fn commit_reading(&mut self, reading: String) -> EngineOutput {
+        self.push_history(&reading);
+        self.input.reset();
+        self.state = EngineState::Direct;
+        self.committed_output(reading)
     }
 
-    fn engine_without_dict() -> ConversionEngine {
-        ConversionEngine::new(None)
+    /// 読みを文節に区切り、各文節の候補を引く。どの文節にも候補がなければ None。
+    ///
+    /// `correct` が真で、候補のない文節があれば、打ち間違いを補正した読みを試す。
+    fn conversion_segments(&self, reading: &str, correct: bool) -> Option<Vec<Segment>> {
+        let mut segments: Vec<Segment> = Vec::new();
+        for (b, corrected) in self.conversion_bunsetsu(reading, correct)? {
+            // 直前の文節の先頭候補を文脈として並び替える
+            let prev = match segments.last() {
+                Some(seg) => Some(seg.context_word().to_string()),
+                None => self.history.back().cloned(),
+            };
+            let mut seg = self.make_segment(b, prev.as_deref());
+            if corrected {
+                seg.mark_corrected();
+            }
+            segments.push(seg);
+        }
+        Some(segments)
     }
 
-    // === 初期状態 ===
+    /// 読みを変換する文節に区切り、各文節を打ち間違いを補正したものかとともに返す。
+    /// どの文節にも候補がなければ None。
+    fn conversion_bunsetsu(&self, reading: &str, correct: bool) -> Option<Vec<(Bunsetsu, bool)>> {
+        // 補正した文節の読みの範囲（文字単位）
+        let mut corrected = None;
+        // abbrev の英字と計算式は読み全体を1つの文節とする
+        let bunsetsu = if self.input.is_literal() || calculator::is_expression(reading) {
+            vec![Bunsetsu {
+                known: self.has_candidates(reading),
+                stem: reading.to_string(),
+                suffix: String::new(),
+            }]
+        } else {
+            let bunsetsu = self.segment_reading(reading);
+            match correct
+                .then(|| self.corrected_bunsetsu(reading, &bunsetsu))
+                .flatten()
+            {
+                Some((range, fixed)) => {
+                    corrected = Some(range);
+                    fixed
+                }
+                None => bunsetsu,
+            }
+        };
+        if bunsetsu.iter().all(|b| !b.known) {
+            return None;
+        }
 
-    #[test]
-    fn initial_state_is_direct() {
-        let engine = test_engine();
-        assert_eq!(engine.state(), EngineState::Direct);
+        let mut start = 0;
+        let marked = bunsetsu
+            .into_iter()
+            .map(|b| {
+                let end = start + b.reading().chars().count();
+                let overlaps = corrected
+                    .as_ref()
+                    .is_some_and(|range| start < range.end && range.start < end);
+                start = end;
+                (b, overlaps)
+            })
+            .collect();
+        Some(marked)
     }
 
-    // === Direct → Composing ===
-
-    #[test]
-    fn insert_char_transitions_to_composing() {
-        let mut engine = test_engine();
-        engine.process(EngineCommand::InsertChar('k'));
-        assert_eq!(engine.state(), EngineState::Composing);
+    /// 読みを文節に区切る。
+    fn segment_reading(&self, reading: &str) -> Vec<Bunsetsu> {
+        segmenter::segment(reading, &self.cost_model, |r| self.has_candidates(r))
     }
 
-    // === Composing → Converting ===
+    /// ライブ変換を表示するか。abbrev モードとカーソルが末尾にないときは読みを表示する。
+    fn live_conversion_active(&self) -> bool {
+        self.options.live_conversion
+            && !self.input.is_literal()
+            && self.input.split_output().1.is_empty()
+    }
 
-    #[test]
-    fn convert_transitions_to_converting() {
-        let mut engine = test_engine();
-        for ch in "kanji".chars() {
-            engine.process(EngineCommand::InsertChar(ch));
+    /// 確定するときと同じく未確定のローマ字を確定した読みを、各文節の先頭の候補で
+    /// 変換したテキスト。候補がなければ読みのまま。
+    ///
+    /// 未確定のローマ字があるうちは打ち間違いの補正を試さない。
+    /// 読みと文脈が直前と同じなら前回の結果を使う。
+    fn live_conversion(&self) -> String {
+        let mut input = self.input.clone();
+        input.flush();
+        let reading = input.output();
+        let typed = self.input.typed();
+        let prev = self.history.back().map(String::as_str);
+        let mut cache = self.cache.borrow_mut();
+        if let Some(live) = &cache.live
+            && live.reading == reading
+            && live.typed.as_deref() == typed
+            && live.prev.as_deref() == prev
+        {
+            return live.text(reading);
         }
-        engine.process(EngineCommand::Convert);
-        assert_eq!(engine.state(), EngineState::Converting);
-        assert!(engine.candidates().is_some());
+        let previous = cache
+            .live
+            .take()
+            .and_then(|l| l.segments)
+            .unwrap_or_default();
+        drop(cache);
+        let correct = self.input.pending().is_empty();
+        let segments = self.conversion_bunsetsu(reading, correct).map(|bunsetsu| {
+            let mut segments: Vec<LiveSegment> = Vec::new();
+            for (b, corrected) in bunsetsu {
+                let prev = match segments.last() {
+                    Some(live) => Some(live.segment.context_word().to_string()),
+                    None => self.history.back().cloned(),
+                };
+                // 自立語の読みと文脈が前回と同じ文節は候補を引き直さない
+                let reused = previous
+                    .iter()
+                    .find(|live| live.prev == prev && live.segment.has_stem(&b));
+                let stem = match reused {
+                    Some(live) => live.stem.clone(),
+                    None => self.top_stem(&b, prev.as_deref()),
+                };
+                let mut segment = self.segment_with(b, vec![stem.clone()]);
+                if corrected {
+                    segment.mark_corrected();
+                }
+                segments.push(LiveSegment {
+                    segment,
+                    stem,
+                    prev,
+                    corrected,
+                });
+            }
+            segments
+        });
+        let live = LiveConversion {
+            reading: reading.to_string(),
+            typed: typed.map(str::to_string),
+            prev: prev.map(str::to_string),
+            segments,
+        };
+        let text = live.text(reading);
+        self.cache.borrow_mut().live = Some(live);
+        text
     }
 
-    // === Converting → Direct (Commit) ===
+    /// ライブ変換の表示どおりに確定する。
+    ///
+    /// 表示した文節を候補を揃えて作り直し、先頭の候補で確定する。変換して確定したのと
+    /// 同じく学習し、直後の UndoCommit で取り消せる。どの文節にも候補がなければ読みを確定する。
+    fn commit_live_conversion(&mut self) -> EngineOutput {
+        self.live_conversion();
+        let live = self.cache.borrow_mut().live.take();
+        self.input.flush();
+        let reading = self.input.output().to_string();
+        self.reading = reading.clone();
+        let Some(segments) = live.and_then(|l| l.segments) else {
+            return self.commit_reading(reading);
+        };
+        self.segments = segments
+            .into_iter()
+            .map(|live| {
+                let mut segment = self.make_segment(live.segment.bunsetsu(), live.prev.as_deref());
+                if live.corrected {
+                    segment.mark_corrected();
+                }
+                segment
+            })
+            .collect();
+        self.focus = 0;
+        self.state = EngineState::Converting;
+        self.commit_candidate()
+    }
 
-    #[test]
+    /// 打鍵したローマ字を補正して文節に区切り直し、候補のない文節が減る区切りを返す。
+    ///
+    /// 補正した読みのうち元の読みと異なる範囲（文字単位）と、補正した読みの文節を返す。
+    /// 候補のない文節の文字数がいちばん少ないもの（同じなら先に試したもの）を選ぶ。
+    /// 候補のない文節がない、テーブルがない、打鍵したローマ字が分からない、
+    /// 候補のない文節が減る補正がなければ None。
+    fn corrected_bunsetsu(
+        &self,
+        reading: &str,
+        bunsetsu: &[Bunsetsu],
+    ) -> Option<(Range<usize>, Vec<Bunsetsu>)> {
+        let unknown_len = |bunsetsu: &[Bunsetsu]| -> usize {
+            bunsetsu
+                .iter()
+                .filter(|b| !b.known)
+                .map(|b| b.reading().chars().count())
+                .sum()
+        };
+        let mut fewest = unknown_len(bunsetsu);
+        if fewest == 0 {
+            return None;
+        }
+        let table = self.typo_correction.as_ref()?;
+        let typed = self.input.typed()?;
+        let mut best = None;
+        for romaji in table.alternatives(typed) {
+            let mut input = InputState::new();
+            for ch in romaji.chars() {
+                input.feed_char(ch);
+            }
+            input.flush();
+            let fixed = input.output();
+            let fixed_bunsetsu = self.segment_reading(fixed);
+            let unknown = unknown_len(&fixed_bunsetsu);
+            if unknown < fewest {
+                fewest = unknown;
+                best = Some((changed_range(reading, fixed), fixed_bunsetsu));
+                if unknown == 0 {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// IME のオン/オフを切り替える。
+    ///
+    /// オフにするときは、入力中・変換中の内容を `ime_off_action` に従って
+    /// 確定または破棄し、Direct に戻る。
+    fn toggle_ime(&mut self) -> EngineOutput {
+        if !self.enabled {
+            self.enabled = true;
+            return self.empty_output();
+        }
+        let output = match (self.state, self.options.ime_off_action) {
+            (EngineState::Direct, _) => self.empty_output(),
+            (_, ImeOffAction::Commit) => self.process(EngineCommand::Commit),
+            (_, ImeOffAction::Cancel) => {
+                self.segments.clear();
+                self.input.reset();
+                self.skk.clear();
+                self.state = EngineState::Direct;
+                self.empty_output()
+            }
+        };
+        self.forget_last_commit();
+        self.enabled = false;
+        EngineOutput {
+            predictions: Vec::new(),
+            ..output
+        }
+    }
+
+    /// 確定済みのテキストを再変換する。入力中・変換中の内容は破棄する。
+    ///
+    /// 逆引き索引でテキストを単語・かな・読めない文字に分け、読める部分は
+    /// 読みを文節に区切り直して Converting へ移る。元のテキストと区切りが
+    /// 一致する文節は元の文字列を先頭の候補にする。読めない文字はそのままの
+    /// 1つの文節とする。読める部分がなければ何もしない。
+    pub fn reconvert(&mut self, text: &str) -> EngineOutput {
+        if self.reverse_index.is_none() {
+            self.reverse_index = self.dict.as_ref().map(ReverseIndex::from_dictionary);
+        }
+        let user_index = self
+            .cache
+            .borrow_mut()
+            .user_reverse_index
+            .take()
+            .or_else(|| {
+                self.user_dict
+                    .as_ref()
+                    .map(ReverseIndex::from_user_dictionary)
+            });
+        let indexes: Vec<&ReverseIndex> =
+            user_index.iter().chain(self.reverse_index.iter()).collect();
+        let pieces = reverse_index::split(text, &indexes);
+        self.cache.borrow_mut().user_reverse_index = user_index;
+        if pieces.iter().all(|p| p.kind == PieceKind::Unknown) {
+            return self.empty_output();
+        }
+
+        self.forget_last_commit();
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut run: Vec<Piece> = Vec::new();
+        for piece in pieces {
+            if piece.kind != PieceKind::Unknown {
+                run.push(piece);
+                continue;
+            }
+            let readable = std::mem::take(&mut run);
+            self.reconvert_run(&readable, &mut segments);
+            segments.push(Segment {
+                reading: piece.surface.clone(),
+                suffix: String::new(),
+                known: false,
+                candidates: CandidateList::with_page_size(
+                    vec![Candidate::new(
+                        &piece.surface,
+                        &piece.surface,
+                        CandidateSource::Generated,
+                    )],
+                    self.options.effective_page_size(),
+                ),
+            });
+        }
+        self.reconvert_run(&run, &mut segments);
+
+        self.reading = segments.iter().map(|s| s.reading.as_str()).collect();
+        self.input.set_text(&self.reading);
+        self.segments = segments;
+        self.focus = 0;
+        self.state = EngineState::Converting;
+        self.converting_output()
+    }
+
+    /// 再変換で、読める断片の並びを文節に区切って `segments` に追加する。
+    fn reconvert_run(&self, run: &[Piece], segments: &mut Vec<Segment>) {
+        if run.is_empty() {
+            return;
+        }
+        // 断片の境界（読みの文字位置 → 元のテキストのバイト位置）
+        let mut boundaries: Vec<(usize, usize)> = vec![(0, 0)];
+        let mut surface = String::new();
+        let mut reading = String::new();
+        for piece in run {
+            surface.push_str(&piece.surface);
+            reading.push_str(&piece.reading);
+            boundaries.push((reading.chars().count(), surface.len()));
+        }
+        let boundary = |pos: usize| boundaries.iter().find(|(r, _)| *r == pos).map(|b| b.1);
+
+        let mut start = 0;
+        for b in segmenter::segment(&reading, &self.cost_model, |r| self.has_candidates(r)) {
+            let end = start + b.reading().chars().count();
+            let prev = match segments.last() {
+                Some(seg) => Some(seg.context_word().to_string()),
+                None => self.history.back().cloned(),
+            };
+            let mut seg = self.make_segment(b, prev.as_deref());
+            if let (Some(s), Some(e)) = (boundary(start), boundary(end)) {
+                let original = &surface[s..e];
+                let mut candidates = seg.candidates.candidates().to_vec();
+                let pos = candidates.iter().position(|c| c.text == original);
+                let first = match pos {
+                    Some(pos) => candidates.remove(pos),
+                    None => Candidate::new(original, &seg.reading, CandidateSource::Generated),
+                };
+                candidates.insert(0, first);
+                seg.candidates =
+                    CandidateList::with_page_size(candidates, self.options.effective_page_size());
+            }
+            segments.push(seg);
+            start = end;
+        }
+    }
+
+    /// 予測候補の選択を開始する。予測候補を1つの文節の候補として Converting へ。
+    /// 予測候補がなければ何もしない。
+    fn do_predict(&mut self) -> EngineOutput {
+        let predictions = self.predictions();
+        if predictions.is_empty() {
+            return self.composing_output();
+        }
+        self.input.flush();
+        self.reading = self.input.output().to_string();
+        self.segments = vec![Segment {
+            reading: self.reading.clone(),
+            suffix: String::new(),
+            known: true,
+            candidates: CandidateList::with_page_size(
+                predictions,
+                self.options.effective_page_size(),
+            ),
+        }];
+        self.focus = 0;
+        self.state = EngineState::Converting;
+        self.converting_output()
+    }
+
+    /// 確定直後に出した次の語の候補の選択を開始する。
+    ///
+    /// 候補は読みのない1つの文節として Converting へ移る。確定すると
+    /// 直前の確定テキストとの組 (bigram) だけを学習する。
+    fn do_suggest(&mut self) -> EngineOutput {
+        let suggestions = std::mem::take(&mut self.suggestions);
+        if suggestions.is_empty() {
+            return self.empty_output();
+        }
+        self.reading.clear();
+        self.segments = vec![Segment {
+            reading: String::new(),
+            suffix: String::new(),
+            known: true,
+            candidates: CandidateList::with_page_size(
+                suggestions,
+                self.options.effective_page_size(),
+            ),
+        }];
+        self.focus = 0;
+        self.state = EngineState::Converting;
+        self.converting_output()
+    }
+
+    /// 直前の確定テキストに続けて確定したことのある語を、回数の多い順に返す。
+    fn next_word_suggestions(&self) -> Vec<Candidate> {
+        if !self.options.next_word_suggestion {
+            return Vec::new();
+        }
+        let (Some(ud), Some(prev)) = (&self.user_dict, self.history.back()) else {
+            return Vec::new();
+        };
+        ud.successors(prev)
+            .into_iter()
+            .take(self.options.effective_page_size())
+            .map(|w| Candidate::new(w, "", CandidateSource::UserDictionary))
+            .collect()
+    }
+
+    /// 入力中の読みで始まる単語を予測候補として返す。
+    ///
+    /// ユーザー辞書の最近使った単語を先に、システム辞書の単語を読みの辞書順で後に並べる。
+    /// 各辞書で走査する読みの数は `prediction_scan_limit` まで、
+    /// 候補数は1ページに収まる数までとする。
+    fn predictions(&self) -> Vec<Candidate> {
+        let prefix = self.input.output();
+        if !self.options.prediction || prefix.chars().count() < PREDICTION_MIN_CHARS {
+            return Vec::new();
+        }
+        let limit = self.options.prediction_scan_limit;
+        let mut predictions: Vec<Candidate> = Vec::new();
+        let mut push = |c: Candidate| {
+            if !predictions.iter().any(|p| p.text == c.text) {
+                predictions.push(c);
+            }
+        };
+        if let Some(ref ud) = self.user_dict {
+            for (reading, words) in ud.lookup_prefix_with_limit(prefix, limit) {
+                for w in words {
+                    push(
+                        Candidate::new(w, reading, CandidateSource::UserDictionary)
+                            .with_annotation(ud.annotation(reading, w)),
+                    );
+                }
+            }
+        }
+        if let Some(ref dict) = self.dict {
+            for (reading, words) in dict.lookup_prefix_with_limit(prefix, limit) {
+                // 送りありの読み (おおきi など) は予測しない
+                if reading.ends_with(|c: char| c.is_ascii_lowercase()) {
+                    continue;
+                }
+                for w in words {
+                    push(
+                        Candidate::new(w, reading, CandidateSource::SystemDictionary)
+                            .with_annotation(dict.annotation(reading, w)),
+                    );
+                }
+            }
+        }
+        predictions.truncate(self.options.effective_page_size());
+        predictions
+    }
+
+    /// 文節の候補リストを作る。辞書にない文節はひらがな・カタカナを候補とする。
+    fn make_segment(&self, b: Bunsetsu, prev: Option<&str>) -> Segment {
+        let stems = if b.known {
+            self.merge_candidates(&b.stem, prev)
+        } else {
+            vec![
+                Candidate::new(&b.stem, &b.stem, CandidateSource::Generated),
+                Candidate::new(
+                    &katakana::to_katakana(&b.stem),
+                    &b.stem,
+                    CandidateSource::Generated,
+                ),
+            ]
+        };
+        self.segment_with(b, stems)
+    }
+
+    /// 文節の自立語の先頭の候補を返す。`make_segment` の先頭の候補と同じになる。
+    fn top_stem(&self, b: &Bunsetsu, prev: Option<&str>) -> Candidate {
+        let top = if b.known {
+            self.merge_candidates(&b.stem, prev).into_iter().next()
+        } else {
+            None
+        };
+        top.unwrap_or_else(|| Candidate::new(&b.stem, &b.stem, CandidateSource::Generated))
+    }
+
+    /// 自立語の候補に付属語を付けて文節を作る。
+    fn segment_with(&self, b: Bunsetsu, stems: Vec<Candidate>) -> Segment {
+        let reading = b.reading();
+        let candidates = stems
+            .into_iter()
+            .map(|mut c| {
+                c.text.push_str(&b.suffix);
+                c.reading = reading.clone();
+                c
+            })
+            .collect();
+        Segment {
+            reading,
+            suffix: b.suffix,
+            known: b.known,
+            candidates: CandidateList::with_page_size(
+                candidates,
+                self.options.effective_page_size(),
+            ),
+        }
+    }
+
+    /// 選択中の文節を1文字伸ばす（`extend`）または縮める。
+    ///
+    /// 増減した文字は次の文節との間でやり取りし、選択中の文節と次の文節だけを
+    /// 変換し直す。他の文節で選んだ候補はそのまま残す。
+    fn resize_segment(&mut self, extend: bool) -> EngineOutput {
+        let focus = self.focus;
+        let Some(seg) = self.segments.get(focus) else {
+            return self.empty_output();
+        };
+        let mut focused: Vec<char> = seg.reading.chars().collect();
+        let mut next: Vec<char> = self
+            .segments
+            .get(focus + 1)
+            .map(|s| s.reading.chars().collect())
+            .unwrap_or_default();
+        if extend {
+            if next.is_empty() {
+                return self.converting_output();
+            }
+            focused.push(next.remove(0));
+        } else {
+            if focused.len() <= 1 {
+                return self.converting_output();
+            }
+            if let Some(ch) = focused.pop() {
+                next.insert(0, ch);
+            }
+        }
+
+        let prev = match focus.checked_sub(1) {
+            Some(i) => Some(self.segments[i].context_word().to_string()),
+            None => self.history.back().cloned(),
+        };
+        let focused: String = focused.into_iter().collect();
+        let resized = self.make_segment(
+            segmenter::analyze(&focused, |r| self.has_candidates(r)),
+            prev.as_deref(),
+        );
+        let mut replaced = vec![resized];
+        // 次の文節に残った読みは文節に区切り直す
+        let next: String = next.into_iter().collect();
+        for b in segmenter::segment(&next, &self.cost_model, |r| self.has_candidates(r)) {
+            let prev = replaced.last().map(|s| s.context_word().to_string());
+            let seg = self.make_segment(b, prev.as_deref());
+            replaced.push(seg);
+        }
+        let end = (focus + 2).min(self.segments.len());
+        self.segments.splice(focus..end, replaced);
+        self.converting_output()
+    }
+
+    /// 全文節の選択中の候補を連結して確定し、Direct に戻る。
+    ///
+    /// 確定前の状態と学習の記録を残し、直後の UndoCommit で取り消せるようにする。
+    fn commit_candidate(&mut self) -> EngineOutput {
+        let history = self.history.clone();
+        if let Some(ref mut ud) = self.user_dict {
+            ud.begin_checkpoint();
+        }
+        let segments = std::mem::take(&mut self.segments);
+        let mut committed = String::new();
+        let mut commit_caret = None;
+        for seg in &segments {
+            match seg.stem() {
+                Some((reading, word)) if seg.learnable() => {
+                    // SKK の送りあり変換では付属語の位置に送り仮名が入っている
+                    let okuri = self.skk.has_okuri().then_some(seg.suffix.as_str());
+                    self.learn(reading, okuri, word)
+                }
+                // 日付・計算結果・スニペットなど、辞書以外から生成した候補は文脈にしない
+                Some(_) if seg.generated() => {}
+                _ => self.push_history(seg.context_word()),
+            }
+            if let Some(cursor) = seg.candidates.current_candidate().and_then(|c| c.cursor) {
+                commit_caret.get_or_insert(committed.chars().count() + cursor);
+            }
+            committed.push_str(seg.current());
+        }
+        let last_commit = LastCommit {
+            committed: committed.clone(),
+            segments,
+            focus: self.focus,
+            input: self.input.clone(),
+            reading: self.reading.clone(),
+            history,
+            checkpoint: self
+                .user_dict
+                .as_mut()
+                .and_then(|ud| ud.finish_checkpoint()),
+        };
+        self.focus = 0;
+        self.input.reset();
+        self.state = EngineState::Direct;
+        let output = self.committed_output(committed);
+        // カーソルを途中に置いた確定はカーソルの前の文字列と一致しないため取り消せない
+        if commit_caret.is_none() {
+            self.last_commit = Some(last_commit);
+        }
+        EngineOutput {
+            commit_caret,
+            ..output
+        }
+    }
+
+    /// 直前の確定を取り消し、確定前の候補で Converting に戻る。
+    ///
+    /// 確定時の学習と確定履歴も元に戻す。
+    fn undo_commit(&mut self) -> EngineOutput {
+        let Some(last) = self.last_commit.take() else {
+            return self.empty_output();
+        };
+        self.suggestions.clear();
+        if let (Some(ud), Some(checkpoint)) = (self.user_dict.as_mut(), last.checkpoint) {
+            ud.rollback(checkpoint);
+            self.clear_cache();
+        }
+        self.segments = last.segments;
+        self.focus = last.focus;
+        self.input = last.input;
+        self.reading = last.reading;
+        self.history = last.history;
+        self.state = EngineState::Converting;
+        EngineOutput {
+            undone_commit: last.committed,
+            ..self.converting_output()
+        }
+    }
+
+    /// 表示中のページ内の位置で候補を選択する。範囲外なら何もしない。
+    ///
+    /// 最後の文節なら全体を確定し、そうでなければ次の文節に移る。
+    fn select_on_page(&mut self, position: usize) -> EngineOutput {
+        let selected = self
+            .focused_candidates_mut()
+            .is_some_and(|cl| cl.select_on_page(position));
+        if !selected {
+            return self.converting_output();
+        }
+        if self.focus + 1 < self.segments.len() {
+            self.focus += 1;
+            self.converting_output()
+        } else {
+            self.commit_candidate()
+        }
+    }
+
+    /// 選択中の文節の候補リストの可変参照を返す。
+    fn focused_candidates_mut(&mut self) -> Option<&mut CandidateList> {
+        self.segments
+            .get_mut(self.focus)
+            .map(|seg| &mut seg.candidates)
+    }
+
+    /// 文字が表示中のページの選択ラベルであれば、そのページ内の位置を返す。
+    ///
+    /// 数字のラベルは変換直後（Space を1回押しただけ）でも使える。
+    /// 英字のラベル (asdfjkl) は続けて入力する読みと区別できないため、
+    /// 候補を移動して候補ウィンドウを開いているときだけ使う。
+    fn label_position(&self, ch: char) -> Option<usize> {
+        let candidates = &self.segments.get(self.focus)?.candidates;
+        if ch.is_ascii_alphabetic() && !candidates.is_browsed() {
+            return None;
+        }
+        self.options
+            .candidate_labels
+            .iter()
+            .position(|&l| l == ch)
+            .filter(|&pos| pos < candidates.page().len())
+    }
+
+    /// 確定した候補をユーザー辞書に学習し、確定履歴に追加する。
+    ///
+    /// 読みと候補の組に加え、直前の確定テキストとの組 (bigram) も記録する。
+    /// 読みのない候補（次の語の候補）は bigram だけを記録する。
+    /// 送り仮名 `okuri` があれば、送り仮名ごとの候補としても記録する。
+    fn learn(&mut self, reading: &str, okuri: Option<&str>, word: &str) {
+        if let Some(ref mut ud) = self.user_dict
+            && !word.is_empty()
+        {
+            if !reading.is_empty() {
+                match okuri {
+                    Some(okuri) => ud.record_okuri(reading, okuri, word),
+                    None => ud.record(reading, word),
+                }
+            }
+            if let Some(prev) = self.history.back() {
+                ud.record_bigram(prev, word);
+            }
+            self.clear_cache();
+        }
+        self.push_history(word);
+    }
+
+    /// 確定テキストを履歴に追加する。古いものから捨てる。
+    ///
+    /// タブ・改行を含むテキストは単語として扱えないので追加しない。
+    fn push_history(&mut self, committed: &str) {
+        if committed.is_empty() || committed.contains(['\t', '\n', '\r']) {
+            return;
+        }
+        self.history.push_back(committed.to_string());
+        while self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
+
+    /// 文脈（直近の確定テキスト）に基づいて候補を並び替える。
+    ///
+    /// コンテキスト優先テーブルを適用した後、直前の単語 `prev` に続いて
+    /// 確定した回数 (bigram) を優先度に加え、優先度の高い候補を先頭に移動する。
+    fn apply_context(&self, reading: &str, candidates: &mut Vec<Candidate>, prev: Option<&str>) {
+        if let Some(ref table) = self.context_priority {
+            let context: String = self.history.iter().map(|s| s.as_str()).collect();
+            table.reorder(reading, candidates, &context);
+        }
+        if let Some(ref ud) = self.user_dict
+            && let Some(prev) = prev
+        {
+            for c in candidates.iter_mut() {
+                let count = ud.bigram_count(prev, &c.text);
+                c.score = c.score.saturating_add(count.try_into().unwrap_or(i32::MAX));
+            }
+        }
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.score));
+    }
+
+    /// ユーザー辞書・システム辞書・追加の提供元を優先度の規則とともに返す。
+    fn sources(&self) -> Vec<(&dyn CandidateProvider, MergeRule)> {
+        let mut sources: Vec<(&dyn CandidateProvider, MergeRule)> = Vec::new();
+        if let Some(ref ud) = self.user_dict {
+            sources.push((ud, MergeRule::new(USER_DICTIONARY_PRIORITY)));
+        }
+        if let Some(ref dict) = self.dict {
+            sources.push((dict, MergeRule::new(SYSTEM_DICTIONARY_PRIORITY)));
+        }
+        for (provider, rule) in &self.providers {
+            sources.push((provider.as_ref(), *rule));
+        }
+        for (provider, rule) in sources.iter_mut() {
+            if let Some(custom) = self.merge_rules.get(provider.name()) {
+                *rule = *custom;
+            }
+        }
+        sources
+    }
+
+    /// いずれかの提供元に読みの候補があるか。
+    fn has_candidates(&self, reading: &str) -> bool {
+        if let Some(&known) = self.cache.borrow().known.get(reading) {
+            return known;
+        }
+        let known = if self.options.emoji {
+            self.sources()
+                .iter()
+                .any(|(p, _)| !p.lookup(reading).is_empty())
+        } else {
+            !self.merged(reading).is_empty()
+        };
+        let mut cache = self.cache.borrow_mut();
+        if cache.known.len() >= LOOKUP_CACHE_SIZE {
+            cache.known.clear();
+        }
+        cache.known.insert(reading.to_string(), known);
+        known
+    }
+
+    /// 検索結果のキャッシュを捨てる。辞書・提供元・動作設定が変わったときに呼ぶ。
+    fn clear_cache(&self) {
+        *self.cache.borrow_mut() = LookupCache::default();
+    }
+
+    /// 全提供元の候補をマージし、絵文字が無効なら絵文字の候補を除く。
+    ///
+    /// マージで注釈が補われるので、記号辞書にある絵文字はユーザー辞書の候補も除ける。
+    fn merged(&self, reading: &str) -> Vec<Candidate> {
+        let mut merged = provider::merge(&self.sources(), reading);
+        if !self.options.emoji {
+            merged.retain(|c| c.annotation.as_deref() != Some(EMOJI_CATEGORY));
+        }
+        merged
+    }
+
+    /// 全提供元の候補をマージする。
+    /// 優先度の高い提供元から順に並べ、重複した候補は先のものを残す。
+    /// 最後に直前の単語 `prev` を文脈とした並び替えを適用する。
+    fn merge_candidates(&self, reading: &str, prev: Option<&str>) -> Vec<Candidate> {
+        let mut merged = self.merged(reading);
+        self.apply_context(reading, &mut merged, prev);
+        merged
+    }
+
+    /// Composing 状態の EngineOutput を組み立てる。
+    fn composing_output(&self) -> EngineOutput {
+        if self.live_conversion_active() {
+            return self.live_output();
+        }
+        let (before, after) = self.input.split_output();
+        let preedit = [
+            PreeditSpan::new(before, PreeditAttribute::Input),
+            PreeditSpan::new(self.input.pending(), PreeditAttribute::PendingRomaji),
+            PreeditSpan::new(after, PreeditAttribute::Input),
+        ]
+        .into_iter()
+        .filter(|span| !span.text.is_empty())
+        .collect();
+        EngineOutput {
+            display: self.input.display(),
+            preedit,
+            caret: Some(self.input.caret()),
+            predictions: self.predictions(),
+            ..EngineOutput::default()
+        }
+    }
+
+    /// ライブ変換中の Composing 状態の EngineOutput を組み立てる。
+    /// 確定したときと同じテキストを表示し、カーソルは末尾に置く。
+    /// 変換しても末尾に残った未確定のローマ字は、その部分だけローマ字として表示する。
+    fn live_output(&self) -> EngineOutput {
+        let display = self.live_conversion();
+        let pending = self.input.pending();
+        let (converted, romaji) = match display.strip_suffix(pending) {
+            Some(converted) if pending != "n" => (converted, pending),
+            _ => (display.as_str(), ""),
+        };
+        let preedit = [
+            PreeditSpan::new(converted, PreeditAttribute::Converted),
+            PreeditSpan::new(romaji, PreeditAttribute::PendingRomaji),
+        ]
+        .into_iter()
+        .filter(|span| !span.text.is_empty())
+        .collect();
+        EngineOutput {
+            caret: Some(display.chars().count()),
+            display,
+            preedit,
+            predictions: self.predictions(),
+            ..EngineOutput::default()
+        }
+    }
+
+    /// Converting 状態の EngineOutput を組み立てる。
+    fn converting_output(&self) -> EngineOutput {
+        match self.segments.get(self.focus) {
+            Some(seg) => {
+                let segments: Vec<SegmentView> = self
+                    .segments
+                    .iter()
+                    .map(|s| SegmentView {
+                        text: s.current().to_string(),
+                        reading: s.reading.clone(),
+                    })
+                    .collect();
+                let preedit = segments
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| {
+                        let attribute = if i == self.focus {
+                            PreeditAttribute::Focused
+                        } else {
+                            PreeditAttribute::Converted
+                        };
+                        PreeditSpan::new(&s.text, attribute)
+                    })
+                    .collect();
+                let caret = segments[..=self.focus]
+                    .iter()
+                    .map(|s| s.text.chars().count())
+                    .sum();
+                EngineOutput {
+                    display: segments.iter().map(|s| s.text.as_str()).collect(),
+                    preedit,
+                    caret: Some(caret),
+                    candidate_index: Some(seg.candidates.index()),
+                    page: Some(seg.candidates.current_page(&self.options.candidate_labels)),
+                    segments,
+                    focused_segment: Some(self.focus),
+                    ..EngineOutput::default()
+                }
+            }
+            None => self.empty_output(),
+        }
+    }
+
+    /// 確定時の EngineOutput を組み立てる。確定した語に続く語の候補も出す。
+    fn committed_output(&mut self, committed: String) -> EngineOutput {
+        self.last_commit = None;
+        self.suggestions = self.next_word_suggestions();
+        EngineOutput {
+            committed,
+            predictions: self.suggestions.clone(),
+            ..EngineOutput::default()
+        }
+    }
+
+    /// 空の EngineOutput を返す。
+    fn empty_output(&self) -> EngineOutput {
+        EngineOutput::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::{CALCULATOR_PRIORITY, Calculator};
+    use crate::candidate::CandidateSource;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    fn test_engine() -> ConversionEngine {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        ConversionEngine::new(Some(dict))
+    }
+
+    fn engine_without_dict() -> ConversionEngine {
+        ConversionEngine::new(None)
+    }
+
+    // === 初期状態 ===
+
+    #[test]
+    fn initial_state_is_direct() {
+        let engine = test_engine();
+        assert_eq!(engine.state(), EngineState::Direct);
+    }
+
+    // === Direct → Composing ===
+
+    #[test]
+    fn insert_char_transitions_to_composing() {
+        let mut engine = test_engine();
+        engine.process(EngineCommand::InsertChar('k'));
+        assert_eq!(engine.state(), EngineState::Composing);
+    }
+
+    // === Composing → Converting ===
+
+    #[test]
+    fn convert_transitions_to_converting() {
+        let mut engine = test_engine();
+        for ch in "kanji".chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        engine.process(EngineCommand::Convert);
+        assert_eq!(engine.state(), EngineState::Converting);
+        assert!(engine.candidates().is_some());
+    }
+
+    // === Converting → Direct (Commit) ===
+
+    #[test]
     fn commit_in_converting_transitions_to_direct() {
         let mut engine = test_engine();
         for ch in "kanji".chars() {
@@ -794,8 +2286,41 @@ mod tests {
         assert_eq!(output.display, "感じ");
         let candidates = engine.candidates().unwrap();
         assert_eq!(candidates[0], "感じ");
-        assert!(candidates.contains(&"漢字".to_string()));
-        assert!(candidates.contains(&"幹事".to_string()));
+        assert!(candidates.iter().any(|c| c.text == "漢字"));
+        assert!(candidates.iter().any(|c| c.text == "幹事"));
+        assert_eq!(candidates[0].source, CandidateSource::UserDictionary);
+        assert_eq!(candidates[1].source, CandidateSource::SystemDictionary);
+    }
+
+    #[test]
+    fn candidates_carry_reading_and_annotation() {
+        let mut engine = test_engine();
+        for ch in "nihon".chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        let output = engine.process(EngineCommand::Convert);
+        let page = output.page.unwrap();
+        assert_eq!(page.candidates[0].reading, "にほん");
+        assert_eq!(page.candidates[0].annotation.as_deref(), Some("country"));
+        assert_eq!(page.candidates[1].annotation, None);
+    }
+
+    #[test]
+    fn bigram_count_becomes_score() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut user_dict = UserDictionary::new();
+        user_dict.record_bigram("会議", "幹事");
+        user_dict.record_bigram("会議", "幹事");
+        let mut engine = ConversionEngine::new_with_user_dict(Some(dict), Some(user_dict));
+        convert_and_commit(&mut engine, "kaigi", 0);
+        for ch in "kanji".chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        engine.process(EngineCommand::Convert);
+        let candidates = engine.candidates().unwrap();
+        assert_eq!(candidates[0], "幹事");
+        assert_eq!(candidates[0].score, 2);
+        assert_eq!(candidates[1].score, 0);
     }
 
     #[test]
@@ -807,7 +2332,7 @@ mod tests {
         engine.process(EngineCommand::Convert);
         let candidates = engine.candidates().unwrap();
         // "感じ" が重複していないこと
-        let count = candidates.iter().filter(|c| c.as_str() == "感じ").count();
+        let count = candidates.iter().filter(|c| c.text == "感じ").count();
         assert_eq!(count, 1);
     }
 
@@ -857,4 +2382,1609 @@ mod tests {
         let candidates = engine.candidates().unwrap();
         assert_eq!(candidates, &["感じ"]);
     }
+
+    // === 文脈による候補の並び替え ===
+
+    fn convert_and_commit(engine: &mut ConversionEngine, romaji: &str, nth: usize) -> String {
+        for ch in romaji.chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        engine.process(EngineCommand::Convert);
+        for _ in 0..nth {
+            engine.process(EngineCommand::NextCandidate);
+        }
+        engine.process(EngineCommand::Commit).committed
+    }
+
+    #[test]
+    fn commit_records_history() {
+        let mut engine = test_engine();
+        convert_and_commit(&mut engine, "kaigi", 0);
+        convert_and_commit(&mut engine, "kanji", 0);
+        let history: Vec<&str> = engine.history().collect();
+        assert_eq!(history, vec!["会議", "漢字"]);
+        engine.clear_history();
+        assert_eq!(engine.history().count(), 0);
+    }
+
+    #[test]
+    fn bigram_prefers_candidate_after_previous_word() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+
+        // 会議 → 幹事 の並びを学習
+        convert_and_commit(&mut engine, "kaigi", 0);
+        assert_eq!(convert_and_commit(&mut engine, "kanji", 2), "幹事");
+        // 文脈なしで 漢字 を選び直す → ユーザー辞書では 漢字 が先頭
+        engine.clear_history();
+        assert_eq!(convert_and_commit(&mut engine, "kanji", 1), "漢字");
+        assert_eq!(
+            engine.user_dict_mut().unwrap().lookup("かんじ").unwrap()[0],
+            "漢字"
+        );
+
+        // 会議 の直後では 幹事 が先頭
+        convert_and_commit(&mut engine, "kaigi", 0);
+        for ch in "kanji".chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "幹事");
+    }
+
+    #[test]
+    fn context_priority_table_reorders() {
+        let mut engine = test_engine();
+        engine.set_context_priority(ContextPriority::parse("会議,打ち合わせ\tかんじ\t幹事\n"));
+        convert_and_commit(&mut engine, "kaigi", 0);
+        for ch in "kanji".chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "幹事");
+        assert_eq!(engine.candidates().unwrap(), &["幹事", "漢字", "感じ"]);
+    }
+
+    // === ページ・ラベル選択 ===
+
+    fn type_and_convert(engine: &mut ConversionEngine, romaji: &str) -> EngineOutput {
+        for ch in romaji.chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        engine.process(EngineCommand::Convert)
+    }
+
+    fn paged_engine(page_size: usize, labels: &str) -> ConversionEngine {
+        let mut engine = test_engine();
+        engine.set_options(EngineOptions {
+            page_size,
+            candidate_labels: labels.chars().collect(),
+            ..EngineOptions::default()
+        });
+        engine
+    }
+
+    #[test]
+    fn converting_output_has_page() {
+        let mut engine = test_engine();
+        let output = type_and_convert(&mut engine, "kanji");
+        let page = output.page.unwrap();
+        assert_eq!(page.candidates, vec!["漢字", "感じ", "幹事"]);
+        assert_eq!(page.labels, vec!['1', '2', '3']);
+        assert_eq!(page.selected, 0);
+        assert_eq!(page.page_count, 1);
+    }
+
+    #[test]
+    fn next_and_prev_page() {
+        let mut engine = paged_engine(2, NUMBER_LABELS);
+        type_and_convert(&mut engine, "kanji");
+        let output = engine.process(EngineCommand::NextPage);
+        assert_eq!(output.display, "幹事");
+        let page = output.page.unwrap();
+        assert_eq!(page.candidates, vec!["幹事"]);
+        assert_eq!(page.labels, vec!['1']);
+        assert_eq!((page.page_index, page.page_count), (1, 2));
+
+        let output = engine.process(EngineCommand::PrevPage);
+        assert_eq!(output.display, "漢字");
+        assert_eq!(output.page.unwrap().page_index, 0);
+    }
+
+    #[test]
+    fn number_label_selects_and_commits() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "kanji");
+        let output = engine.process(EngineCommand::InsertChar('2'));
+        assert_eq!(output.committed, "感じ");
+        assert_eq!(engine.state(), EngineState::Direct);
+    }
+
+    #[test]
+    fn number_label_selects_right_after_convert() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "kanji");
+        // Space の直後（候補を移動する前）でも数字はラベルとして候補を選ぶ
+        engine.process(EngineCommand::Convert);
+        let output = engine.process(EngineCommand::InsertChar('3'));
+        assert_eq!(output.committed, "幹事");
+        assert_eq!(engine.state(), EngineState::Direct);
+
+        // 英字のラベルは同じ操作では選ばれず、入力の続きになる
+        let mut engine = paged_engine(9, SKK_LABELS);
+        type_romaji(&mut engine, "kanji");
+        engine.process(EngineCommand::Convert);
+        let output = engine.process(EngineCommand::InsertChar('d'));
+        assert_eq!(output.committed, "漢字");
+        assert_eq!(engine.state(), EngineState::Composing);
+    }
+
+    #[test]
+    fn skk_label_selects_on_current_page() {
+        let mut engine = paged_engine(9, SKK_LABELS);
+        type_and_convert(&mut engine, "kanji");
+        // 候補を移動して候補ウィンドウを開いた後は英字のラベルで選ぶ
+        engine.process(EngineCommand::NextCandidate);
+        let output = engine.process(EngineCommand::InsertChar('d'));
+        assert_eq!(output.committed, "幹事");
+    }
+
+    #[test]
+    fn skk_label_before_browsing_continues_input() {
+        let mut engine = paged_engine(9, SKK_LABELS);
+        type_and_convert(&mut engine, "kanji");
+        // 変換直後の英字はラベルではなく、確定して次の読みの入力になる
+        let output = type_romaji(&mut engine, "desu");
+        assert_eq!(output.display, "です");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "です");
+        assert_eq!(engine.history().collect::<Vec<_>>(), ["漢字", "です"]);
+    }
+
+    #[test]
+    fn page_size_is_capped_by_labels() {
+        let mut engine = paged_engine(9, "as");
+        let output = type_and_convert(&mut engine, "kanji");
+        assert_eq!(output.page.unwrap().page_count, 2);
+    }
+
+    #[test]
+    fn label_outside_page_starts_new_input() {
+        let mut engine = paged_engine(2, NUMBER_LABELS);
+        type_and_convert(&mut engine, "kanji");
+        // 1ページ2件なので '3' はラベルではない → 現在の候補を確定して入力を続ける
+        let output = engine.process(EngineCommand::InsertChar('3'));
+        assert_eq!(output.committed, "漢字");
+        assert_eq!(engine.state(), EngineState::Composing);
+    }
+
+    #[test]
+    fn select_candidate_command() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "kanji");
+        // 範囲外は無視して Converting のまま
+        let output = engine.process(EngineCommand::SelectCandidate(5));
+        assert_eq!(output.committed, "");
+        assert_eq!(engine.state(), EngineState::Converting);
+        let output = engine.process(EngineCommand::SelectCandidate(2));
+        assert_eq!(output.committed, "幹事");
+    }
+
+    // === 候補の提供元 ===
+
+    struct Glossary;
+
+    impl CandidateProvider for Glossary {
+        fn name(&self) -> &str {
+            "glossary"
+        }
+
+        fn lookup(&self, reading: &str) -> Vec<Candidate> {
+            match reading {
+                "かんじ" => vec![Candidate::new("監事", reading, CandidateSource::Generated)],
+                "じゃぴん" => vec![Candidate::new(
+                    "japinput",
+                    reading,
+                    CandidateSource::Generated,
+                )],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn added_provider_merges_by_priority() {
+        let mut engine = test_engine();
+        engine.add_provider(
+            Box::new(Glossary),
+            MergeRule::new(SYSTEM_DICTIONARY_PRIORITY + 1),
+        );
+        type_and_convert(&mut engine, "kanji");
+        assert_eq!(
+            engine.candidates().unwrap(),
+            &["監事", "漢字", "感じ", "幹事"]
+        );
+    }
+
+    #[test]
+    fn added_provider_below_system_dictionary() {
+        let mut engine = test_engine();
+        engine.add_provider(Box::new(Glossary), MergeRule::new(0));
+        type_and_convert(&mut engine, "kanji");
+        assert_eq!(
+            engine.candidates().unwrap(),
+            &["漢字", "感じ", "幹事", "監事"]
+        );
+    }
+
+    #[test]
+    fn merge_rule_reorders_dictionaries() {
+        let mut engine = test_engine_with_user_dict();
+        engine.add_provider(Box::new(Glossary), MergeRule::new(0));
+        // システム辞書をユーザー辞書より先に、用語集を最優先に並べる
+        engine.set_merge_rule("system", MergeRule::new(USER_DICTIONARY_PRIORITY + 1));
+        engine.set_merge_rule("glossary", MergeRule::new(1000));
+        type_and_convert(&mut engine, "kanji");
+        assert_eq!(
+            engine.candidates().unwrap(),
+            &["監事", "漢字", "感じ", "幹事"]
+        );
+    }
+
+    #[test]
+    fn merge_rule_limits_dictionary_candidates() {
+        let mut engine = test_engine();
+        engine.set_merge_rule(
+            "system",
+            MergeRule::new(SYSTEM_DICTIONARY_PRIORITY).with_max_count(1),
+        );
+        type_and_convert(&mut engine, "kanji");
+        assert_eq!(engine.candidates().unwrap()[0], "漢字");
+        assert!(
+            !engine
+                .candidates()
+                .unwrap()
+                .iter()
+                .any(|c| c.text == "感じ" || c.text == "幹事")
+        );
+    }
+
+    #[test]
+    fn added_provider_without_dictionary_entry() {
+        let mut engine = test_engine();
+        engine.add_provider(Box::new(Glossary), MergeRule::new(0));
+        let output = type_and_convert(&mut engine, "japin");
+        assert_eq!(output.display, "japinput");
+    }
+
+    /// 2026-10-17 14:05:30 (JST) を返す時計。
+    struct FixedClock;
+
+    impl crate::date_provider::Clock for FixedClock {
+        fn now(&self) -> i64 {
+            1_792_213_530
+        }
+    }
+
+    fn add_date_provider(engine: &mut ConversionEngine) {
+        use crate::date_provider::{DATE_PRIORITY, DateFormats, DateProvider};
+        let formats = DateFormats {
+            date: vec!["{month}月{day}日".to_string()],
+            time: vec!["{hour2}:{minute2}".to_string()],
+            ..DateFormats::default()
+        };
+        engine.add_provider(
+            Box::new(DateProvider::with_clock(formats, Box::new(FixedClock))),
+            MergeRule::new(DATE_PRIORITY),
+        );
+    }
+
+    #[test]
+    fn date_candidates_follow_dictionary() {
+        let mut engine = test_engine();
+        add_date_provider(&mut engine);
+        type_and_convert(&mut engine, "kyou");
+        assert_eq!(engine.candidates().unwrap(), &["今日", "10月17日"]);
+    }
+
+    #[test]
+    fn time_candidate_without_dictionary_entry() {
+        let mut engine = test_engine();
+        add_date_provider(&mut engine);
+        let output = type_and_convert(&mut engine, "ima");
+        assert_eq!(output.display, "14:05");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "14:05");
+    }
+
+    #[test]
+    fn date_candidate_is_not_learned() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        add_date_provider(&mut engine);
+        type_and_convert(&mut engine, "kyou");
+        engine.process(EngineCommand::NextCandidate);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "10月17日");
+        let ud = engine.user_dict_mut().unwrap();
+        assert!(ud.lookup("きょう").is_none());
+    }
+
+    // === 連文節変換 ===
+
+    fn segment_texts(output: &EngineOutput) -> Vec<&str> {
+        output.segments.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    fn segment_readings(output: &EngineOutput) -> Vec<&str> {
+        output.segments.iter().map(|s| s.reading.as_str()).collect()
+    }
+
+    #[test]
+    fn sentence_is_split_into_segments() {
+        let mut engine = test_engine();
+        let output = type_and_convert(&mut engine, "watashinonamaehanakanodesu");
+        assert_eq!(engine.state(), EngineState::Converting);
+        assert_eq!(segment_texts(&output), vec!["私の", "名前は", "中野です"]);
+        assert_eq!(output.display, "私の名前は中野です");
+        assert_eq!(output.focused_segment, Some(0));
+        let readings: Vec<&str> = engine.segments().iter().map(|s| s.reading()).collect();
+        assert_eq!(readings, vec!["わたしの", "なまえは", "なかのです"]);
+        // 選択中の文節の候補には付属語が付く
+        assert_eq!(engine.candidates().unwrap(), &["私の", "渡しの"]);
+        assert_eq!(engine.candidates().unwrap()[0].reading, "わたしの");
+    }
+
+    #[test]
+    fn next_candidate_changes_focused_segment_only() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaehanakanodesu");
+        let output = engine.process(EngineCommand::NextCandidate);
+        assert_eq!(output.display, "渡しの名前は中野です");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "渡しの名前は中野です");
+        assert_eq!(engine.state(), EngineState::Direct);
+    }
+
+    #[test]
+    fn label_selection_moves_to_next_segment() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaeha");
+        let output = engine.process(EngineCommand::InsertChar('2'));
+        assert_eq!(output.committed, "");
+        assert_eq!(output.focused_segment, Some(1));
+        assert_eq!(output.display, "渡しの名前は");
+        // 最後の文節で選択すると全体を確定する
+        let output = engine.process(EngineCommand::InsertChar('1'));
+        assert_eq!(output.committed, "渡しの名前は");
+    }
+
+    #[test]
+    fn unknown_segment_offers_hiragana_and_katakana() {
+        let mut engine = test_engine();
+        let output = type_and_convert(&mut engine, "nunuwatashi");
+        assert_eq!(segment_texts(&output), vec!["ぬぬ", "私"]);
+        assert_eq!(engine.candidates().unwrap(), &["ぬぬ", "ヌヌ"]);
+    }
+
+    #[test]
+    fn commit_learns_each_segment_stem() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        type_and_convert(&mut engine, "nunuwatashinonamae");
+        engine.process(EngineCommand::Commit);
+        let ud = engine.user_dict_mut().unwrap();
+        // 付属語を除いた自立語を学習し、未知語の文節は学習しない
+        assert_eq!(ud.lookup("わたし").unwrap(), &["私"]);
+        assert!(ud.lookup("わたしの").is_none());
+        assert!(ud.lookup("ぬぬ").is_none());
+        assert_eq!(ud.bigram_count("私", "名前"), 1);
+        let history: Vec<&str> = engine.history().collect();
+        assert_eq!(history, vec!["ぬぬ", "私", "名前"]);
+    }
+
+    #[test]
+    fn cancel_multi_segment_returns_to_composing() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamae");
+        let output = engine.process(EngineCommand::Cancel);
+        assert_eq!(engine.state(), EngineState::Composing);
+        assert_eq!(output.display, "わたしのなまえ");
+        assert!(engine.segments().is_empty());
+    }
+
+    // === 文節の移動・伸縮 ===
+
+    #[test]
+    fn direct_state_passes_through_non_input_keys() {
+        let mut engine = test_engine();
+        assert!(engine.accepts(&EngineCommand::InsertChar('a')));
+        assert!(!engine.accepts(&EngineCommand::MoveLeft));
+        assert!(!engine.accepts(&EngineCommand::Commit));
+        engine.process(EngineCommand::InsertChar('a'));
+        assert!(engine.accepts(&EngineCommand::MoveLeft));
+    }
+
+    #[test]
+    fn move_focus_between_segments() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaehanakanodesu");
+        let output = engine.process(EngineCommand::MoveRight);
+        assert_eq!(output.focused_segment, Some(1));
+        assert_eq!(engine.candidates().unwrap(), &["名前は"]);
+        engine.process(EngineCommand::MoveRight);
+        // 末尾で止まる
+        let output = engine.process(EngineCommand::MoveRight);
+        assert_eq!(output.focused_segment, Some(2));
+        engine.process(EngineCommand::MoveLeft);
+        engine.process(EngineCommand::MoveLeft);
+        let output = engine.process(EngineCommand::MoveLeft);
+        assert_eq!(output.focused_segment, Some(0));
+    }
+
+    #[test]
+    fn shrink_segment_moves_char_to_next() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaeha");
+        let output = engine.process(EngineCommand::ShrinkSegment);
+        // 次の文節に移った文字を含めて区切り直す
+        assert_eq!(segment_readings(&output), vec!["わたし", "の", "なまえは"]);
+        assert_eq!(segment_texts(&output), vec!["私", "の", "名前は"]);
+        assert_eq!(output.focused_segment, Some(0));
+    }
+
+    #[test]
+    fn extend_segment_takes_char_from_next() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaeha");
+        engine.process(EngineCommand::ShrinkSegment);
+        let output = engine.process(EngineCommand::ExtendSegment);
+        assert_eq!(segment_readings(&output), vec!["わたしの", "なまえは"]);
+        assert_eq!(segment_texts(&output), vec!["私の", "名前は"]);
+    }
+
+    #[test]
+    fn shrink_last_segment_creates_new_segment() {
+        let mut engine = test_engine();
+        let output = type_and_convert(&mut engine, "nakanodesu");
+        assert_eq!(segment_readings(&output), vec!["なかのです"]);
+        let output = engine.process(EngineCommand::ShrinkSegment);
+        assert_eq!(segment_readings(&output), vec!["なかので", "す"]);
+        assert_eq!(segment_texts(&output), vec!["中野で", "す"]);
+    }
+
+    #[test]
+    fn extend_last_segment_is_noop() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "kanji");
+        let output = engine.process(EngineCommand::ExtendSegment);
+        assert_eq!(segment_readings(&output), vec!["かんじ"]);
+    }
+
+    #[test]
+    fn shrink_single_char_segment_is_noop() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "nakanodesu");
+        engine.process(EngineCommand::ShrinkSegment);
+        engine.process(EngineCommand::MoveRight);
+        let output = engine.process(EngineCommand::ShrinkSegment);
+        assert_eq!(segment_readings(&output), vec!["なかので", "す"]);
+    }
+
+    #[test]
+    fn resize_keeps_choices_in_other_segments() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaehanakanodesu");
+        engine.process(EngineCommand::NextCandidate); // 渡しの
+        engine.process(EngineCommand::MoveRight);
+        let output = engine.process(EngineCommand::ShrinkSegment);
+        assert_eq!(
+            segment_readings(&output),
+            vec!["わたしの", "なまえ", "は", "なかのです"]
+        );
+        assert_eq!(segment_texts(&output)[0], "渡しの");
+        assert_eq!(output.focused_segment, Some(1));
+    }
+
+    // === 予測変換 ===
+
+    fn type_romaji(engine: &mut ConversionEngine, romaji: &str) -> EngineOutput {
+        let mut output = EngineOutput::default();
+        for ch in romaji.chars() {
+            output = engine.process(EngineCommand::InsertChar(ch));
+        }
+        output
+    }
+
+    #[test]
+    fn composing_offers_predictions() {
+        let mut engine = test_engine();
+        let output = type_romaji(&mut engine, "toukyo");
+        assert_eq!(output.display, "とうきょ");
+        assert_eq!(output.predictions, vec!["東京", "東京駅", "東京都"]);
+        assert_eq!(output.predictions[2].reading, "とうきょうと");
+    }
+
+    #[test]
+    fn predictions_rank_user_history_first() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut user_dict = UserDictionary::new();
+        user_dict.record("とうきょうと", "東京都");
+        let mut engine = ConversionEngine::new_with_user_dict(Some(dict), Some(user_dict));
+        let output = type_romaji(&mut engine, "toukyo");
+        assert_eq!(output.predictions, vec!["東京都", "東京", "東京駅"]);
+        assert_eq!(
+            output.predictions[0].source,
+            CandidateSource::UserDictionary
+        );
+    }
+
+    #[test]
+    fn short_input_has_no_predictions() {
+        let mut engine = test_engine();
+        let output = type_romaji(&mut engine, "to");
+        assert!(output.predictions.is_empty());
+    }
+
+    #[test]
+    fn prediction_can_be_disabled() {
+        let mut engine = test_engine();
+        engine.set_options(EngineOptions {
+            prediction: false,
+            ..EngineOptions::default()
+        });
+        let output = type_romaji(&mut engine, "toukyo");
+        assert!(output.predictions.is_empty());
+        engine.process(EngineCommand::Predict);
+        assert_eq!(engine.state(), EngineState::Composing);
+    }
+
+    #[test]
+    fn prediction_scan_limit() {
+        let mut engine = test_engine();
+        engine.set_options(EngineOptions {
+            prediction_scan_limit: 1,
+            ..EngineOptions::default()
+        });
+        let output = type_romaji(&mut engine, "toukyo");
+        assert_eq!(output.predictions, vec!["東京"]);
+    }
+
+    #[test]
+    fn tab_selects_and_commits_prediction() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        type_romaji(&mut engine, "toukyo");
+        let output = engine.process(EngineCommand::Predict);
+        assert_eq!(engine.state(), EngineState::Converting);
+        assert_eq!(output.display, "東京");
+        let output = engine.process(EngineCommand::Predict);
+        assert_eq!(output.display, "東京駅");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "東京駅");
+        // 予測候補の読みで学習する
+        let ud = engine.user_dict_mut().unwrap();
+        assert_eq!(ud.lookup("とうきょうえき").unwrap(), &["東京駅"]);
+        assert!(ud.lookup("とうきょ").is_none());
+    }
+
+    #[test]
+    fn cancel_prediction_returns_to_composing() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "toukyo");
+        engine.process(EngineCommand::Predict);
+        let output = engine.process(EngineCommand::Cancel);
+        assert_eq!(engine.state(), EngineState::Composing);
+        assert_eq!(output.display, "とうきょ");
+    }
+
+    // === 次の語の候補 ===
+
+    fn engine_with_successors() -> ConversionEngine {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut user_dict = UserDictionary::new();
+        user_dict.record_bigram("東京", "都");
+        user_dict.record_bigram("東京", "駅");
+        user_dict.record_bigram("東京", "駅");
+        ConversionEngine::new_with_user_dict(Some(dict), Some(user_dict))
+    }
+
+    #[test]
+    fn commit_offers_next_word_suggestions() {
+        let mut engine = engine_with_successors();
+        type_and_convert(&mut engine, "toukyou");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "東京");
+        assert_eq!(output.predictions, vec!["駅", "都"]);
+        assert!(engine.accepts(&EngineCommand::Predict));
+        assert!(!engine.accepts(&EngineCommand::Commit));
+    }
+
+    #[test]
+    fn accept_next_word_suggestion() {
+        let mut engine = engine_with_successors();
+        type_and_convert(&mut engine, "toukyou");
+        engine.process(EngineCommand::Commit);
+        let output = engine.process(EngineCommand::Predict);
+        assert_eq!(engine.state(), EngineState::Converting);
+        assert_eq!(output.display, "駅");
+        let output = engine.process(EngineCommand::Predict);
+        assert_eq!(output.display, "都");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "都");
+        assert_eq!(engine.state(), EngineState::Direct);
+        // 確定した組を学習し、読みのない単語は登録しない
+        let ud = engine.user_dict_mut().unwrap();
+        assert_eq!(ud.bigram_count("東京", "都"), 2);
+        assert_eq!(ud.len(), 1);
+    }
+
+    #[test]
+    fn typing_dismisses_suggestions() {
+        let mut engine = engine_with_successors();
+        type_and_convert(&mut engine, "toukyou");
+        engine.process(EngineCommand::Commit);
+        let output = engine.process(EngineCommand::InsertChar('k'));
+        assert!(output.predictions.is_empty());
+        engine.process(EngineCommand::Cancel);
+        assert!(!engine.accepts(&EngineCommand::Predict));
+    }
+
+    #[test]
+    fn dismiss_suggestions_when_key_passed_through() {
+        let mut engine = engine_with_successors();
+        type_and_convert(&mut engine, "toukyou");
+        engine.process(EngineCommand::Commit);
+        engine.forget_last_commit();
+        assert!(!engine.accepts(&EngineCommand::Predict));
+    }
+
+    #[test]
+    fn cancel_suggestion_returns_to_direct() {
+        let mut engine = engine_with_successors();
+        type_and_convert(&mut engine, "toukyou");
+        engine.process(EngineCommand::Commit);
+        engine.process(EngineCommand::Predict);
+        let output = engine.process(EngineCommand::Cancel);
+        assert_eq!(engine.state(), EngineState::Direct);
+        assert_eq!(output.display, "");
+    }
+
+    #[test]
+    fn next_word_suggestion_can_be_disabled() {
+        let mut engine = engine_with_successors();
+        engine.set_options(EngineOptions {
+            next_word_suggestion: false,
+            ..EngineOptions::default()
+        });
+        type_and_convert(&mut engine, "toukyou");
+        let output = engine.process(EngineCommand::Commit);
+        assert!(output.predictions.is_empty());
+        assert!(!engine.accepts(&EngineCommand::Predict));
+    }
+
+    // === 入力中のカーソル移動・編集 ===
+
+    #[test]
+    fn composing_caret_at_end() {
+        let mut engine = test_engine();
+        let output = type_romaji(&mut engine, "kak");
+        assert_eq!(output.display, "かk");
+        assert_eq!(output.caret, Some(2));
+    }
+
+    #[test]
+    fn fix_typo_in_the_middle() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "kanzi");
+        engine.process(EngineCommand::MoveLeft);
+        let output = engine.process(EngineCommand::Backspace);
+        assert_eq!(output.display, "かじ");
+        assert_eq!(output.caret, Some(1));
+        let output = type_romaji(&mut engine, "n");
+        assert_eq!(output.display, "かnじ");
+        assert_eq!(output.caret, Some(2));
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "漢字");
+    }
+
+    #[test]
+    fn home_end_and_delete() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "akanzi");
+        let output = engine.process(EngineCommand::MoveToStart);
+        assert_eq!(output.caret, Some(0));
+        let output = engine.process(EngineCommand::Delete);
+        assert_eq!(output.display, "かんじ");
+        let output = engine.process(EngineCommand::MoveToEnd);
+        assert_eq!(output.caret, Some(3));
+        // 末尾での Delete は何もしない
+        let output = engine.process(EngineCommand::Delete);
+        assert_eq!(output.display, "かんじ");
+    }
+
+    #[test]
+    fn delete_last_char_returns_to_direct() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "a");
+        engine.process(EngineCommand::MoveLeft);
+        let output = engine.process(EngineCommand::Delete);
+        assert_eq!(engine.state(), EngineState::Direct);
+        assert_eq!(output.display, "");
+    }
+
+    #[test]
+    fn home_end_select_first_and_last_segment() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaehanakanodesu");
+        let output = engine.process(EngineCommand::MoveToEnd);
+        assert_eq!(output.focused_segment, Some(2));
+        let output = engine.process(EngineCommand::MoveToStart);
+        assert_eq!(output.focused_segment, Some(0));
+    }
+
+    // === 確定の取り消し ===
+
+    #[test]
+    fn undo_commit_returns_to_converting() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        type_and_convert(&mut engine, "kanji");
+        engine.process(EngineCommand::NextCandidate);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "感じ");
+        assert!(engine.accepts(&EngineCommand::UndoCommit));
+
+        let output = engine.process(EngineCommand::UndoCommit);
+        assert_eq!(engine.state(), EngineState::Converting);
+        assert_eq!(output.undone_commit, "感じ");
+        assert_eq!(output.display, "感じ");
+        assert_eq!(output.candidate_index, Some(1));
+        // 学習と確定履歴も取り消される
+        assert_eq!(engine.history().count(), 0);
+        assert!(engine.user_dict_mut().unwrap().lookup("かんじ").is_none());
+
+        engine.process(EngineCommand::PrevCandidate);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "漢字");
+        assert_eq!(
+            engine.user_dict_mut().unwrap().lookup("かんじ").unwrap(),
+            &["漢字"]
+        );
+    }
+
+    #[test]
+    fn undo_commit_restores_previous_learning() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut user_dict = UserDictionary::new();
+        user_dict.record("かんじ", "幹事");
+        let mut engine = ConversionEngine::new_with_user_dict(Some(dict), Some(user_dict));
+        convert_and_commit(&mut engine, "kaigi", 0);
+        let history: Vec<String> = engine.history().map(String::from).collect();
+        convert_and_commit(&mut engine, "kanji", 1);
+        engine.process(EngineCommand::UndoCommit);
+        let ud = engine.user_dict_mut().unwrap();
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["幹事"]);
+        assert_eq!(ud.bigram_count("会議", "漢字"), 0);
+        assert_eq!(engine.history().collect::<Vec<_>>(), history);
+    }
+
+    #[test]
+    fn undo_multi_segment_commit() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaehanakanodesu");
+        engine.process(EngineCommand::MoveRight);
+        engine.process(EngineCommand::Commit);
+        let output = engine.process(EngineCommand::UndoCommit);
+        assert_eq!(output.undone_commit, "私の名前は中野です");
+        assert_eq!(output.focused_segment, Some(1));
+        assert_eq!(segment_texts(&output), vec!["私の", "名前は", "中野です"]);
+        // キャンセルすると確定前の読みに戻る
+        let output = engine.process(EngineCommand::Cancel);
+        assert_eq!(output.display, "わたしのなまえはなかのです");
+    }
+
+    #[test]
+    fn undo_commit_only_right_after_commit() {
+        let mut engine = test_engine();
+        convert_and_commit(&mut engine, "kanji", 0);
+        engine.process(EngineCommand::InsertChar('a'));
+        engine.process(EngineCommand::Commit);
+        // ひらがなの確定は取り消せない
+        assert!(!engine.accepts(&EngineCommand::UndoCommit));
+
+        convert_and_commit(&mut engine, "kanji", 0);
+        engine.forget_last_commit();
+        assert!(!engine.accepts(&EngineCommand::UndoCommit));
+        let output = engine.process(EngineCommand::UndoCommit);
+        assert_eq!(engine.state(), EngineState::Direct);
+        assert_eq!(output.undone_commit, "");
+    }
+
+    // === 再変換 ===
+
+    #[test]
+    fn reconvert_word() {
+        let mut engine = test_engine();
+        let output = engine.reconvert("感じ");
+        assert_eq!(engine.state(), EngineState::Converting);
+        assert_eq!(output.display, "感じ");
+        assert_eq!(engine.reading(), "かんじ");
+        assert_eq!(engine.candidates().unwrap(), &["感じ", "漢字", "幹事"]);
+        let output = engine.process(EngineCommand::NextCandidate);
+        assert_eq!(output.display, "漢字");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "漢字");
+    }
+
+    #[test]
+    fn reconvert_sentence_keeps_original_text() {
+        let mut engine = test_engine();
+        let output = engine.reconvert("私の名前は中野です");
+        assert_eq!(segment_texts(&output), vec!["私の", "名前は", "中野です"]);
+        assert_eq!(
+            segment_readings(&output),
+            vec!["わたしの", "なまえは", "なかのです"]
+        );
+        let output = engine.process(EngineCommand::NextCandidate);
+        assert_eq!(segment_texts(&output)[0], "渡しの");
+    }
+
+    #[test]
+    fn reconvert_katakana() {
+        let mut engine = test_engine();
+        let output = engine.reconvert("カンジ");
+        assert_eq!(output.display, "カンジ");
+        assert_eq!(
+            engine.candidates().unwrap(),
+            &["カンジ", "漢字", "感じ", "幹事"]
+        );
+    }
+
+    #[test]
+    fn reconvert_unknown_characters_as_own_segment() {
+        let mut engine = test_engine();
+        let output = engine.reconvert("𠮷漢字");
+        assert_eq!(segment_texts(&output), vec!["𠮷", "漢字"]);
+        assert_eq!(output.focused_segment, Some(0));
+        // 取り消すと読みの入力に戻る
+        let output = engine.process(EngineCommand::Cancel);
+        assert_eq!(output.display, "𠮷かんじ");
+    }
+
+    #[test]
+    fn reconvert_uses_user_dictionary_readings() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut user_dict = UserDictionary::new();
+        user_dict.record("にっぽん", "日本");
+        let mut engine = ConversionEngine::new_with_user_dict(Some(dict), Some(user_dict));
+        engine.reconvert("日本");
+        assert_eq!(engine.reading(), "にっぽん");
+    }
+
+    #[test]
+    fn reconvert_sees_user_dictionary_changes() {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        engine.reconvert("日本");
+        assert_eq!(engine.reading(), "にほん");
+        engine.process(EngineCommand::Cancel);
+        engine.user_dict_mut().unwrap().record("にっぽん", "日本");
+        engine.reconvert("日本");
+        assert_eq!(engine.reading(), "にっぽん");
+    }
+
+    #[test]
+    fn reconvert_unreadable_text_is_noop() {
+        let mut engine = test_engine();
+        let output = engine.reconvert("𠮷");
+        assert_eq!(engine.state(), EngineState::Direct);
+        assert_eq!(output.display, "");
+    }
+
+    // === 表示用テキストの属性 ===
+
+    fn spans(output: &EngineOutput) -> Vec<(&str, PreeditAttribute)> {
+        output
+            .preedit
+            .iter()
+            .map(|s| (s.text.as_str(), s.attribute))
+            .collect()
+    }
+
+    #[test]
+    fn composing_preedit_marks_pending_romaji() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "kanji");
+        engine.process(EngineCommand::MoveLeft);
+        let output = type_romaji(&mut engine, "k");
+        assert_eq!(
+            spans(&output),
+            vec![
+                ("かん", PreeditAttribute::Input),
+                ("k", PreeditAttribute::PendingRomaji),
+                ("じ", PreeditAttribute::Input),
+            ]
+        );
+        assert_eq!(output.caret, Some(3));
+    }
+
+    #[test]
+    fn converting_preedit_marks_focused_segment() {
+        let mut engine = test_engine();
+        type_and_convert(&mut engine, "watashinonamaehanakanodesu");
+        let output = engine.process(EngineCommand::MoveRight);
+        assert_eq!(
+            spans(&output),
+            vec![
+                ("私の", PreeditAttribute::Converted),
+                ("名前は", PreeditAttribute::Focused),
+                ("中野です", PreeditAttribute::Converted),
+            ]
+        );
+        assert_eq!(output.caret, Some(5));
+        let joined: String = output.preedit.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(joined, output.display);
+    }
+
+    #[test]
+    fn empty_output_has_no_preedit() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "a");
+        let output = engine.process(EngineCommand::Cancel);
+        assert!(output.preedit.is_empty());
+        assert_eq!(output.caret, None);
+    }
+
+    // === IME のオン/オフ ===
+
+    #[test]
+    fn toggle_ime_off_and_on() {
+        let mut engine = test_engine();
+        engine.process(EngineCommand::ToggleIme);
+        assert!(!engine.is_enabled());
+        assert!(!engine.accepts(&EngineCommand::InsertChar('a')));
+        assert!(engine.accepts(&EngineCommand::ToggleIme));
+        let output = engine.process(EngineCommand::InsertChar('a'));
+        assert_eq!(output.display, "");
+        assert_eq!(engine.state(), EngineState::Direct);
+
+        engine.process(EngineCommand::ToggleIme);
+        assert!(engine.is_enabled());
+        let output = engine.process(EngineCommand::InsertChar('a'));
+        assert_eq!(output.display, "あ");
+    }
+
+    #[test]
+    fn ime_off_commits_composition() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "kan");
+        let output = engine.process(EngineCommand::ToggleIme);
+        assert_eq!(output.committed, "かん");
+        assert_eq!(engine.state(), EngineState::Direct);
+
+        engine.process(EngineCommand::ToggleIme);
+        type_and_convert(&mut engine, "kanji");
+        engine.process(EngineCommand::NextCandidate);
+        let output = engine.process(EngineCommand::ToggleIme);
+        assert_eq!(output.committed, "感じ");
+        assert!(!engine.accepts(&EngineCommand::UndoCommit));
+    }
+
+    #[test]
+    fn ime_off_cancels_composition() {
+        let mut engine = test_engine();
+        engine.set_options(EngineOptions {
+            ime_off_action: ImeOffAction::Cancel,
+            ..EngineOptions::default()
+        });
+        type_and_convert(&mut engine, "kanji");
+        let output = engine.process(EngineCommand::ToggleIme);
+        assert_eq!(output.committed, "");
+        assert_eq!(output.display, "");
+        assert_eq!(engine.state(), EngineState::Direct);
+        engine.process(EngineCommand::ToggleIme);
+        let output = engine.process(EngineCommand::InsertChar('a'));
+        assert_eq!(output.display, "あ");
+    }
+
+    #[test]
+    fn set_enabled_only_acts_on_change() {
+        let mut engine = test_engine();
+        type_romaji(&mut engine, "kan");
+        let output = engine.set_enabled(true);
+        assert_eq!(output.committed, "");
+        assert_eq!(engine.state(), EngineState::Composing);
+
+        let output = engine.set_enabled(false);
+        assert_eq!(output.committed, "かん");
+        assert!(!engine.is_enabled());
+        engine.set_enabled(false);
+        assert!(!engine.is_enabled());
+        engine.set_enabled(true);
+        assert!(engine.is_enabled());
+    }
+
+    // === 記号辞書 ===
+
+    fn add_symbol_dictionary(engine: &mut ConversionEngine) {
+        use crate::symbol_dict::{SYMBOL_PRIORITY, SymbolDictionary};
+        engine.add_provider(
+            Box::new(SymbolDictionary::bundled()),
+            MergeRule::new(SYMBOL_PRIORITY),
+        );
+    }
+
+    #[test]
+    fn symbol_candidates_show_category() {
+        let mut engine = test_engine();
+        add_symbol_dictionary(&mut engine);
+        let output = type_and_convert(&mut engine, "nikoniko");
+        let page = output.page.unwrap();
+        assert_eq!(page.candidates[0].text, "😊");
+        assert_eq!(page.candidates[0].annotation.as_deref(), Some("絵文字"));
+        assert_eq!(page.candidates[1].text, "(^_^)");
+        assert_eq!(page.candidates[1].annotation.as_deref(), Some("顔文字"));
+    }
+
+    #[test]
+    fn symbol_candidate_is_not_learned() {
+        let mut engine = test_engine_with_user_dict();
+        add_symbol_dictionary(&mut engine);
+        type_and_convert(&mut engine, "nikoniko");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "😊");
+        let ud = engine.user_dict_mut().unwrap();
+        assert!(ud.lookup("にこにこ").is_none());
+    }
+
+    #[test]
+    fn emoji_can_be_disabled_after_merge() {
+        let mut engine = test_engine_with_user_dict();
+        add_symbol_dictionary(&mut engine);
+        // 以前に学習した絵文字もユーザー辞書に残っている
+        engine.user_dict_mut().unwrap().record("にこにこ", "😊");
+        engine.set_options(EngineOptions {
+            emoji: false,
+            ..EngineOptions::default()
+        });
+        type_and_convert(&mut engine, "nikoniko");
+        let candidates = engine.candidates().unwrap();
+        assert_eq!(candidates[0], "(^_^)");
+        assert!(candidates.iter().all(|c| c.text != "😊"));
+        engine.process(EngineCommand::Cancel);
+        engine.process(EngineCommand::Cancel);
+
+        // 絵文字しかない読みは辞書にない読みとして扱う
+        let output = type_and_convert(&mut engine, "guddo");
+        assert_eq!(output.committed, "ぐっど");
+    }
+
+    fn typo_engine() -> ConversionEngine {
+        let mut engine = test_engine();
+        engine.set_typo_correction(TypoCorrection::bundled());
+        engine
+    }
+
+    #[test]
+    fn typo_corrected_candidates_are_marked() {
+        let mut engine = typo_engine();
+        // nn の後に子音が続くと「かんんじ」になる
+        let output = type_and_convert(&mut engine, "kannji");
+        assert_eq!(output.display, "漢字");
+        let page = output.page.unwrap();
+        assert_eq!(page.candidates[0].reading, "かんじ");
+        assert_eq!(page.candidates[0].annotation.as_deref(), Some("補正"));
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "漢字");
+    }
+
+    #[test]
+    fn typo_correction_from_table_entry() {
+        let mut engine = typo_engine();
+        let output = type_and_convert(&mut engine, "kaiig");
+        assert_eq!(output.display, "会議");
+    }
+
+    #[test]
+    fn extra_n_is_corrected() {
+        let mut engine = typo_engine();
+        let output = type_and_convert(&mut engine, "konnnichiha");
+        assert_eq!(output.display, "今日は");
+    }
+
+    #[test]
+    fn adjacent_transposition_without_table_rule() {
+        let mut engine = test_engine();
+        engine.set_typo_correction(TypoCorrection::default());
+        let output = type_and_convert(&mut engine, "knaji");
+        assert_eq!(output.display, "漢字");
+        assert_eq!(
+            output.page.unwrap().candidates[0].annotation.as_deref(),
+            Some("補正")
+        );
+    }
+
+    #[test]
+    fn typo_in_one_segment_is_corrected() {
+        let mut engine = typo_engine();
+        let output = type_and_convert(&mut engine, "watashinokaiig");
+        assert_eq!(output.display, "私の会議");
+        // 補正したのは打ち間違いを含む文節だけ
+        let annotations: Vec<Option<&str>> = engine
+            .segments()
+            .iter()
+            .map(|s| s.candidates()[0].annotation.as_deref())
+            .collect();
+        assert_eq!(annotations, [None, Some("補正")]);
+
+        engine.process(EngineCommand::Cancel);
+        engine.process(EngineCommand::Cancel);
+        let output = type_and_convert(&mut engine, "kannjinokaigi");
+        assert_eq!(output.display, "漢字の会議");
+        assert_eq!(
+            engine.segments()[0].candidates()[0].annotation.as_deref(),
+            Some("補正")
+        );
+        assert_eq!(engine.segments()[1].candidates()[0].annotation, None);
+    }
+
+    #[test]
+    fn legitimate_nn_is_not_corrected() {
+        // nn を n にする広いルールがあっても、入力どおりの読みに候補があれば補正しない
+        let mut engine = test_engine();
+        engine.set_typo_correction(TypoCorrection::parse("nn\tn\n"));
+        let output = type_and_convert(&mut engine, "konnichiha");
+        assert_eq!(output.display, "今日は");
+        assert_eq!(output.page.unwrap().candidates[0].annotation, None);
+    }
+
+    #[test]
+    fn typo_correction_needs_typed_romaji() {
+        let mut engine = typo_engine();
+        for ch in "kannjii".chars() {
+            engine.process(EngineCommand::InsertChar(ch));
+        }
+        // Backspace で直した入力は打鍵したローマ字と対応しない
+        engine.process(EngineCommand::Backspace);
+        let output = engine.process(EngineCommand::Convert);
+        assert_ne!(output.display, "漢字");
+    }
+
+    #[test]
+    fn typo_correction_disabled_without_table() {
+        let mut engine = test_engine();
+        let output = type_and_convert(&mut engine, "kannji");
+        assert_ne!(output.display, "漢字");
+    }
+
+    // === 句読点での自動変換 ===
+
+    fn punctuation_engine() -> ConversionEngine {
+        let mut engine = test_engine();
+        engine.set_options(EngineOptions {
+            auto_commit_on_punctuation: true,
+            ..EngineOptions::default()
+        });
+        engine
+    }
+
+    #[test]
+    fn punctuation_converts_and_commits() {
+        let mut engine = punctuation_engine();
+        let output = type_romaji(&mut engine, "kanji.");
+        assert_eq!(output.committed, "漢字。");
+        assert_eq!(output.display, "");
+        // 続けて入力できる
+        let output = type_romaji(&mut engine, "kaigi,");
+        assert_eq!(output.committed, "会議、");
+    }
+
+    #[test]
+    fn punctuation_converts_each_segment() {
+        let mut engine = punctuation_engine();
+        let output = type_romaji(&mut engine, "watashinonamae.");
+        assert_eq!(output.committed, "私の名前。");
+    }
+
+    #[test]
+    fn punctuation_commits_unknown_reading_as_is() {
+        let mut engine = punctuation_engine();
+        let output = type_romaji(&mut engine, "hoge,");
+        assert_eq!(output.committed, "ほげ、");
+    }
+
+    #[test]
+    fn punctuation_alone_commits() {
+        let mut engine = punctuation_engine();
+        let output = engine.process(EngineCommand::InsertChar('.'));
+        assert_eq!(output.committed, "。");
+        assert!(!engine.accepts(&EngineCommand::Convert));
+    }
+
+    #[test]
+    fn punctuation_keeps_text_after_caret() {
+        let mut engine = punctuation_engine();
+        type_romaji(&mut engine, "kanjikaigi");
+        for _ in 0..3 {
+            engine.process(EngineCommand::MoveLeft);
+        }
+        let output = engine.process(EngineCommand::InsertChar(','));
+        assert_eq!(output.committed, "漢字、");
+        assert_eq!(output.display, "かいぎ");
+        assert_eq!(output.caret, Some(0));
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "会議");
+    }
+
+    #[test]
+    fn punctuation_while_converting_commits_candidate() {
+        let mut engine = punctuation_engine();
+        type_and_convert(&mut engine, "kanji");
+        engine.process(EngineCommand::NextCandidate);
+        let output = engine.process(EngineCommand::InsertChar('.'));
+        assert_eq!(output.committed, "感じ。");
+        assert_eq!(output.display, "");
+    }
+
+    #[test]
+    fn decimal_point_after_digit_does_not_commit() {
+        let mut engine = punctuation_engine();
+        let output = type_romaji(&mut engine, "1.5");
+        assert_eq!(output.committed, "");
+        assert_eq!(output.display, "1。5");
+    }
+
+    #[test]
+    fn punctuation_appended_when_disabled() {
+        let mut engine = test_engine();
+        let output = type_romaji(&mut engine, "kanji.");
+        assert_eq!(output.committed, "");
+        assert_eq!(output.display, "かんじ。");
+    }
+
+    // === ライブ変換 ===
+
+    fn live_engine() -> ConversionEngine {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        engine.set_options(EngineOptions {
+            live_conversion: true,
+            ..EngineOptions::default()
+        });
+        engine
+    }
+
+    #[test]
+    fn live_conversion_updates_each_keystroke() {
+        let mut engine = live_engine();
+        let output = type_romaji(&mut engine, "kanj");
+        assert_eq!(output.display, "缶j");
+        let output = type_romaji(&mut engine, "ik");
+        assert_eq!(output.display, "漢字k");
+        assert_eq!(
+            spans(&output),
+            [
+                ("漢字", PreeditAttribute::Converted),
+                ("k", PreeditAttribute::PendingRomaji),
+            ]
+        );
+        assert_eq!(output.caret, Some(3));
+        assert_eq!(engine.state(), EngineState::Composing);
+    }
+
+    #[test]
+    fn live_conversion_converts_each_segment() {
+        let mut engine = live_engine();
+        let output = type_romaji(&mut engine, "watashinonamae");
+        assert_eq!(output.display, "私の名前");
+    }
+
+    #[test]
+    fn live_conversion_commits_on_enter() {
+        let mut engine = live_engine();
+        type_romaji(&mut engine, "kanji");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "漢字");
+        assert_eq!(engine.state(), EngineState::Direct);
+        // 変換して確定したのと同じく取り消せる
+        let output = engine.process(EngineCommand::UndoCommit);
+        assert_eq!(output.undone_commit, "漢字");
+        assert_eq!(engine.candidates().unwrap(), &["漢字", "感じ", "幹事"]);
+    }
+
+    #[test]
+    fn live_conversion_shows_what_enter_commits() {
+        // 末尾の n は確定すると「ん」になるので、表示も「ん」として変換する
+        let mut engine = live_engine();
+        let output = type_romaji(&mut engine, "kan");
+        assert_eq!(output.display, "缶");
+        assert_eq!(spans(&output), [("缶", PreeditAttribute::Converted)]);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "缶");
+
+        let output = type_romaji(&mut engine, "kanjik");
+        let display = output.display.clone();
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, display);
+    }
+
+    /// 引いた読みを記録する提供元。
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl CandidateProvider for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        fn lookup(&self, reading: &str) -> Vec<Candidate> {
+            self.0.lock().unwrap().push(reading.to_string());
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn live_conversion_reuses_unchanged_segments() {
+        let mut engine = live_engine();
+        let lookups = Arc::new(Mutex::new(Vec::new()));
+        engine.add_provider(Box::new(Recorder(Arc::clone(&lookups))), MergeRule::new(0));
+        type_romaji(&mut engine, "watashino");
+        let count = |lookups: &Mutex<Vec<String>>| {
+            lookups
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|r| *r == "わたし")
+                .count()
+        };
+        let before = count(&lookups);
+        let output = type_romaji(&mut engine, "namae");
+        assert_eq!(output.display, "私の名前");
+        // 前の文節の候補は引き直さない
+        assert_eq!(count(&lookups), before);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "私の名前");
+    }
+
+    #[test]
+    fn live_conversion_space_opens_candidates() {
+        let mut engine = live_engine();
+        type_romaji(&mut engine, "kanji");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "漢字");
+        assert_eq!(engine.state(), EngineState::Converting);
+        assert_eq!(engine.candidates().unwrap(), &["漢字", "感じ", "幹事"]);
+    }
+
+    #[test]
+    fn live_conversion_keeps_unknown_reading() {
+        let mut engine = live_engine();
+        let output = type_romaji(&mut engine, "hoge");
+        assert_eq!(output.display, "ほげ");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "ほげ");
+    }
+
+    #[test]
+    fn live_conversion_shows_reading_while_editing() {
+        let mut engine = live_engine();
+        type_romaji(&mut engine, "kanji");
+        let output = engine.process(EngineCommand::MoveLeft);
+        assert_eq!(output.display, "かんじ");
+        let output = engine.process(EngineCommand::MoveToEnd);
+        assert_eq!(output.display, "漢字");
+        engine.process(EngineCommand::MoveLeft);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "かんじ");
+    }
+
+    #[test]
+    fn live_conversion_follows_learning() {
+        let mut engine = live_engine();
+        type_and_convert(&mut engine, "kanji");
+        engine.process(EngineCommand::NextCandidate);
+        engine.process(EngineCommand::Commit);
+        let output = type_romaji(&mut engine, "kanji");
+        assert_eq!(output.display, "感じ");
+        engine.process(EngineCommand::Cancel);
+
+        // 一度引いた読みでも、辞書に登録すれば変換される
+        let output = type_romaji(&mut engine, "hoge");
+        assert_eq!(output.display, "ほげ");
+        engine.process(EngineCommand::Cancel);
+        engine.user_dict_mut().unwrap().record("ほげ", "保下");
+        let output = type_romaji(&mut engine, "hoge");
+        assert_eq!(output.display, "保下");
+    }
+
+    // === スニペット ===
+
+    fn snippet_engine() -> ConversionEngine {
+        use crate::snippet::{SNIPPET_PRIORITY, SnippetDictionary};
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        let snippets = SnippetDictionary::parse(
+            "@@sig\t山田太郎\\n株式会社サンプル\t署名\n\
+             あいさつ\tお世話になっております。{cursor}\\n以上\n",
+        );
+        engine.add_provider(Box::new(snippets), MergeRule::new(SNIPPET_PRIORITY));
+        engine
+    }
+
+    #[test]
+    fn snippet_commits_multiline_text() {
+        let mut engine = snippet_engine();
+        let output = type_and_convert(&mut engine, "@@sig");
+        assert_eq!(output.display, "山田太郎\n株式会社サンプル");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "山田太郎\n株式会社サンプル");
+        assert_eq!(output.commit_caret, None);
+    }
+
+    #[test]
+    fn snippet_cursor_sets_commit_caret() {
+        let mut engine = snippet_engine();
+        type_and_convert(&mut engine, "aisatsu");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "お世話になっております。\n以上");
+        assert_eq!(output.commit_caret, Some(12));
+        // カーソルを途中に置いた確定は取り消せない
+        assert!(!engine.accepts(&EngineCommand::UndoCommit));
+    }
+
+    #[test]
+    fn snippet_is_not_learned() {
+        let mut engine = snippet_engine();
+        type_and_convert(&mut engine, "@@sig");
+        engine.process(EngineCommand::Commit);
+        let ud = engine.user_dict_mut().unwrap();
+        assert!(ud.lookup("@@しg").is_none());
+        assert_eq!(engine.history().count(), 0);
+    }
+
+    #[test]
+    fn snippet_is_kept_out_of_context() {
+        let mut engine = snippet_engine();
+        type_and_convert(&mut engine, "kaigi");
+        engine.process(EngineCommand::Commit);
+        type_and_convert(&mut engine, "@@sig");
+        engine.process(EngineCommand::Commit);
+        type_and_convert(&mut engine, "kanji");
+        engine.process(EngineCommand::Commit);
+        assert_eq!(engine.history().collect::<Vec<_>>(), ["会議", "漢字"]);
+
+        // 保存して読み込んでも bigram の行が壊れない
+        let path = std::env::temp_dir().join("japinput_snippet_context_test.txt");
+        let ud = engine.user_dict_mut().unwrap();
+        ud.save(&path).unwrap();
+        let loaded = UserDictionary::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(loaded.successors("山田太郎\n株式会社サンプル").is_empty());
+        assert_eq!(loaded.successors("会議"), ["漢字"]);
+        assert_eq!(loaded.lookup("かんじ").unwrap(), &["漢字"]);
+    }
+
+    // === 計算式 ===
+
+    fn calculator_engine() -> ConversionEngine {
+        let mut engine = test_engine();
+        engine.add_provider(Box::new(Calculator), MergeRule::new(CALCULATOR_PRIORITY));
+        engine
+    }
+
+    #[test]
+    fn convert_expression_offers_result() {
+        let mut engine = calculator_engine();
+        let output = type_and_convert(&mut engine, "12*(3+4)=");
+        assert_eq!(segment_texts(&output), ["84"]);
+        assert_eq!(
+            engine.candidates().unwrap(),
+            &["84", "８４", "84.0", "12*(3+4)=84"]
+        );
+        engine.process(EngineCommand::NextCandidate);
+        engine.process(EngineCommand::NextCandidate);
+        engine.process(EngineCommand::NextCandidate);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "12*(3+4)=84");
+    }
+
+    #[test]
+    fn convert_expression_typed_with_kana_symbols() {
+        // `-` `.` はローマ字変換で「ー」「。」になる
+        let mut engine = calculator_engine();
+        let output = type_and_convert(&mut engine, "1.5-0.25=");
+        assert_eq!(output.display, "1.25");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "1.25");
+    }
+
+    #[test]
+    fn expression_without_equals_is_not_calculated() {
+        let mut engine = calculator_engine();
+        let output = type_and_convert(&mut engine, "12*3");
+        assert_eq!(output.committed, "12*3");
+    }
+
+    // === abbrev モード ===
+
+    fn abbrev_engine() -> ConversionEngine {
+        let mut engine = test_engine();
+        engine.set_options(EngineOptions {
+            abbrev_key: Some('/'),
+            ..EngineOptions::default()
+        });
+        engine
+    }
+
+    #[test]
+    fn abbrev_is_off_by_default() {
+        let mut engine = test_engine();
+        let output = type_romaji(&mut engine, "/");
+        assert_eq!(output.display, "/");
+    }
+
+    #[test]
+    fn abbrev_key_keeps_ascii_reading() {
+        let mut engine = abbrev_engine();
+        let output = type_romaji(&mut engine, "/greek");
+        assert_eq!(output.display, "greek");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(segment_texts(&output), vec!["α"]);
+        engine.process(EngineCommand::NextCandidate);
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "β");
+
+        // abbrev モードは確定で終わる
+        let output = type_romaji(&mut engine, "ka");
+        assert_eq!(output.display, "か");
+    }
+
+    #[test]
+    fn abbrev_without_candidates_commits_ascii() {
+        let mut engine = abbrev_engine();
+        type_romaji(&mut engine, "/hoge");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.committed, "hoge");
+        assert_eq!(engine.state(), EngineState::Direct);
+    }
+
+    #[test]
+    fn abbrev_key_mid_composition_is_ordinary_input() {
+        let mut engine = abbrev_engine();
+        let output = type_romaji(&mut engine, "ka/");
+        assert_eq!(output.display, "か/");
+    }
+
+    #[test]
+    fn abbrev_key_is_configurable() {
+        let mut engine = test_engine();
+        engine.set_options(EngineOptions {
+            abbrev_key: Some(';'),
+            ..EngineOptions::default()
+        });
+        let output = type_romaji(&mut engine, "/ka");
+        assert_eq!(output.display, "/か");
+        engine.process(EngineCommand::Cancel);
+        let output = type_romaji(&mut engine, ";tel");
+        assert_eq!(output.display, "tel");
+    }
+
+    #[test]
+    fn backspace_on_empty_abbrev_leaves_mode() {
+        let mut engine = abbrev_engine();
+        type_romaji(&mut engine, "/");
+        assert_eq!(engine.state(), EngineState::Composing);
+        engine.process(EngineCommand::Backspace);
+        assert_eq!(engine.state(), EngineState::Direct);
+        let output = type_romaji(&mut engine, "a");
+        assert_eq!(output.display, "あ");
+    }
 }
@@ -0,0 +1,675 @@
+//! SKK 方式の入力。
+//!
+//! `InputStyle::Skk` のときのコマンド処理。小文字はかなとして即座に確定し、
+//! 大文字で ▽（読みの入力）を始め、Space で ▼（候補の選択）に移る。
+//! ▽ で続けて大文字を入力すると、そこからを送り仮名として変換する。
+//! `q` `l` `L` で入力モードを切り替え、Ctrl+J (KanaMode) でかなモードに戻る。
+//! abbrev モードのキー（設定ファイルの既定は `/`）で、英字をそのまま読みとする ▽ を始める。
+//! 辞書・ユーザー辞書・候補リストは通常の入力方式と共通。
+
+use super::{ConversionEngine, EngineCommand, EngineOutput, EngineState};
+use super::{PreeditAttribute, PreeditSpan};
+use crate::candidate::Candidate;
+use crate::katakana;
+use crate::segmenter::Bunsetsu;
+
+/// 読みの入力中（▽）の印。
+const MARK_READING: &str = "▽";
+/// 候補の選択中（▼）の印。
+const MARK_CONVERTING: &str = "▼";
+/// 送り仮名の開始位置の印。
+const MARK_OKURI: &str = "*";
+
+/// SKK の入力モード。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkkMode {
+    /// ひらがな
+    #[default]
+    Hiragana,
+    /// カタカナ (`q` で切り替え)
+    Katakana,
+    /// 半角英数 (`l`)。キーはアプリケーションにそのまま渡す。
+    Latin,
+    /// 全角英数 (`L`)
+    WideLatin,
+}
+
+/// SKK 方式の入力の状態。
+#[derive(Debug, Clone, Default)]
+pub(super) struct SkkState {
+    mode: SkkMode,
+    /// ▽ で読みを入力中か（abbrev モードも含む）
+    marking: bool,
+    /// 送り仮名の入力中（▼ では送り仮名付きで変換中）なら、
+    /// 送り仮名の前までの読みと送り仮名の子音
+    okuri: Option<(String, char)>,
+}
+
+impl SkkState {
+    /// ▽・送り仮名の状態を破棄する。入力モードはそのまま。
+    pub(super) fn clear(&mut self) {
+        self.marking = false;
+        self.okuri = None;
+    }
+
+    /// 送り仮名付きで入力・変換中か。
+    pub(super) fn has_okuri(&self) -> bool {
+        self.okuri.is_some()
+    }
+}
+
+impl ConversionEngine {
+    /// SKK の入力モードを返す。
+    pub fn skk_mode(&self) -> SkkMode {
+        self.skk.mode
+    }
+
+    /// SKK 方式でコマンドを処理するかどうかを返す。
+    ///
+    /// 入力がないときは文字入力と、かなモード以外での KanaMode だけを処理する。
+    /// 半角英数モードでは文字もアプリケーションにそのまま渡す。
+    pub(super) fn skk_accepts(&self, command: &EngineCommand) -> bool {
+        match self.state {
+            EngineState::Direct => match command {
+                EngineCommand::InsertChar(_) => self.skk.mode != SkkMode::Latin,
+                EngineCommand::KanaMode => self.skk.mode != SkkMode::Hiragana,
+                _ => false,
+            },
+            EngineState::Composing | EngineState::Converting => true,
+        }
+    }
+
+    /// SKK 方式でコマンドを処理する。
+    pub(super) fn process_skk(&mut self, command: EngineCommand) -> EngineOutput {
+        match self.state {
+            EngineState::Direct => {
+                self.forget_last_commit();
+                match command {
+                    EngineCommand::InsertChar(ch) => self.skk_insert(ch),
+                    EngineCommand::KanaMode => {
+                        self.skk.mode = SkkMode::Hiragana;
+                        self.empty_output()
+                    }
+                    _ => self.empty_output(),
+                }
+            }
+            EngineState::Composing => self.skk_composing(command),
+            EngineState::Converting => self.skk_converting(command),
+        }
+    }
+
+    /// 入力中（ローマ字の途中、▽、abbrev）のコマンドを処理する。
+    fn skk_composing(&mut self, command: EngineCommand) -> EngineOutput {
+        match command {
+            EngineCommand::InsertChar(ch) => self.skk_insert(ch),
+            EngineCommand::Convert if self.skk.marking => {
+                // ▼ から戻った送り仮名付きの読みは、送り仮名付きで変換し直す
+                let okuri = self.skk.okuri.clone().map(|(stem, consonant)| {
+                    let okuri = self.input.output()[stem.len()..].to_string();
+                    (stem, consonant, okuri)
+                });
+                self.skk_convert(okuri)
+            }
+            EngineCommand::Commit | EngineCommand::KanaMode => self.skk_commit_reading(false),
+            EngineCommand::Cancel => {
+                self.input.reset();
+                self.skk.clear();
+                self.state = EngineState::Direct;
+                self.empty_output()
+            }
+            EngineCommand::Backspace => {
+                self.input.backspace();
+                if self.input.pending().is_empty() {
+                    self.skk.okuri = None;
+                }
+                if self.input.is_empty() {
+                    self.input.reset();
+                    self.skk.clear();
+                    self.state = EngineState::Direct;
+                    return self.empty_output();
+                }
+                self.skk_composing_output()
+            }
+            _ => self.skk_composing_output(),
+        }
+    }
+
+    /// 候補の選択中（▼）のコマンドを処理する。
+    fn skk_converting(&mut self, command: EngineCommand) -> EngineOutput {
+        match command {
+            EngineCommand::Convert | EngineCommand::NextCandidate => {
+                if let Some(cl) = self.focused_candidates_mut() {
+                    cl.next();
+                }
+                self.skk_converting_output()
+            }
+            EngineCommand::InsertChar(ch) if self.label_position(ch).is_some() => {
+                let position = self.label_position(ch).unwrap_or_default();
+                self.skk_select_on_page(position)
+            }
+            EngineCommand::PrevCandidate | EngineCommand::InsertChar('x') => {
+                if let Some(cl) = self.focused_candidates_mut() {
+                    cl.prev();
+                }
+                self.skk_converting_output()
+            }
+            EngineCommand::NextPage => {
+                if let Some(cl) = self.focused_candidates_mut() {
+                    cl.next_page();
+                }
+                self.skk_converting_output()
+            }
+            EngineCommand::PrevPage => {
+                if let Some(cl) = self.focused_candidates_mut() {
+                    cl.prev_page();
+                }
+                self.skk_converting_output()
+            }
+            EngineCommand::SelectCandidate(position) => self.skk_select_on_page(position),
+            EngineCommand::Commit | EngineCommand::KanaMode => self.skk_commit_candidate(),
+            EngineCommand::Cancel | EngineCommand::Backspace => {
+                // ▽ に戻る。送り仮名の区切りはそのまま残す
+                self.segments.clear();
+                self.state = EngineState::Composing;
+                self.skk_composing_output()
+            }
+            EngineCommand::InsertChar(ch) => {
+                // 選択中の候補を確定して、続けて入力する
+                let committed = self.skk_commit_candidate().committed;
+                self.forget_last_commit();
+                let output = self.skk_insert(ch);
+                EngineOutput {
+                    committed: committed + &output.committed,
+                    ..output
+                }
+            }
+            _ => self.skk_converting_output(),
+        }
+    }
+
+    /// 1文字入力する。
+    fn skk_insert(&mut self, ch: char) -> EngineOutput {
+        if self.input.is_literal() {
+            self.input.feed_char(ch);
+            return self.skk_composing_output();
+        }
+        match self.skk.mode {
+            SkkMode::Latin => return self.committed_output(ch.to_string()),
+            SkkMode::WideLatin => return self.committed_output(to_wide(ch).to_string()),
+            SkkMode::Hiragana | SkkMode::Katakana => {}
+        }
+        let idle = self.input.pending().is_empty();
+        match ch {
+            'q' if idle && self.skk.marking => self.skk_commit_reading(true),
+            'q' if idle => {
+                self.skk.mode = match self.skk.mode {
+                    SkkMode::Katakana => SkkMode::Hiragana,
+                    _ => SkkMode::Katakana,
+                };
+                self.empty_output()
+            }
+            'l' | 'L' if idle && !self.skk.marking => {
+                self.skk.mode = if ch == 'l' {
+                    SkkMode::Latin
+                } else {
+                    SkkMode::WideLatin
+                };
+                self.empty_output()
+            }
+            _ if idle && !self.skk.marking && Some(ch) == self.options.abbrev_key => {
+                self.input.start_literal();
+                self.skk.marking = true;
+                self.state = EngineState::Composing;
+                self.skk_composing_output()
+            }
+            _ if ch.is_ascii_uppercase() => self.skk_insert_upper(ch.to_ascii_lowercase()),
+            _ => {
+                self.input.feed_char(ch);
+                self.skk_after_feed()
+            }
+        }
+    }
+
+    /// 大文字を入力する。▽ を開始するか、▽ の途中なら送り仮名を開始する。
+    fn skk_insert_upper(&mut self, ch: char) -> EngineOutput {
+        if !self.skk.marking {
+            // 入力途中のローマ字（`kA` の `k`）は ▽ の読みに含める
+            self.skk.marking = true;
+            self.state = EngineState::Composing;
+        } else if self.skk.okuri.is_none() && !self.input.output().is_empty() {
+            if self.input.pending() == "n" {
+                self.input.flush();
+            }
+            if self.input.pending().is_empty() {
+                self.skk.okuri = Some((self.input.output().to_string(), ch));
+            }
+        }
+        self.input.feed_char(ch);
+        self.skk_after_feed()
+    }
+
+    /// ローマ字を入力した後の処理。
+    ///
+    /// ▽ でなければ、できたかなをすぐに確定する。送り仮名がそろったら変換する。
+    fn skk_after_feed(&mut self) -> EngineOutput {
+        if let Some((stem, consonant)) = self.skk.okuri.clone() {
+            if !self.input.pending().is_empty() {
+                return self.skk_composing_output();
+            }
+            let okuri = self.input.output()[stem.len()..].to_string();
+            return self.skk_convert(Some((stem, consonant, okuri)));
+        }
+        if self.skk.marking {
+            return self.skk_composing_output();
+        }
+        let kana = self.input.take_output();
+        self.state = if self.input.is_empty() {
+            EngineState::Direct
+        } else {
+            EngineState::Composing
+        };
+        EngineOutput {
+            committed: self.skk_kana(&kana),
+            ..self.skk_composing_output()
+        }
+    }
+
+    /// ▽ の読み（abbrev では英字）を変換して ▼ に移る。
+    ///
+    /// 送り仮名があれば「読み + 送り仮名の子音」で辞書を引き、候補に送り仮名を付ける。
+    /// ユーザー辞書に同じ送り仮名で確定した候補があれば先頭に並べる。
+    /// 送り仮名の区切りは ▽ に戻るときのために確定するまで残す。
+    /// 辞書にない読みはひらがな・カタカナを候補とする。abbrev で辞書にない場合は ▽ のまま。
+    fn skk_convert(&mut self, okuri: Option<(String, char, String)>) -> EngineOutput {
+        let (reading, key, suffix) = match okuri {
+            Some((stem, consonant, okuri)) => (stem.clone(), format!("{stem}{consonant}"), okuri),
+            None => {
+                self.input.flush();
+                let reading = self.input.output().to_string();
+                (reading.clone(), reading, String::new())
+            }
+        };
+        let known = self.has_candidates(&key);
+        if !known && self.input.is_literal() {
+            return self.skk_composing_output();
+        }
+        self.reading = format!("{reading}{suffix}");
+        let stem = if known { key } else { reading };
+        let prev = self.history.back().cloned();
+        let b = Bunsetsu {
+            stem,
+            known,
+            suffix,
+        };
+        let segment = if known && !b.suffix.is_empty() {
+            let stems = self.skk_okuri_candidates(&b.stem, &b.suffix, prev.as_deref());
+            self.segment_with(b, stems)
+        } else {
+            self.make_segment(b, prev.as_deref())
+        };
+        self.segments = vec![segment];
+        self.focus = 0;
+        self.state = EngineState::Converting;
+        self.skk_converting_output()
+    }
+
+    /// 送りありの読み `key` の候補を返す。
+    ///
+    /// ユーザー辞書の送り仮名ブロック（`[る/来/]`）に `okuri` の候補があれば、
+    /// その順で先頭に移す。なければ送り仮名を問わない読みの候補の順になる。
+    fn skk_okuri_candidates(&self, key: &str, okuri: &str, prev: Option<&str>) -> Vec<Candidate> {
+        let mut candidates = self.merge_candidates(key, prev);
+        let preferred = self
+            .user_dict
+            .as_ref()
+            .and_then(|ud| ud.lookup_okuri(key, okuri))
+            .unwrap_or_default();
+        for word in preferred.iter().rev() {
+            if let Some(pos) = candidates.iter().position(|c| &c.text == word) {
+                let c = candidates.remove(pos);
+                candidates.insert(0, c);
+            }
+        }
+        candidates
+    }
+
+    /// ▽ の読み（またはローマ字の途中）をかなのまま確定する。
+    ///
+    /// `toggle` が true なら、ひらがなモードではカタカナ、カタカナモードではひらがなで確定する (`q`)。
+    fn skk_commit_reading(&mut self, toggle: bool) -> EngineOutput {
+        self.input.flush();
+        let kana = self.input.output();
+        let committed = match (toggle, self.skk.mode) {
+            _ if self.input.is_literal() => kana.to_string(),
+            (true, SkkMode::Katakana) => kana.to_string(),
+            (true, _) => katakana::to_katakana(kana),
+            (false, _) => self.skk_kana(kana),
+        };
+        self.input.reset();
+        self.skk.clear();
+        self.state = EngineState::Direct;
+        self.push_history(&committed);
+        self.committed_output(committed)
+    }
+
+    /// ▼ で選択中の候補を確定する。
+    fn skk_commit_candidate(&mut self) -> EngineOutput {
+        let output = self.commit_candidate();
+        self.skk.clear();
+        output
+    }
+
+    /// ▼ で表示中のページ内の位置の候補を確定する。
+    fn skk_select_on_page(&mut self, position: usize) -> EngineOutput {
+        let output = self.select_on_page(position);
+        if self.state == EngineState::Converting {
+            return self.skk_converting_output();
+        }
+        self.skk.clear();
+        output
+    }
+
+    /// かなを入力モードに合わせて変換する（カタカナモードならカタカナ）。
+    fn skk_kana(&self, kana: &str) -> String {
+        match self.skk.mode {
+            SkkMode::Katakana => katakana::to_katakana(kana),
+            _ => kana.to_string(),
+        }
+    }
+
+    /// 入力中の EngineOutput を組み立てる。▽ の読みには印を付ける。
+    fn skk_composing_output(&self) -> EngineOutput {
+        if self.state == EngineState::Direct {
+            return self.empty_output();
+        }
+        let mut spans = Vec::new();
+        if self.skk.marking {
+            let output = self.input.output();
+            let (stem, okuri) = match self.skk.okuri {
+                Some((ref stem, _)) => output.split_at(stem.len()),
+                None => (output, ""),
+            };
+            spans.push(PreeditSpan::new(MARK_READING, PreeditAttribute::Input));
+            spans.push(PreeditSpan::new(
+                &self.skk_kana(stem),
+                PreeditAttribute::Input,
+            ));
+            if self.skk.okuri.is_some() {
+                spans.push(PreeditSpan::new(MARK_OKURI, PreeditAttribute::Input));
+                spans.push(PreeditSpan::new(
+                    &self.skk_kana(okuri),
+                    PreeditAttribute::Input,
+                ));
+            }
+        }
+        spans.push(PreeditSpan::new(
+            self.input.pending(),
+            PreeditAttribute::PendingRomaji,
+        ));
+        spans.retain(|span| !span.text.is_empty());
+        let display: String = spans.iter().map(|span| span.text.as_str()).collect();
+        EngineOutput {
+            caret: Some(display.chars().count()),
+            display,
+            preedit: spans,
+            ..EngineOutput::default()
+        }
+    }
+
+    /// 候補の選択中の EngineOutput を組み立てる。先頭に ▼ を付ける。
+    fn skk_converting_output(&self) -> EngineOutput {
+        let mut output = self.converting_output();
+        output.display.insert_str(0, MARK_CONVERTING);
+        output.preedit.insert(
+            0,
+            PreeditSpan::new(MARK_CONVERTING, PreeditAttribute::Converted),
+        );
+        output.caret = output.caret.map(|caret| caret + 1);
+        output
+    }
+}
+
+/// ASCII 文字を全角に変換する。
+fn to_wide(ch: char) -> char {
+    match ch {
+        ' ' => '\u{3000}',
+        '!'..='~' => char::from_u32(ch as u32 + 0xFEE0).unwrap_or(ch),
+        _ => ch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionary::Dictionary;
+    use crate::engine::{EngineOptions, InputStyle};
+    use crate::user_dictionary::UserDictionary;
+    use std::path::Path;
+
+    fn skk_engine() -> ConversionEngine {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        let mut engine =
+            ConversionEngine::new_with_user_dict(Some(dict), Some(UserDictionary::new()));
+        engine.set_options(EngineOptions {
+            input_style: InputStyle::Skk,
+            abbrev_key: Some('/'),
+            ..EngineOptions::default()
+        });
+        engine
+    }
+
+    /// 文字列を1文字ずつ入力し、確定したテキストを連結して最後の出力とともに返す。
+    fn type_keys(engine: &mut ConversionEngine, keys: &str) -> (String, EngineOutput) {
+        let mut committed = String::new();
+        let mut output = EngineOutput::default();
+        for ch in keys.chars() {
+            output = engine.process(EngineCommand::InsertChar(ch));
+            committed.push_str(&output.committed);
+        }
+        (committed, output)
+    }
+
+    // === かなの入力 ===
+
+    #[test]
+    fn lowercase_commits_kana_immediately() {
+        let mut engine = skk_engine();
+        let (committed, output) = type_keys(&mut engine, "kak");
+        assert_eq!(committed, "か");
+        assert_eq!(output.display, "k");
+        assert_eq!(engine.state(), EngineState::Composing);
+        let (committed, _) = type_keys(&mut engine, "i");
+        assert_eq!(committed, "き");
+        assert_eq!(engine.state(), EngineState::Direct);
+    }
+
+    #[test]
+    fn q_toggles_katakana_mode() {
+        let mut engine = skk_engine();
+        let (committed, _) = type_keys(&mut engine, "qkana");
+        assert_eq!(committed, "カナ");
+        assert_eq!(engine.skk_mode(), SkkMode::Katakana);
+        type_keys(&mut engine, "q");
+        assert_eq!(engine.skk_mode(), SkkMode::Hiragana);
+    }
+
+    // === ▽ と ▼ ===
+
+    #[test]
+    fn uppercase_starts_reading_and_space_converts() {
+        let mut engine = skk_engine();
+        let (committed, output) = type_keys(&mut engine, "Kanji");
+        assert_eq!(committed, "");
+        assert_eq!(output.display, "▽かんじ");
+        assert_eq!(output.caret, Some(4));
+
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▼漢字");
+        assert_eq!(output.preedit[0].text, "▼");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▼感じ");
+        let output = engine.process(EngineCommand::InsertChar('x'));
+        assert_eq!(output.display, "▼漢字");
+
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "漢字");
+        assert_eq!(engine.state(), EngineState::Direct);
+    }
+
+    #[test]
+    fn typing_in_converting_commits_and_continues() {
+        let mut engine = skk_engine();
+        type_keys(&mut engine, "Kanji");
+        engine.process(EngineCommand::Convert);
+        let (committed, _) = type_keys(&mut engine, "wo");
+        assert_eq!(committed, "漢字を");
+    }
+
+    #[test]
+    fn cancel_in_converting_returns_to_reading() {
+        let mut engine = skk_engine();
+        type_keys(&mut engine, "Kanji");
+        engine.process(EngineCommand::Convert);
+        let output = engine.process(EngineCommand::Cancel);
+        assert_eq!(output.display, "▽かんじ");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "かんじ");
+    }
+
+    #[test]
+    fn q_in_reading_commits_katakana() {
+        let mut engine = skk_engine();
+        let (committed, _) = type_keys(&mut engine, "Kanaq");
+        assert_eq!(committed, "カナ");
+        assert_eq!(engine.skk_mode(), SkkMode::Hiragana);
+    }
+
+    #[test]
+    fn okurigana_converts_with_consonant() {
+        let mut engine = skk_engine();
+        let (_, output) = type_keys(&mut engine, "Ooki");
+        assert_eq!(output.display, "▽おおき");
+        let (_, output) = type_keys(&mut engine, "I");
+        assert_eq!(output.display, "▼大きい");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "大きい");
+        let ud = engine.user_dict_mut().unwrap();
+        assert_eq!(ud.lookup("おおきi").unwrap()[0], "大き");
+        assert_eq!(ud.lookup_okuri("おおきi", "い").unwrap(), &["大き"]);
+    }
+
+    #[test]
+    fn okurigana_ranks_candidates_by_okuri_block() {
+        let mut engine = skk_engine();
+        let ud = engine.user_dict_mut().unwrap();
+        ud.record_okuri("あk", "く", "開");
+        ud.record_okuri("あk", "け", "明");
+        // 送り仮名を問わない候補の先頭は「明」だが、「く」では「開」を先にする
+        let (_, output) = type_keys(&mut engine, "AKu");
+        assert_eq!(output.display, "▼開く");
+        engine.process(EngineCommand::Cancel);
+        engine.process(EngineCommand::Cancel);
+        let (_, output) = type_keys(&mut engine, "AKe");
+        assert_eq!(output.display, "▼明け");
+    }
+
+    #[test]
+    fn cancel_keeps_okurigana() {
+        let mut engine = skk_engine();
+        type_keys(&mut engine, "OokiI");
+        let output = engine.process(EngineCommand::Cancel);
+        assert_eq!(output.display, "▽おおき*い");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▼大きい");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "大きい");
+
+        type_keys(&mut engine, "OokiI");
+        engine.process(EngineCommand::Backspace);
+        let output = engine.process(EngineCommand::Backspace);
+        assert_eq!(output.display, "▽おおき");
+    }
+
+    #[test]
+    fn uppercase_keeps_pending_consonant() {
+        let mut engine = skk_engine();
+        let (committed, output) = type_keys(&mut engine, "kA");
+        assert_eq!(committed, "");
+        assert_eq!(output.display, "▽か");
+        let (_, output) = type_keys(&mut engine, "nji");
+        assert_eq!(output.display, "▽かんじ");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▼漢字");
+    }
+
+    #[test]
+    fn okurigana_is_marked_while_pending() {
+        let mut engine = skk_engine();
+        let (_, output) = type_keys(&mut engine, "KaK");
+        assert_eq!(output.display, "▽か*k");
+    }
+
+    #[test]
+    fn unknown_reading_offers_kana() {
+        let mut engine = skk_engine();
+        type_keys(&mut engine, "Hoge");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▼ほげ");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▼ホゲ");
+    }
+
+    #[test]
+    fn backspace_leaves_reading_when_empty() {
+        let mut engine = skk_engine();
+        type_keys(&mut engine, "A");
+        let output = engine.process(EngineCommand::Backspace);
+        assert_eq!(output.display, "");
+        assert_eq!(engine.state(), EngineState::Direct);
+        let (committed, _) = type_keys(&mut engine, "a");
+        assert_eq!(committed, "あ");
+    }
+
+    // === 英数・abbrev ===
+
+    #[test]
+    fn latin_mode_passes_keys_through() {
+        let mut engine = skk_engine();
+        type_keys(&mut engine, "l");
+        assert_eq!(engine.skk_mode(), SkkMode::Latin);
+        assert!(!engine.accepts(&EngineCommand::InsertChar('a')));
+        assert!(engine.accepts(&EngineCommand::KanaMode));
+        engine.process(EngineCommand::KanaMode);
+        assert_eq!(engine.skk_mode(), SkkMode::Hiragana);
+        assert!(!engine.accepts(&EngineCommand::KanaMode));
+    }
+
+    #[test]
+    fn wide_latin_mode_commits_fullwidth() {
+        let mut engine = skk_engine();
+        let (committed, _) = type_keys(&mut engine, "LAb1");
+        assert_eq!(committed, "Ａｂ１");
+        assert_eq!(engine.skk_mode(), SkkMode::WideLatin);
+    }
+
+    #[test]
+    fn abbrev_converts_ascii_reading() {
+        let mut engine = skk_engine();
+        let (_, output) = type_keys(&mut engine, "/tel");
+        assert_eq!(output.display, "▽tel");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▼℡");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "℡");
+    }
+
+    #[test]
+    fn abbrev_without_candidates_commits_ascii() {
+        let mut engine = skk_engine();
+        type_keys(&mut engine, "/qwe");
+        let output = engine.process(EngineCommand::Convert);
+        assert_eq!(output.display, "▽qwe");
+        let output = engine.process(EngineCommand::Commit);
+        assert_eq!(output.committed, "qwe");
+    }
+}
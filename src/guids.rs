@@ -33,3 +33,51 @@ pub fn guid_profile() -> windows::core::GUID {
         data4: [0x8D, 0x3E, 0x7F, 0x0B, 0x2C, 0x5A, 0x6E, 0x9D],
     }
 }
+
+/// 表示属性「入力中のかな」の GUID。
+/// {9C970833-C854-4E1A-AED6-E329C039737A}
+#[cfg(windows)]
+pub fn guid_display_attribute_input() -> windows::core::GUID {
+    windows::core::GUID {
+        data1: 0x9C970833,
+        data2: 0xC854,
+        data3: 0x4E1A,
+        data4: [0xAE, 0xD6, 0xE3, 0x29, 0xC0, 0x39, 0x73, 0x7A],
+    }
+}
+
+/// 表示属性「かなになっていないローマ字」の GUID。
+/// {11084991-BD97-4E6A-A29C-1219E62E0186}
+#[cfg(windows)]
+pub fn guid_display_attribute_pending() -> windows::core::GUID {
+    windows::core::GUID {
+        data1: 0x11084991,
+        data2: 0xBD97,
+        data3: 0x4E6A,
+        data4: [0xA2, 0x9C, 0x12, 0x19, 0xE6, 0x2E, 0x01, 0x86],
+    }
+}
+
+/// 表示属性「変換済みの文節」の GUID。
+/// {AC8F420B-AA66-4F3D-AF86-181C900A8581}
+#[cfg(windows)]
+pub fn guid_display_attribute_converted() -> windows::core::GUID {
+    windows::core::GUID {
+        data1: 0xAC8F420B,
+        data2: 0xAA66,
+        data3: 0x4F3D,
+        data4: [0xAF, 0x86, 0x18, 0x1C, 0x90, 0x0A, 0x85, 0x81],
+    }
+}
+
+/// 表示属性「候補を選択中の文節」の GUID。
+/// {80FA7F6B-6BE5-4670-88E2-8DD97C98B92C}
+#[cfg(windows)]
+pub fn guid_display_attribute_focused() -> windows::core::GUID {
+    windows::core::GUID {
+        data1: 0x80FA7F6B,
+        data2: 0x6BE5,
+        data3: 0x4670,
+        data4: [0x88, 0xE2, 0x8D, 0xD9, 0x7C, 0x98, 0xB9, 0x2C],
+    }
+}
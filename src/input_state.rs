@@ -1,7 +1,10 @@
 //! 入力状態管理。
 //!
 //! ローマ字を1文字ずつ受け取り、逐次的にひらがなへ変換する。
-//! バッファと確定済み出力を保持する。
+//! バッファと確定済み出力、出力内のカーソル位置を保持する。
+//! 未確定のローマ字は常にカーソル位置にあり、変換したかなはカーソル位置に挿入される。
+//! abbrev モードでは、入力した文字をかなにせずそのまま出力に挿入する。
+//! 末尾への入力だけで組み立てた出力は、打鍵したローマ字も保持する（タイポ補正用）。
 
 use crate::romaji;
 
@@ -12,6 +15,12 @@ pub struct InputState {
     output: String,
     /// まだ確定していないローマ字バッファ
     pending: String,
+    /// 出力内のカーソル位置（文字単位）。pending はこの位置にある。
+    caret: usize,
+    /// 入力した文字をそのまま出力するか（abbrev モード）
+    literal: bool,
+    /// 打鍵したローマ字。末尾への入力以外で出力を変えたら None。
+    typed: Option<String>,
 }
 
 impl InputState {
@@ -20,32 +29,76 @@ impl InputState {
         Self {
             output: String::new(),
             pending: String::new(),
+            caret: 0,
+            literal: false,
+            typed: Some(String::new()),
         }
     }
 
-    /// 1文字入力する。確定したひらがながあれば output に追加される。
+    /// 1文字入力する。確定したひらがながあればカーソル位置に挿入される。
     pub fn feed_char(&mut self, ch: char) {
+        if self.literal {
+            self.typed = None;
+            self.insert_at_caret(&ch.to_string());
+            return;
+        }
+        if self.caret == self.output.chars().count()
+            && let Some(ref mut typed) = self.typed
+        {
+            typed.push(ch);
+        } else {
+            self.typed = None;
+        }
         self.pending.push(ch);
         let result = romaji::convert(&self.pending);
-        self.output.push_str(&result.output);
+        self.insert_at_caret(&result.output);
         self.pending = result.pending;
     }
 
     /// 未確定バッファを確定する（末尾の "n" → "ん"）。
     pub fn flush(&mut self) {
-        if self.pending == "n" {
-            self.output.push('ん');
-            self.pending.clear();
-        } else if !self.pending.is_empty() {
-            self.output.push_str(&self.pending);
-            self.pending.clear();
+        let pending = std::mem::take(&mut self.pending);
+        if pending == "n" {
+            self.insert_at_caret("ん");
+        } else {
+            self.insert_at_caret(&pending);
         }
     }
 
-    /// バッファと出力をクリアする。
+    /// 出力をかなの文字列で置き換え、カーソルを末尾に置く（再変換用）。
+    pub fn set_text(&mut self, text: &str) {
+        self.output = text.to_string();
+        self.pending.clear();
+        self.literal = false;
+        self.typed = None;
+        self.caret = self.output.chars().count();
+    }
+
+    /// 出力を取り出して空にする。pending はそのまま残す（SKK のかな入力用）。
+    pub fn take_output(&mut self) -> String {
+        self.caret = 0;
+        self.typed = None;
+        std::mem::take(&mut self.output)
+    }
+
+    /// バッファと出力をクリアする。abbrev モードも終える。
     pub fn reset(&mut self) {
         self.output.clear();
         self.pending.clear();
+        self.caret = 0;
+        self.literal = false;
+        self.typed = Some(String::new());
+    }
+
+    /// 入力をクリアして abbrev モードを始める。以降の文字はかなにせずそのまま入力する。
+    pub fn start_literal(&mut self) {
+        self.reset();
+        self.literal = true;
+    }
+
+    /// abbrev モードかどうか。
+    pub fn is_literal(&self) -> bool {
+        self.literal
     }
 
     /// 確定済みの出力を返す。
@@ -53,20 +106,92 @@ impl InputState {
         &self.output
     }
 
+    /// 出力と pending を打鍵したローマ字を返す。
+    /// カーソル移動後の入力や削除などで出力と対応しなくなったら None。
+    pub fn typed(&self) -> Option<&str> {
+        self.typed.as_deref()
+    }
+
     /// 未確定のバッファを返す。
     pub fn pending(&self) -> &str {
         &self.pending
     }
 
-    /// 末尾の1文字を削除する。pending があれば pending から、なければ output から削除。
+    /// 表示用の文字列（カーソル位置に pending を挟んだ出力）を返す。
+    pub fn display(&self) -> String {
+        let (before, after) = self.split_output();
+        format!("{before}{}{after}", self.pending)
+    }
+
+    /// 出力をカーソルの前と後ろに分けて返す。
+    pub fn split_output(&self) -> (&str, &str) {
+        self.output.split_at(self.byte_offset(self.caret))
+    }
+
+    /// 表示用の文字列内のカーソル位置（文字単位、pending の後ろ）を返す。
+    pub fn caret(&self) -> usize {
+        self.caret + self.pending.chars().count()
+    }
+
+    /// カーソルの前の1文字を削除する。pending があれば pending から、なければ output から削除。
     pub fn backspace(&mut self) {
         if !self.pending.is_empty() {
+            self.typed = None;
             self.pending.pop();
-        } else {
-            self.output.pop();
+        } else if self.caret > 0 {
+            self.typed = None;
+            self.caret -= 1;
+            self.output.remove(self.byte_offset(self.caret));
         }
     }
 
+    /// カーソルの後ろの1文字を output から削除する。
+    pub fn delete(&mut self) {
+        if self.caret < self.output.chars().count() {
+            self.typed = None;
+            self.output.remove(self.byte_offset(self.caret));
+        }
+    }
+
+    /// カーソルを1文字左へ移動する。pending は先に確定する。
+    pub fn move_left(&mut self) {
+        self.flush();
+        self.caret = self.caret.saturating_sub(1);
+    }
+
+    /// カーソルを1文字右へ移動する。pending は先に確定する。
+    pub fn move_right(&mut self) {
+        self.flush();
+        self.caret = (self.caret + 1).min(self.output.chars().count());
+    }
+
+    /// カーソルを先頭へ移動する。pending は先に確定する。
+    pub fn move_to_start(&mut self) {
+        self.flush();
+        self.caret = 0;
+    }
+
+    /// カーソルを末尾へ移動する。pending は先に確定する。
+    pub fn move_to_end(&mut self) {
+        self.flush();
+        self.caret = self.output.chars().count();
+    }
+
+    /// カーソル位置に文字列を挿入し、カーソルをその後ろへ進める。
+    fn insert_at_caret(&mut self, text: &str) {
+        let at = self.byte_offset(self.caret);
+        self.output.insert_str(at, text);
+        self.caret += text.chars().count();
+    }
+
+    /// 文字単位の位置を output のバイト位置に変換する。
+    fn byte_offset(&self, chars: usize) -> usize {
+        self.output
+            .char_indices()
+            .nth(chars)
+            .map_or(self.output.len(), |(i, _)| i)
+    }
+
     /// 出力と pending の両方が空かどうか。
     pub fn is_empty(&self) -> bool {
         self.output.is_empty() && self.pending.is_empty()
@@ -261,6 +386,168 @@ mod tests {
         assert_eq!(state.output(), "か");
     }
 
+    // === カーソル移動・途中の編集 ===
+
+    fn state_with(romaji: &str) -> InputState {
+        let mut state = InputState::new();
+        for ch in romaji.chars() {
+            state.feed_char(ch);
+        }
+        state
+    }
+
+    #[test]
+    fn insert_at_caret() {
+        let mut state = state_with("kaki");
+        state.move_left();
+        for ch in "ku".chars() {
+            state.feed_char(ch);
+        }
+        assert_eq!(state.output(), "かくき");
+        assert_eq!(state.caret(), 2);
+    }
+
+    #[test]
+    fn pending_is_shown_at_caret() {
+        let mut state = state_with("kaki");
+        state.move_left();
+        state.feed_char('k');
+        assert_eq!(state.display(), "かkき");
+        assert_eq!(state.caret(), 2);
+        state.backspace();
+        assert_eq!(state.display(), "かき");
+        assert_eq!(state.caret(), 1);
+    }
+
+    #[test]
+    fn move_flushes_pending() {
+        let mut state = state_with("kan");
+        state.move_left();
+        assert_eq!(state.output(), "かん");
+        assert_eq!(state.pending(), "");
+        assert_eq!(state.caret(), 1);
+    }
+
+    #[test]
+    fn backspace_and_delete_at_caret() {
+        let mut state = state_with("aiueo");
+        state.move_to_start();
+        state.move_right();
+        state.move_right();
+        state.backspace();
+        assert_eq!(state.output(), "あうえお");
+        state.delete();
+        assert_eq!(state.output(), "あえお");
+        assert_eq!(state.caret(), 1);
+    }
+
+    #[test]
+    fn caret_stays_in_range() {
+        let mut state = state_with("ka");
+        state.move_right();
+        assert_eq!(state.caret(), 1);
+        state.delete();
+        assert_eq!(state.output(), "か");
+        state.move_to_start();
+        state.move_left();
+        state.backspace();
+        assert_eq!(state.caret(), 0);
+        assert_eq!(state.output(), "か");
+        state.move_to_end();
+        assert_eq!(state.caret(), 1);
+    }
+
+    #[test]
+    fn set_text_places_caret_at_end() {
+        let mut state = state_with("k");
+        state.set_text("かんじ");
+        assert_eq!(state.display(), "かんじ");
+        assert_eq!(state.caret(), 3);
+    }
+
+    #[test]
+    fn reset_moves_caret_to_start() {
+        let mut state = state_with("kaki");
+        state.reset();
+        state.feed_char('a');
+        assert_eq!(state.output(), "あ");
+        assert_eq!(state.caret(), 1);
+    }
+
+    #[test]
+    fn take_output_keeps_pending() {
+        let mut state = state_with("kak");
+        assert_eq!(state.take_output(), "か");
+        assert_eq!(state.display(), "k");
+        state.feed_char('i');
+        assert_eq!(state.output(), "き");
+    }
+
+    #[test]
+    fn literal_input_keeps_ascii() {
+        let mut state = InputState::new();
+        state.start_literal();
+        for ch in "Tel-1".chars() {
+            state.feed_char(ch);
+        }
+        assert_eq!(state.output(), "Tel-1");
+        assert_eq!(state.pending(), "");
+        state.reset();
+        assert!(!state.is_literal());
+        state.feed_char('a');
+        assert_eq!(state.output(), "あ");
+    }
+
+    // === 打鍵したローマ字 ===
+
+    #[test]
+    fn typed_keeps_romaji() {
+        let mut state = InputState::new();
+        for ch in "konnnichiha".chars() {
+            state.feed_char(ch);
+        }
+        assert_eq!(state.output(), "こんんにちは");
+        assert_eq!(state.typed(), Some("konnnichiha"));
+        state.flush();
+        assert_eq!(state.typed(), Some("konnnichiha"));
+        state.reset();
+        assert_eq!(state.typed(), Some(""));
+    }
+
+    #[test]
+    fn typed_lost_after_editing() {
+        let mut state = InputState::new();
+        for ch in "kana".chars() {
+            state.feed_char(ch);
+        }
+        state.backspace();
+        assert_eq!(state.typed(), None);
+
+        let mut state = InputState::new();
+        for ch in "kana".chars() {
+            state.feed_char(ch);
+        }
+        state.move_left();
+        state.feed_char('a');
+        assert_eq!(state.typed(), None);
+
+        let mut state = InputState::new();
+        state.set_text("かな");
+        assert_eq!(state.typed(), None);
+    }
+
+    #[test]
+    fn typed_survives_cursor_return_to_end() {
+        let mut state = InputState::new();
+        for ch in "kana".chars() {
+            state.feed_char(ch);
+        }
+        state.move_left();
+        state.move_to_end();
+        state.feed_char('i');
+        assert_eq!(state.typed(), Some("kanai"));
+    }
+
     // === is_empty ===
 
     #[test]
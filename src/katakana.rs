@@ -18,6 +18,24 @@ pub fn to_katakana(input: &str) -> String {
         .collect()
 }
 
+/// カタカナ→ひらがな変換。
+///
+/// カタカナ (U+30A1〜U+30F6) をひらがな (U+3041〜U+3096) に変換する。
+/// カタカナ以外の文字はそのまま保持する。
+pub fn to_hiragana(input: &str) -> String {
+    input
+        .chars()
+        .map(|ch| {
+            let cp = ch as u32;
+            if (0x30A1..=0x30F6).contains(&cp) {
+                char::from_u32(cp - 0x60).unwrap_or(ch)
+            } else {
+                ch
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,4 +95,12 @@ mod tests {
     fn vu_katakana() {
         assert_eq!(to_katakana("ゔ"), "ヴ");
     }
+
+    // === カタカナ→ひらがな ===
+
+    #[test]
+    fn katakana_to_hiragana() {
+        assert_eq!(to_hiragana("ラーメンヴ"), "らーめんゔ");
+        assert_eq!(to_hiragana("漢字カナabc"), "漢字かなabc");
+    }
 }
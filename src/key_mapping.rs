@@ -4,20 +4,30 @@
 //! ConversionEngine に渡す EngineCommand に変換する。
 //! プラットフォーム非依存のため、どの OS でもテスト可能。
 
-use crate::engine::EngineCommand;
+use crate::engine::{EngineCommand, EngineOptions, InputStyle};
 
 // === 仮想キーコード定数 ===
 
 pub const VK_BACK: u16 = 0x08;
+pub const VK_TAB: u16 = 0x09;
 pub const VK_RETURN: u16 = 0x0D;
 pub const VK_SHIFT: u16 = 0x10;
 pub const VK_CONTROL: u16 = 0x11;
 pub const VK_MENU: u16 = 0x12; // Alt
+pub const VK_KANJI: u16 = 0x19; // 半角/全角 (IME 有効時)
 pub const VK_ESCAPE: u16 = 0x1B;
 pub const VK_SPACE: u16 = 0x20;
+pub const VK_PRIOR: u16 = 0x21; // PageUp
+pub const VK_NEXT: u16 = 0x22; // PageDown
+pub const VK_END: u16 = 0x23;
+pub const VK_HOME: u16 = 0x24;
+pub const VK_LEFT: u16 = 0x25;
 pub const VK_UP: u16 = 0x26;
+pub const VK_RIGHT: u16 = 0x27;
 pub const VK_DOWN: u16 = 0x28;
+pub const VK_DELETE: u16 = 0x2E;
 pub const VK_0: u16 = 0x30;
+pub const VK_8: u16 = 0x38;
 pub const VK_9: u16 = 0x39;
 pub const VK_A: u16 = 0x41;
 pub const VK_G: u16 = 0x47;
@@ -27,10 +37,23 @@ pub const VK_M: u16 = 0x4D;
 pub const VK_N: u16 = 0x4E;
 pub const VK_P: u16 = 0x50;
 pub const VK_Z: u16 = 0x5A;
+pub const VK_NUMPAD0: u16 = 0x60;
+pub const VK_NUMPAD9: u16 = 0x69;
+pub const VK_MULTIPLY: u16 = 0x6A; // テンキー *
+pub const VK_ADD: u16 = 0x6B; // テンキー +
+pub const VK_SUBTRACT: u16 = 0x6D; // テンキー -
+pub const VK_DECIMAL: u16 = 0x6E; // テンキー .
+pub const VK_DIVIDE: u16 = 0x6F; // テンキー /
 pub const VK_F1: u16 = 0x70;
+pub const VK_OEM_1: u16 = 0xBA; // : (日本語配列)
+pub const VK_OEM_PLUS: u16 = 0xBB; // ; (日本語配列)
 pub const VK_OEM_COMMA: u16 = 0xBC;
 pub const VK_OEM_MINUS: u16 = 0xBD;
 pub const VK_OEM_PERIOD: u16 = 0xBE;
+pub const VK_OEM_2: u16 = 0xBF; // /
+pub const VK_OEM_3: u16 = 0xC0; // ` (US 配列) / @ (日本語配列)
+pub const VK_OEM_AUTO: u16 = 0xF3; // 半角/全角 (日本語配列)
+pub const VK_OEM_ENLW: u16 = 0xF4; // 半角/全角 (日本語配列)
 
 /// 修飾キーの状態。
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -82,6 +105,34 @@ impl Modifiers {
     }
 }
 
+// === IME のオン/オフ ===
+
+/// IME のオン/オフ切り替えキー。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToggleKey {
+    /// 半角/全角
+    ZenkakuHankaku,
+    /// Ctrl+Space
+    CtrlSpace,
+    /// Alt+` (Alt+~)
+    AltTilde,
+}
+
+impl ToggleKey {
+    /// キーが切り替えキーかどうか。
+    pub fn matches(&self, vk: u16, modifiers: &Modifiers) -> bool {
+        match self {
+            ToggleKey::ZenkakuHankaku => {
+                matches!(vk, VK_KANJI | VK_OEM_AUTO | VK_OEM_ENLW) && !modifiers.ctrl
+            }
+            ToggleKey::CtrlSpace => {
+                vk == VK_SPACE && modifiers.ctrl && !modifiers.shift && !modifiers.alt
+            }
+            ToggleKey::AltTilde => vk == VK_OEM_3 && modifiers.alt && !modifiers.ctrl,
+        }
+    }
+}
+
 // === キーバインドプリセット ===
 
 /// キーバインドプリセット。
@@ -99,6 +150,8 @@ pub enum KeybindPreset {
 /// 各フィールドが None の場合、そのキーは OS に処理を委ねる。
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CtrlKeyConfig {
+    /// Ctrl+Backspace。どのプリセットでも MS-IME と同じく確定の取り消し。
+    pub ctrl_backspace: Option<EngineCommand>,
     pub ctrl_g: Option<EngineCommand>,
     pub ctrl_h: Option<EngineCommand>,
     pub ctrl_j: Option<EngineCommand>,
@@ -112,6 +165,7 @@ impl CtrlKeyConfig {
     pub fn from_preset(preset: &KeybindPreset) -> Self {
         match preset {
             KeybindPreset::None => Self {
+                ctrl_backspace: Some(EngineCommand::UndoCommit),
                 ctrl_g: None,
                 ctrl_h: None,
                 ctrl_j: None,
@@ -120,6 +174,7 @@ impl CtrlKeyConfig {
                 ctrl_p: None,
             },
             KeybindPreset::Minimal => Self {
+                ctrl_backspace: Some(EngineCommand::UndoCommit),
                 ctrl_g: Some(EngineCommand::Cancel),
                 ctrl_h: None,
                 ctrl_j: Some(EngineCommand::Commit),
@@ -128,6 +183,7 @@ impl CtrlKeyConfig {
                 ctrl_p: None,
             },
             KeybindPreset::Emacs => Self {
+                ctrl_backspace: Some(EngineCommand::UndoCommit),
                 ctrl_g: Some(EngineCommand::Cancel),
                 ctrl_h: Some(EngineCommand::Backspace),
                 ctrl_j: Some(EngineCommand::Commit),
@@ -150,15 +206,23 @@ impl Default for CtrlKeyConfig {
 /// - `vk`: Windows 仮想キーコード
 /// - `modifiers`: 修飾キーの状態
 /// - `ime_on`: IME がオンかどうか
+/// - `toggle_key`: IME のオン/オフ切り替えキー
 /// - `ctrl_config`: Ctrl+キーの割り当て設定
 ///
 /// 戻り値: 対応する EngineCommand。処理しないキーの場合は None。
+/// 切り替えキーは IME がオフでも `ToggleIme` を返す。
+/// 記号キーはキーボード配列によって文字が異なるので扱わない（`map_text` を使う）。
 pub fn map_key(
     vk: u16,
     modifiers: &Modifiers,
     ime_on: bool,
+    toggle_key: &ToggleKey,
     ctrl_config: &CtrlKeyConfig,
 ) -> Option<EngineCommand> {
+    if toggle_key.matches(vk, modifiers) {
+        return Some(EngineCommand::ToggleIme);
+    }
+
     if !ime_on {
         return None;
     }
@@ -190,15 +254,55 @@ pub fn map_key(
             let ch = (b'0' + (vk - VK_0) as u8) as char;
             Some(EngineCommand::InsertChar(ch))
         }
+        VK_NUMPAD0..=VK_NUMPAD9 => {
+            let ch = (b'0' + (vk - VK_NUMPAD0) as u8) as char;
+            Some(EngineCommand::InsertChar(ch))
+        }
+        VK_MULTIPLY => Some(EngineCommand::InsertChar('*')),
+        VK_ADD => Some(EngineCommand::InsertChar('+')),
+        VK_SUBTRACT => Some(EngineCommand::InsertChar('-')),
+        VK_DECIMAL => Some(EngineCommand::InsertChar('.')),
+        VK_DIVIDE => Some(EngineCommand::InsertChar('/')),
         VK_SPACE => Some(EngineCommand::Convert),
         VK_RETURN => Some(EngineCommand::Commit),
         VK_ESCAPE => Some(EngineCommand::Cancel),
         VK_BACK => Some(EngineCommand::Backspace),
+        VK_TAB => Some(EngineCommand::Predict),
         VK_DOWN => Some(EngineCommand::NextCandidate),
         VK_UP => Some(EngineCommand::PrevCandidate),
-        VK_OEM_MINUS => Some(EngineCommand::InsertChar('-')),
-        VK_OEM_PERIOD => Some(EngineCommand::InsertChar('.')),
-        VK_OEM_COMMA => Some(EngineCommand::InsertChar(',')),
+        VK_LEFT if modifiers.shift => Some(EngineCommand::ShrinkSegment),
+        VK_RIGHT if modifiers.shift => Some(EngineCommand::ExtendSegment),
+        VK_LEFT => Some(EngineCommand::MoveLeft),
+        VK_RIGHT => Some(EngineCommand::MoveRight),
+        VK_HOME => Some(EngineCommand::MoveToStart),
+        VK_END => Some(EngineCommand::MoveToEnd),
+        VK_DELETE => Some(EngineCommand::Delete),
+        VK_NEXT => Some(EngineCommand::NextPage),
+        VK_PRIOR => Some(EngineCommand::PrevPage),
+        _ => None,
+    }
+}
+
+/// キーボード配列で入力される文字を EngineCommand に変換する。
+///
+/// 記号キーの文字は配列（日本語配列・US 配列など）によって異なるため、
+/// フロントエンドが配列から求めた文字を渡す。計算式・スニペットの読みなどに使う記号だけを
+/// 処理し、それ以外はアプリケーションに委ねる。
+/// `/` は SKK 方式か、abbrev モードのキーにしているときだけ処理する。
+pub fn map_text(
+    ch: char,
+    modifiers: &Modifiers,
+    ime_on: bool,
+    options: &EngineOptions,
+) -> Option<EngineCommand> {
+    if !ime_on || modifiers.ctrl || modifiers.alt {
+        return None;
+    }
+    match ch {
+        '-' | '.' | ',' | '(' | ')' | '=' | '*' | '+' | '@' => Some(EngineCommand::InsertChar(ch)),
+        '/' if options.input_style == InputStyle::Skk || options.abbrev_key == Some('/') => {
+            Some(EngineCommand::InsertChar(ch))
+        }
         _ => None,
     }
 }
@@ -206,6 +310,7 @@ pub fn map_key(
 /// Ctrl+キーを設定に基づいて EngineCommand に変換する。
 fn map_ctrl_key(vk: u16, config: &CtrlKeyConfig) -> Option<EngineCommand> {
     match vk {
+        VK_BACK => config.ctrl_backspace.clone(),
         VK_G => config.ctrl_g.clone(),
         VK_H => config.ctrl_h.clone(),
         VK_J => config.ctrl_j.clone(),
@@ -221,6 +326,8 @@ mod tests {
     use super::*;
     use crate::engine::EngineCommand;
 
+    const TOGGLE: ToggleKey = ToggleKey::ZenkakuHankaku;
+
     // === プリセット ===
 
     #[test]
@@ -269,42 +376,42 @@ mod tests {
     #[test]
     fn emacs_ctrl_j_commits() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Commit));
     }
 
     #[test]
     fn emacs_ctrl_g_cancels() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_G, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_G, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Cancel));
     }
 
     #[test]
     fn emacs_ctrl_n_next_candidate() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_N, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_N, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::NextCandidate));
     }
 
     #[test]
     fn emacs_ctrl_p_prev_candidate() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_P, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_P, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::PrevCandidate));
     }
 
     #[test]
     fn emacs_ctrl_h_backspace() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_H, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_H, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Backspace));
     }
 
     #[test]
     fn emacs_ctrl_m_commits() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_M, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_M, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Commit));
     }
 
@@ -313,28 +420,28 @@ mod tests {
     #[test]
     fn minimal_ctrl_j_commits() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Minimal);
-        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Commit));
     }
 
     #[test]
     fn minimal_ctrl_n_returns_none() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Minimal);
-        let cmd = map_key(VK_N, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_N, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
     #[test]
     fn minimal_ctrl_p_returns_none() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Minimal);
-        let cmd = map_key(VK_P, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_P, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
     #[test]
     fn minimal_ctrl_h_returns_none() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Minimal);
-        let cmd = map_key(VK_H, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_H, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -343,7 +450,7 @@ mod tests {
     #[test]
     fn none_preset_ctrl_j_returns_none() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::None);
-        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -352,14 +459,14 @@ mod tests {
     #[test]
     fn ctrl_other_returns_none() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_A, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_A, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
     #[test]
     fn ctrl_alt_returns_none() {
         let config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
-        let cmd = map_key(VK_J, &Modifiers::ctrl_alt(), true, &config);
+        let cmd = map_key(VK_J, &Modifiers::ctrl_alt(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -369,10 +476,10 @@ mod tests {
     fn emacs_override_ctrl_n_none() {
         let mut config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
         config.ctrl_n = None;
-        let cmd = map_key(VK_N, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_N, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
         // 他のキーは影響なし
-        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Commit));
     }
 
@@ -380,10 +487,10 @@ mod tests {
     fn none_override_ctrl_j_commit() {
         let mut config = CtrlKeyConfig::from_preset(&KeybindPreset::None);
         config.ctrl_j = Some(EngineCommand::Commit);
-        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_J, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Commit));
         // 他は引き続き無効
-        let cmd = map_key(VK_G, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_G, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -391,7 +498,7 @@ mod tests {
     fn minimal_override_ctrl_h_backspace() {
         let mut config = CtrlKeyConfig::from_preset(&KeybindPreset::Minimal);
         config.ctrl_h = Some(EngineCommand::Backspace);
-        let cmd = map_key(VK_H, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_H, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Backspace));
     }
 
@@ -400,7 +507,7 @@ mod tests {
     #[test]
     fn alphabet_key_lowercase() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_A, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_A, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::InsertChar('a')));
     }
 
@@ -408,7 +515,7 @@ mod tests {
     fn alphabet_key_all_letters() {
         let config = CtrlKeyConfig::default();
         for vk in VK_A..=VK_Z {
-            let cmd = map_key(vk, &Modifiers::none(), true, &config);
+            let cmd = map_key(vk, &Modifiers::none(), true, &TOGGLE, &config);
             let expected_char = (b'a' + (vk - VK_A) as u8) as char;
             assert_eq!(cmd, Some(EngineCommand::InsertChar(expected_char)));
         }
@@ -419,58 +526,158 @@ mod tests {
     #[test]
     fn space_key_converts() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_SPACE, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_SPACE, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Convert));
     }
 
     #[test]
     fn enter_key_commits() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_RETURN, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_RETURN, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Commit));
     }
 
     #[test]
     fn escape_key_cancels() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_ESCAPE, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_ESCAPE, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Cancel));
     }
 
     #[test]
     fn backspace_key() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_BACK, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_BACK, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::Backspace));
     }
 
     #[test]
     fn down_arrow_next_candidate() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_DOWN, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_DOWN, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::NextCandidate));
     }
 
     #[test]
     fn up_arrow_prev_candidate() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_UP, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_UP, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::PrevCandidate));
     }
 
+    #[test]
+    fn arrow_keys_move_and_resize_segments() {
+        let config = CtrlKeyConfig::default();
+        let cases = [
+            (VK_LEFT, Modifiers::none(), EngineCommand::MoveLeft),
+            (VK_RIGHT, Modifiers::none(), EngineCommand::MoveRight),
+            (VK_LEFT, Modifiers::shift(), EngineCommand::ShrinkSegment),
+            (VK_RIGHT, Modifiers::shift(), EngineCommand::ExtendSegment),
+        ];
+        for (vk, modifiers, expected) in cases {
+            assert_eq!(
+                map_key(vk, &modifiers, true, &TOGGLE, &config),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn editing_keys() {
+        let config = CtrlKeyConfig::default();
+        let cases = [
+            (VK_HOME, EngineCommand::MoveToStart),
+            (VK_END, EngineCommand::MoveToEnd),
+            (VK_DELETE, EngineCommand::Delete),
+        ];
+        for (vk, expected) in cases {
+            assert_eq!(
+                map_key(vk, &Modifiers::none(), true, &TOGGLE, &config),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn ctrl_backspace_undoes_commit() {
+        let config = CtrlKeyConfig::default();
+        let cmd = map_key(VK_BACK, &Modifiers::ctrl(), true, &TOGGLE, &config);
+        assert_eq!(cmd, Some(EngineCommand::UndoCommit));
+    }
+
+    #[test]
+    fn ctrl_backspace_can_be_disabled() {
+        let mut config = CtrlKeyConfig::from_preset(&KeybindPreset::Emacs);
+        config.ctrl_backspace = None;
+        let cmd = map_key(VK_BACK, &Modifiers::ctrl(), true, &TOGGLE, &config);
+        assert_eq!(cmd, None);
+    }
+
+    #[test]
+    fn tab_starts_prediction() {
+        let config = CtrlKeyConfig::default();
+        let cmd = map_key(VK_TAB, &Modifiers::none(), true, &TOGGLE, &config);
+        assert_eq!(cmd, Some(EngineCommand::Predict));
+    }
+
+    #[test]
+    fn page_keys_map_to_page_commands() {
+        let config = CtrlKeyConfig::default();
+        let down = map_key(VK_NEXT, &Modifiers::none(), true, &TOGGLE, &config);
+        assert_eq!(down, Some(EngineCommand::NextPage));
+        let up = map_key(VK_PRIOR, &Modifiers::none(), true, &TOGGLE, &config);
+        assert_eq!(up, Some(EngineCommand::PrevPage));
+    }
+
     // === IME オフ ===
 
+    #[test]
+    fn zenkaku_hankaku_toggles_ime() {
+        let config = CtrlKeyConfig::default();
+        for vk in [VK_KANJI, VK_OEM_AUTO, VK_OEM_ENLW] {
+            for ime_on in [true, false] {
+                let cmd = map_key(vk, &Modifiers::none(), ime_on, &TOGGLE, &config);
+                assert_eq!(cmd, Some(EngineCommand::ToggleIme));
+            }
+        }
+    }
+
+    #[test]
+    fn ctrl_space_toggles_ime() {
+        let config = CtrlKeyConfig::default();
+        let toggle = ToggleKey::CtrlSpace;
+        let cmd = map_key(VK_SPACE, &Modifiers::ctrl(), false, &toggle, &config);
+        assert_eq!(cmd, Some(EngineCommand::ToggleIme));
+        // Space だけなら変換
+        let cmd = map_key(VK_SPACE, &Modifiers::none(), true, &toggle, &config);
+        assert_eq!(cmd, Some(EngineCommand::Convert));
+        // 設定されていない切り替えキーは処理しない
+        let cmd = map_key(VK_KANJI, &Modifiers::none(), true, &toggle, &config);
+        assert_eq!(cmd, None);
+    }
+
+    #[test]
+    fn alt_tilde_toggles_ime() {
+        let config = CtrlKeyConfig::default();
+        let toggle = ToggleKey::AltTilde;
+        let cmd = map_key(VK_OEM_3, &Modifiers::alt(), false, &toggle, &config);
+        assert_eq!(cmd, Some(EngineCommand::ToggleIme));
+        // Alt なしは切り替えない（入力される文字は map_text で扱う）
+        let cmd = map_key(VK_OEM_3, &Modifiers::none(), true, &toggle, &config);
+        assert_eq!(cmd, None);
+    }
+
     #[test]
     fn ime_off_returns_none() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_A, &Modifiers::none(), false, &config);
+        let cmd = map_key(VK_A, &Modifiers::none(), false, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
     #[test]
     fn ime_off_space_returns_none() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_SPACE, &Modifiers::none(), false, &config);
+        let cmd = map_key(VK_SPACE, &Modifiers::none(), false, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -480,21 +687,21 @@ mod tests {
     fn ctrl_key_with_default_returns_none() {
         // デフォルト (None プリセット) では Ctrl+A は None
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_A, &Modifiers::ctrl(), true, &config);
+        let cmd = map_key(VK_A, &Modifiers::ctrl(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
     #[test]
     fn alt_key_returns_none() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_A, &Modifiers::alt(), true, &config);
+        let cmd = map_key(VK_A, &Modifiers::alt(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
     #[test]
     fn shift_alphabet_uppercase() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_A, &Modifiers::shift(), true, &config);
+        let cmd = map_key(VK_A, &Modifiers::shift(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::InsertChar('A')));
     }
 
@@ -509,7 +716,7 @@ mod tests {
             ctrl: true,
             alt: false,
         };
-        let cmd = map_key(VK_N, &mods, true, &config);
+        let cmd = map_key(VK_N, &mods, true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -522,7 +729,7 @@ mod tests {
             ctrl: true,
             alt: false,
         };
-        let cmd = map_key(VK_J, &mods, true, &config);
+        let cmd = map_key(VK_J, &mods, true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -534,7 +741,7 @@ mod tests {
             ctrl: true,
             alt: false,
         };
-        let cmd = map_key(VK_P, &mods, true, &config);
+        let cmd = map_key(VK_P, &mods, true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -543,7 +750,7 @@ mod tests {
     #[test]
     fn function_keys_return_none() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_F1, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_F1, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
@@ -552,14 +759,14 @@ mod tests {
     #[test]
     fn number_key_0() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_0, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_0, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::InsertChar('0')));
     }
 
     #[test]
     fn number_key_9() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_9, &Modifiers::none(), true, &config);
+        let cmd = map_key(VK_9, &Modifiers::none(), true, &TOGGLE, &config);
         assert_eq!(cmd, Some(EngineCommand::InsertChar('9')));
     }
 
@@ -567,7 +774,7 @@ mod tests {
     fn number_keys_all() {
         let config = CtrlKeyConfig::default();
         for vk in VK_0..=VK_9 {
-            let cmd = map_key(vk, &Modifiers::none(), true, &config);
+            let cmd = map_key(vk, &Modifiers::none(), true, &TOGGLE, &config);
             let expected_char = (b'0' + (vk - VK_0) as u8) as char;
             assert_eq!(cmd, Some(EngineCommand::InsertChar(expected_char)));
         }
@@ -577,30 +784,89 @@ mod tests {
     fn number_key_with_shift_returns_none() {
         // Shift+数字はシステムに処理を委ねる（! @ # 等）
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_0, &Modifiers::shift(), true, &config);
+        let cmd = map_key(VK_0, &Modifiers::shift(), true, &TOGGLE, &config);
         assert_eq!(cmd, None);
     }
 
-    // === 句読点 ===
+    // === 計算式の記号 ===
 
     #[test]
-    fn minus_key_inserts_minus() {
+    fn numpad_keys_insert_digits_and_operators() {
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_OEM_MINUS, &Modifiers::none(), true, &config);
-        assert_eq!(cmd, Some(EngineCommand::InsertChar('-')));
+        let expected = [
+            (VK_NUMPAD0, '0'),
+            (VK_NUMPAD9, '9'),
+            (VK_MULTIPLY, '*'),
+            (VK_ADD, '+'),
+            (VK_SUBTRACT, '-'),
+            (VK_DECIMAL, '.'),
+            (VK_DIVIDE, '/'),
+        ];
+        for (vk, ch) in expected {
+            let cmd = map_key(vk, &Modifiers::none(), true, &TOGGLE, &config);
+            assert_eq!(cmd, Some(EngineCommand::InsertChar(ch)), "vk={vk:#x}");
+        }
     }
 
     #[test]
-    fn period_key_inserts_period() {
+    fn symbol_keys_are_left_to_map_text() {
+        // 記号キーの文字は配列によって違うので、仮想キーコードでは扱わない
         let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_OEM_PERIOD, &Modifiers::none(), true, &config);
-        assert_eq!(cmd, Some(EngineCommand::InsertChar('.')));
+        for vk in [VK_OEM_MINUS, VK_OEM_1, VK_OEM_PLUS, VK_OEM_2, VK_OEM_3] {
+            for mods in [Modifiers::none(), Modifiers::shift()] {
+                let cmd = map_key(vk, &mods, true, &TOGGLE, &config);
+                assert_eq!(cmd, None, "vk={vk:#x}");
+            }
+        }
+        let cmd = map_key(VK_8, &Modifiers::shift(), true, &TOGGLE, &config);
+        assert_eq!(cmd, None);
     }
 
+    // === 配列から求めた文字 ===
+
     #[test]
-    fn comma_key_inserts_comma() {
-        let config = CtrlKeyConfig::default();
-        let cmd = map_key(VK_OEM_COMMA, &Modifiers::none(), true, &config);
-        assert_eq!(cmd, Some(EngineCommand::InsertChar(',')));
+    fn text_symbols_for_expressions_and_snippets() {
+        let options = EngineOptions::default();
+        for ch in ['-', '.', ',', '(', ')', '=', '*', '+', '@'] {
+            let cmd = map_text(ch, &Modifiers::shift(), true, &options);
+            assert_eq!(cmd, Some(EngineCommand::InsertChar(ch)), "ch={ch}");
+        }
+    }
+
+    #[test]
+    fn other_text_is_left_to_application() {
+        // US 配列の Shift+- (_) や : ; などは IME で扱わない
+        let options = EngineOptions::default();
+        for ch in ['_', ':', ';', '!', '`', '~'] {
+            assert_eq!(map_text(ch, &Modifiers::none(), true, &options), None);
+        }
+    }
+
+    #[test]
+    fn text_is_ignored_when_off_or_with_ctrl_alt() {
+        let options = EngineOptions::default();
+        assert_eq!(map_text('-', &Modifiers::none(), false, &options), None);
+        assert_eq!(map_text('-', &Modifiers::ctrl(), true, &options), None);
+        assert_eq!(map_text('-', &Modifiers::alt(), true, &options), None);
+    }
+
+    #[test]
+    fn slash_is_handled_in_skk_or_as_abbrev_key() {
+        let skk = EngineOptions {
+            input_style: InputStyle::Skk,
+            ..EngineOptions::default()
+        };
+        let cmd = map_text('/', &Modifiers::none(), true, &skk);
+        assert_eq!(cmd, Some(EngineCommand::InsertChar('/')));
+        // 通常の入力方式ではアプリケーションに渡す
+        let cmd = map_text('/', &Modifiers::none(), true, &EngineOptions::default());
+        assert_eq!(cmd, None);
+        // abbrev モードのキーにしていれば処理する
+        let abbrev = EngineOptions {
+            abbrev_key: Some('/'),
+            ..EngineOptions::default()
+        };
+        let cmd = map_text('/', &Modifiers::none(), true, &abbrev);
+        assert_eq!(cmd, Some(EngineCommand::InsertChar('/')));
     }
 }
@@ -1,17 +1,29 @@
+pub mod calculator;
 pub mod candidate;
 pub mod config;
+pub mod context_priority;
+pub mod date_provider;
 pub mod dictionary;
 pub mod engine;
 pub mod guids;
 pub mod input_state;
 pub mod katakana;
 pub mod key_mapping;
+pub mod provider;
+pub mod reverse_index;
 pub mod romaji;
+pub mod segmenter;
+pub mod snippet;
+pub mod symbol_dict;
+pub mod typo_correction;
+pub mod user_dict_format;
 pub mod user_dictionary;
 
 #[cfg(windows)]
 pub mod class_factory;
 #[cfg(windows)]
+pub mod display_attribute;
+#[cfg(windows)]
 pub mod registry;
 #[cfg(windows)]
 pub mod text_service;
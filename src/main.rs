@@ -1,6 +1,11 @@
+use japinput::config::Config;
+use japinput::date_provider::DateProvider;
 use japinput::dictionary::Dictionary;
 use japinput::engine::{ConversionEngine, EngineCommand};
 use japinput::katakana;
+use japinput::provider::MergeRule;
+use japinput::snippet::{SNIPPET_PRIORITY, SnippetDictionary};
+use japinput::user_dict_format::{self, UserDictFormat};
 use japinput::user_dictionary::UserDictionary;
 use std::io::{self, BufRead, Write};
 use std::path::Path;
@@ -8,6 +13,15 @@ use std::path::Path;
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
+    // userdict サブコマンド: 他の IME のユーザー辞書のインポート/エクスポート
+    if args.get(1).map(|s| s.as_str()) == Some("userdict") {
+        if let Err(e) = run_userdict(&args[2..]) {
+            eprintln!("エラー: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // --dict オプションで辞書ファイルを指定
     let dict = if let Some(pos) = args.iter().position(|a| a == "--dict") {
         let Some(path) = args.get(pos + 1) else {
@@ -49,8 +63,66 @@ fn main() {
         None
     };
 
+    // --config オプションで設定ファイルを指定（省略時はデフォルト設定）
+    let config = match args
+        .iter()
+        .position(|a| a == "--config")
+        .and_then(|pos| args.get(pos + 1))
+    {
+        Some(path) => match Config::load(Path::new(path)) {
+            Ok(config) => {
+                eprintln!("設定を読み込みました: {path}");
+                config
+            }
+            Err(e) => {
+                eprintln!("設定の読み込みに失敗: {e}");
+                Config::default_config()
+            }
+        },
+        None => Config::default_config(),
+    };
+
     let has_dict = dict.is_some();
     let mut engine = ConversionEngine::new_with_user_dict(dict, user_dict);
+    config.configure_engine(&mut engine);
+
+    // --snippets オプションでスニペットのファイルを指定
+    if let Some(path) = args
+        .iter()
+        .position(|a| a == "--snippets")
+        .and_then(|pos| args.get(pos + 1))
+    {
+        match SnippetDictionary::load_from_file(Path::new(path)) {
+            Ok(snippets) => {
+                eprintln!("スニペットを読み込みました: {path} ({}件)", snippets.len());
+                let dates = DateProvider::new(config.date_formats.clone());
+                engine.add_provider(
+                    Box::new(snippets.with_dates(dates)),
+                    MergeRule::new(SNIPPET_PRIORITY),
+                );
+            }
+            Err(e) => eprintln!("スニペットの読み込みに失敗: {e}"),
+        }
+    }
+
+    // --reconvert オプション: 確定済みのテキストを再変換して候補を表示する
+    if let Some(text) = args
+        .iter()
+        .position(|a| a == "--reconvert")
+        .and_then(|pos| args.get(pos + 1))
+    {
+        let output = engine.reconvert(text);
+        if output.segments.is_empty() {
+            println!("読みが見つかりません: {text}");
+            return;
+        }
+        println!("読み: {}", engine.reading());
+        for seg in engine.segments() {
+            let candidates: Vec<&str> = seg.candidates().iter().map(|c| c.text.as_str()).collect();
+            println!("  {}: {}", seg.reading(), candidates.join(" / "));
+        }
+        return;
+    }
 
     println!("japinput - ローマ字→かな変換デモ");
     if has_dict {
@@ -90,19 +162,36 @@ fn main() {
                 let _ = writeln!(stdout, "  ひらがな: {hiragana}");
                 let _ = writeln!(stdout, "  カタカナ: {katakana_display}");
 
+                if engine.segments().len() > 1 {
+                    let readings: Vec<&str> =
+                        engine.segments().iter().map(|seg| seg.reading()).collect();
+                    let _ = writeln!(stdout, "  文節: {}", readings.join(" / "));
+                }
+
                 let candidates = engine.candidates().unwrap();
                 let _ = writeln!(
                     stdout,
                     "  変換候補: {}",
                     candidates
                         .iter()
-                        .map(|s| s.as_str())
+                        .map(|c| match &c.annotation {
+                            Some(a) => format!("{} ({a})", c.text),
+                            None => c.text.clone(),
+                        })
                         .collect::<Vec<_>>()
                         .join(" / ")
                 );
 
                 let commit_output = engine.process(EngineCommand::Commit);
                 let _ = writeln!(stdout, "  確定: {}", commit_output.committed);
+                if !commit_output.predictions.is_empty() {
+                    let next: Vec<&str> = commit_output
+                        .predictions
+                        .iter()
+                        .map(|c| c.text.as_str())
+                        .collect();
+                    let _ = writeln!(stdout, "  次の語の候補: {}", next.join(" / "));
+                }
             } else {
                 // 候補なし: Convert がひらがなを自動確定
                 let hiragana = &output.committed;
@@ -133,3 +222,75 @@ fn main() {
         }
     }
 }
+
+/// `userdict import|export --format <形式> --user-dict <ユーザー辞書> [--config <設定ファイル>] <ファイル>` を実行する。
+///
+/// ユーザー辞書の上限は IME と同じく設定ファイルの値を使う。
+fn run_userdict(args: &[String]) -> Result<(), String> {
+    const USAGE: &str = "使い方: japinput userdict <import|export> --format <msime|google|atok> --user-dict <ユーザー辞書> [--config <設定ファイル>] <ファイル>";
+
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|pos| args.get(pos + 1))
+            .map(|s| s.as_str())
+    };
+    let action = args.first().map(|s| s.as_str()).ok_or(USAGE)?;
+    let format_name = option("--format").ok_or(USAGE)?;
+    let format = UserDictFormat::from_name(format_name)
+        .ok_or_else(|| format!("不明な形式: {format_name} (msime, google, atok のいずれか)"))?;
+    let user_dict_path = Path::new(option("--user-dict").ok_or(USAGE)?);
+    // オプションとその値以外の最初の引数をファイルとみなす
+    let file = args
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find(|(i, a)| !a.starts_with("--") && !args[i - 1].starts_with("--"))
+        .map(|(_, a)| Path::new(a.as_str()))
+        .ok_or(USAGE)?;
+
+    let config = match option("--config") {
+        Some(path) => Config::load(Path::new(path)).map_err(|e| format!("{path}: {e}"))?,
+        None => Config::default_config(),
+    };
+
+    let mut ud = UserDictionary::load(user_dict_path).map_err(|e| e.to_string())?;
+    config.configure_user_dictionary(&mut ud);
+    match action {
+        "import" => {
+            let bytes = std::fs::read(file).map_err(|e| format!("{}: {e}", file.display()))?;
+            let report = user_dict_format::import(&mut ud, &bytes, format);
+            for skipped in &report.skipped {
+                eprintln!(
+                    "  {} 行目をスキップ: {} ({})",
+                    skipped.line, skipped.reason, skipped.content
+                );
+            }
+            ud.save(user_dict_path).map_err(|e| e.to_string())?;
+            println!(
+                "{} 語を追加、{} 語を更新、{} 行をスキップしました",
+                report.imported,
+                report.updated,
+                report.skipped.len()
+            );
+        }
+        "export" => {
+            let (bytes, skipped) = user_dict_format::export(&ud, format);
+            std::fs::write(file, bytes).map_err(|e| format!("{}: {e}", file.display()))?;
+            for s in &skipped {
+                eprintln!(
+                    "  スキップ: {} ({}\t{})",
+                    s.reason, s.word.reading, s.word.word
+                );
+            }
+            println!(
+                "{} 語を書き出し、{} 語をスキップしました: {}",
+                ud.words().len() - skipped.len(),
+                skipped.len(),
+                file.display()
+            );
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! 変換候補の提供元。
+//!
+//! 読みから候補を返す `CandidateProvider` と、複数の提供元の候補を
+//! 優先度・件数の上限に従ってマージする処理を提供する。
+//! システム辞書・ユーザー辞書も提供元の1つとして扱う。
+
+use crate::candidate::{Candidate, CandidateSource};
+use crate::dictionary::Dictionary;
+use crate::user_dictionary::UserDictionary;
+
+/// ユーザー辞書の優先度。
+pub const USER_DICTIONARY_PRIORITY: i32 = 200;
+
+/// システム辞書の優先度。
+pub const SYSTEM_DICTIONARY_PRIORITY: i32 = 100;
+
+/// 変換候補の提供元。
+pub trait CandidateProvider: Send {
+    /// 提供元の名前。
+    fn name(&self) -> &str;
+
+    /// 読みから候補を返す。候補がなければ空。
+    fn lookup(&self, reading: &str) -> Vec<Candidate>;
+}
+
+/// 提供元の候補をマージするときの規則。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeRule {
+    /// 優先度。大きい提供元の候補ほど先頭に並ぶ。同じ値なら登録順。
+    pub priority: i32,
+    /// 1回の検索で採用する候補数の上限（None は無制限）
+    pub max_count: Option<usize>,
+}
+
+impl MergeRule {
+    /// 件数の上限なしの規則を作成する。
+    pub fn new(priority: i32) -> Self {
+        Self {
+            priority,
+            max_count: None,
+        }
+    }
+
+    /// 件数の上限を設定する。
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+}
+
+/// 複数の提供元の候補をマージする。
+///
+/// 優先度の高い提供元から順に候補を追加し、同じ文字列の候補は先に
+/// 追加したものを残す。残した候補に注釈がなければ後の候補の注釈を使う。
+pub fn merge(sources: &[(&dyn CandidateProvider, MergeRule)], reading: &str) -> Vec<Candidate> {
+    let mut order: Vec<usize> = (0..sources.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sources[i].1.priority));
+
+    let mut merged: Vec<Candidate> = Vec::new();
+    for i in order {
+        let (provider, rule) = sources[i];
+        let mut candidates = provider.lookup(reading);
+        if let Some(max) = rule.max_count {
+            candidates.truncate(max);
+        }
+        for c in candidates {
+            match merged.iter_mut().find(|m| m.text == c.text) {
+                Some(m) => {
+                    if m.annotation.is_none() {
+                        m.annotation = c.annotation;
+                    }
+                }
+                None => merged.push(c),
+            }
+        }
+    }
+    merged
+}
+
+impl CandidateProvider for Dictionary {
+    fn name(&self) -> &str {
+        "system"
+    }
+
+    fn lookup(&self, reading: &str) -> Vec<Candidate> {
+        Dictionary::lookup(self, reading)
+            .unwrap_or_default()
+            .iter()
+            .map(|w| {
+                Candidate::new(w, reading, CandidateSource::SystemDictionary)
+                    .with_annotation(self.annotation(reading, w))
+            })
+            .collect()
+    }
+}
+
+impl CandidateProvider for UserDictionary {
+    fn name(&self) -> &str {
+        "user"
+    }
+
+    fn lookup(&self, reading: &str) -> Vec<Candidate> {
+        UserDictionary::lookup(self, reading)
+            .unwrap_or_default()
+            .iter()
+            .map(|w| {
+                Candidate::new(w, reading, CandidateSource::UserDictionary)
+                    .with_annotation(self.annotation(reading, w))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// 固定の用語集を返すテスト用の提供元。
+    struct Glossary(Vec<(&'static str, &'static str)>);
+
+    impl CandidateProvider for Glossary {
+        fn name(&self) -> &str {
+            "glossary"
+        }
+
+        fn lookup(&self, reading: &str) -> Vec<Candidate> {
+            self.0
+                .iter()
+                .filter(|(r, _)| *r == reading)
+                .map(|(_, w)| {
+                    Candidate::new(w, reading, CandidateSource::Generated)
+                        .with_annotation(Some("用語集"))
+                })
+                .collect()
+        }
+    }
+
+    fn test_dict() -> Dictionary {
+        Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap()
+    }
+
+    #[test]
+    fn dictionary_provider() {
+        let dict = test_dict();
+        let cands = CandidateProvider::lookup(&dict, "にほん");
+        assert_eq!(cands, vec!["日本", "二本"]);
+        assert_eq!(cands[0].source, CandidateSource::SystemDictionary);
+        assert_eq!(cands[0].annotation.as_deref(), Some("country"));
+        assert!(CandidateProvider::lookup(&dict, "ない").is_empty());
+    }
+
+    #[test]
+    fn user_dictionary_provider() {
+        let mut ud = UserDictionary::new();
+        ud.record("かんじ", "感じ");
+        let cands = CandidateProvider::lookup(&ud, "かんじ");
+        assert_eq!(cands, vec!["感じ"]);
+        assert_eq!(cands[0].source, CandidateSource::UserDictionary);
+    }
+
+    #[test]
+    fn merge_by_priority_and_dedup() {
+        let dict = test_dict();
+        let glossary = Glossary(vec![("かんじ", "幹事"), ("かんじ", "監事")]);
+        let sources: Vec<(&dyn CandidateProvider, MergeRule)> = vec![
+            (&dict, MergeRule::new(SYSTEM_DICTIONARY_PRIORITY)),
+            (&glossary, MergeRule::new(150)),
+        ];
+        let merged = merge(&sources, "かんじ");
+        assert_eq!(merged, vec!["幹事", "監事", "漢字", "感じ"]);
+        // 重複した "幹事" は優先度の高い用語集のものを残す
+        assert_eq!(merged[0].source, CandidateSource::Generated);
+    }
+
+    #[test]
+    fn merge_respects_max_count() {
+        let dict = test_dict();
+        let sources: Vec<(&dyn CandidateProvider, MergeRule)> =
+            vec![(&dict, MergeRule::new(0).with_max_count(2))];
+        assert_eq!(merge(&sources, "かんじ"), vec!["漢字", "感じ"]);
+    }
+
+    #[test]
+    fn merge_same_priority_keeps_registration_order() {
+        let a = Glossary(vec![("き", "木")]);
+        let b = Glossary(vec![("き", "気")]);
+        let sources: Vec<(&dyn CandidateProvider, MergeRule)> =
+            vec![(&a, MergeRule::new(0)), (&b, MergeRule::new(0))];
+        assert_eq!(merge(&sources, "き"), vec!["木", "気"]);
+    }
+
+    #[test]
+    fn merge_fills_missing_annotation() {
+        let mut ud = UserDictionary::new();
+        ud.record("にほん", "日本");
+        let dict = test_dict();
+        let sources: Vec<(&dyn CandidateProvider, MergeRule)> = vec![
+            (&ud, MergeRule::new(USER_DICTIONARY_PRIORITY)),
+            (&dict, MergeRule::new(SYSTEM_DICTIONARY_PRIORITY)),
+        ];
+        let merged = merge(&sources, "にほん");
+        assert_eq!(merged[0].source, CandidateSource::UserDictionary);
+        assert_eq!(merged[0].annotation.as_deref(), Some("country"));
+    }
+}
@@ -154,6 +154,8 @@ fn register_categories(clsid: &GUID) -> Result<()> {
 
     unsafe {
         category_mgr.RegisterCategory(clsid, &GUID_TFCAT_TIP_KEYBOARD, clsid)?;
+        // 入力中のテキストの下線を表示属性で描き分ける
+        category_mgr.RegisterCategory(clsid, &GUID_TFCAT_DISPLAYATTRIBUTEPROVIDER, clsid)?;
     }
 
     Ok(())
@@ -188,6 +190,7 @@ fn unregister_categories(clsid: &GUID) -> Result<()> {
 
     unsafe {
         let _ = category_mgr.UnregisterCategory(clsid, &GUID_TFCAT_TIP_KEYBOARD, clsid);
+        let _ = category_mgr.UnregisterCategory(clsid, &GUID_TFCAT_DISPLAYATTRIBUTEPROVIDER, clsid);
     }
 
     Ok(())
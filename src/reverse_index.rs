@@ -0,0 +1,251 @@
+//! 再変換のための逆引き索引。
+//!
+//! 辞書の候補（漢字など）から読みを引く索引を作り、確定済みのテキストを
+//! 辞書の単語・かな・読めない文字の断片に分割して読みを求める。
+
+use std::collections::HashMap;
+
+use crate::dictionary::Dictionary;
+use crate::katakana;
+use crate::user_dictionary::UserDictionary;
+
+/// 読めない文字1文字あたりのコスト。単語・かなより大きくして、読める分割を優先する。
+const UNKNOWN_COST: usize = 10;
+
+/// 候補から読みを引く索引。
+#[derive(Debug, Clone, Default)]
+pub struct ReverseIndex {
+    /// 候補 → 読み（登録順）
+    readings: HashMap<String, Vec<String>>,
+    /// 登録された候補の最大文字数
+    max_word_len: usize,
+}
+
+impl ReverseIndex {
+    /// 空の索引を作成する。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// システム辞書の送りなしエントリから索引を作る。
+    pub fn from_dictionary(dict: &Dictionary) -> Self {
+        let mut index = Self::new();
+        for (reading, words) in dict.lookup_prefix("") {
+            // 送りありの読み (おおきi など) は候補の読みにならない
+            if reading.ends_with(|c: char| c.is_ascii_lowercase()) {
+                continue;
+            }
+            for w in words {
+                index.insert(w, reading);
+            }
+        }
+        index
+    }
+
+    /// ユーザー辞書の送りなしエントリから索引を作る。
+    pub fn from_user_dictionary(ud: &UserDictionary) -> Self {
+        let mut index = Self::new();
+        for (reading, words) in ud.lookup_prefix("") {
+            for w in words {
+                index.insert(w, reading);
+            }
+        }
+        index
+    }
+
+    /// 候補と読みの組を登録する。
+    pub fn insert(&mut self, word: &str, reading: &str) {
+        // かなだけの候補はかなとして読めるので登録しない
+        if word.is_empty() || word.chars().all(is_kana) {
+            return;
+        }
+        let readings = self.readings.entry(word.to_string()).or_default();
+        if !readings.iter().any(|r| r == reading) {
+            readings.push(reading.to_string());
+        }
+        self.max_word_len = self.max_word_len.max(word.chars().count());
+    }
+
+    /// 候補の読みを返す。登録されていなければ空。
+    pub fn readings(&self, word: &str) -> &[String] {
+        self.readings.get(word).map_or(&[], |v| v.as_slice())
+    }
+
+    /// 登録された候補の数を返す。
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    /// 候補が1つも登録されていないかどうか。
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+}
+
+/// 断片の種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PieceKind {
+    /// 索引にある単語
+    Word,
+    /// ひらがな・カタカナ（読みはひらがなにしたもの）
+    Kana,
+    /// 読めない文字（読みは元の文字のまま）
+    Unknown,
+}
+
+/// テキストの断片とその読み。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    pub surface: String,
+    pub reading: String,
+    pub kind: PieceKind,
+}
+
+/// テキストを断片に分割して読みを求める。
+///
+/// 索引の単語（先に渡した索引の読みを優先）とかなで読める分割のうち、
+/// 読めない文字が最も少なく、断片の数が最も少ないものを選ぶ。
+/// 連続するかな・読めない文字はそれぞれ1つの断片にまとめる。
+pub fn split(text: &str, indexes: &[&ReverseIndex]) -> Vec<Piece> {
+    let chars: Vec<char> = text.chars().collect();
+    let n = chars.len();
+    let max_len = indexes.iter().map(|i| i.max_word_len).max().unwrap_or(0);
+
+    // best[j]: 位置 j までの最小コストと、最後の断片 (開始位置, 種類, 読み)
+    let mut best: Vec<Option<(usize, usize, PieceKind, String)>> = vec![None; n + 1];
+    best[0] = Some((0, 0, PieceKind::Unknown, String::new()));
+    for start in 0..n {
+        let Some((cost, ..)) = best[start] else {
+            continue;
+        };
+        let mut relax = |end: usize, step: usize, kind: PieceKind, reading: String| {
+            let total = cost + step;
+            if best[end].as_ref().is_none_or(|b| total < b.0) {
+                best[end] = Some((total, start, kind, reading));
+            }
+        };
+        for end in (start + 1)..=n.min(start + max_len) {
+            let surface: String = chars[start..end].iter().collect();
+            let reading = indexes.iter().find_map(|i| i.readings(&surface).first());
+            if let Some(reading) = reading {
+                relax(end, 1, PieceKind::Word, reading.clone());
+            }
+        }
+        let ch = chars[start].to_string();
+        if is_kana(chars[start]) {
+            relax(start + 1, 1, PieceKind::Kana, katakana::to_hiragana(&ch));
+        } else {
+            relax(start + 1, UNKNOWN_COST, PieceKind::Unknown, ch);
+        }
+    }
+
+    // 末尾から最小コストの分割をたどる
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut end = n;
+    while end > 0 {
+        let Some((_, start, kind, reading)) = best[end].clone() else {
+            break;
+        };
+        pieces.push(Piece {
+            surface: chars[start..end].iter().collect(),
+            reading,
+            kind,
+        });
+        end = start;
+    }
+    pieces.reverse();
+
+    // 連続するかな・読めない文字をまとめる
+    let mut merged: Vec<Piece> = Vec::new();
+    for piece in pieces {
+        match merged.last_mut() {
+            Some(last) if last.kind == piece.kind && piece.kind != PieceKind::Word => {
+                last.surface.push_str(&piece.surface);
+                last.reading.push_str(&piece.reading);
+            }
+            _ => merged.push(piece),
+        }
+    }
+    merged
+}
+
+/// ひらがな・カタカナ・長音記号かどうか。
+fn is_kana(ch: char) -> bool {
+    matches!(ch, '\u{3041}'..='\u{3096}' | '\u{30A1}'..='\u{30F6}' | 'ー')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn test_index() -> ReverseIndex {
+        let dict = Dictionary::load_from_file(Path::new("tests/fixtures/test_dict.txt")).unwrap();
+        ReverseIndex::from_dictionary(&dict)
+    }
+
+    fn surfaces(pieces: &[Piece]) -> Vec<(&str, &str)> {
+        pieces
+            .iter()
+            .map(|p| (p.surface.as_str(), p.reading.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn readings_from_dictionary() {
+        let index = test_index();
+        assert_eq!(index.readings("漢字"), &["かんじ"]);
+        assert_eq!(index.readings("感じ"), &["かんじ"]);
+        assert!(index.readings("ない").is_empty());
+    }
+
+    #[test]
+    fn split_words_and_kana() {
+        let index = test_index();
+        let pieces = split("私の名前は中野です", &[&index]);
+        assert_eq!(
+            surfaces(&pieces),
+            vec![
+                ("私", "わたし"),
+                ("の", "の"),
+                ("名前", "なまえ"),
+                ("は", "は"),
+                ("中野", "なかの"),
+                ("です", "です"),
+            ]
+        );
+        assert_eq!(pieces[0].kind, PieceKind::Word);
+        assert_eq!(pieces[1].kind, PieceKind::Kana);
+    }
+
+    #[test]
+    fn katakana_read_as_hiragana() {
+        let pieces = split("カンジ", &[]);
+        assert_eq!(surfaces(&pieces), vec![("カンジ", "かんじ")]);
+    }
+
+    #[test]
+    fn unknown_characters_are_grouped() {
+        let index = test_index();
+        let pieces = split("𠮷野漢字", &[&index]);
+        assert_eq!(pieces[0].kind, PieceKind::Unknown);
+        assert_eq!(pieces[0].surface, "𠮷野");
+        assert_eq!(pieces[1].reading, "かんじ");
+    }
+
+    #[test]
+    fn earlier_index_takes_priority() {
+        let mut user = ReverseIndex::new();
+        user.insert("日本", "にっぽん");
+        let index = test_index();
+        let pieces = split("日本", &[&user, &index]);
+        assert_eq!(pieces[0].reading, "にっぽん");
+    }
+
+    #[test]
+    fn kana_only_words_are_not_indexed() {
+        let mut index = ReverseIndex::new();
+        index.insert("ひらがな", "ひらがな");
+        assert!(index.is_empty());
+    }
+}
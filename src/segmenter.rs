@@ -0,0 +1,380 @@
+//! 連文節変換の文節区切り。
+//!
+//! 読みの部分文字列を辞書の単語・付属語（助詞・助動詞）・未知語のノードとした
+//! ラティスを作り、ノードのコストと連接コストの合計が最小となる経路を
+//! Viterbi アルゴリズムで求める。得られた単語列は、自立語とそれに続く
+//! 付属語をまとめて文節にする。
+
+/// 1つの単語として扱う読みの最大文字数。
+const MAX_WORD_LEN: usize = 16;
+
+/// 付属語（助詞・助動詞）の読み。
+pub const FUNCTION_WORDS: &[&str] = &[
+    "の",
+    "は",
+    "が",
+    "を",
+    "に",
+    "へ",
+    "と",
+    "で",
+    "も",
+    "や",
+    "か",
+    "ね",
+    "よ",
+    "な",
+    "から",
+    "まで",
+    "より",
+    "には",
+    "では",
+    "とは",
+    "でも",
+    "です",
+    "でした",
+    "ます",
+    "ました",
+    "だ",
+    "だった",
+];
+
+/// ラティスのノードの種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    /// 辞書にある単語
+    Word,
+    /// 付属語
+    FunctionWord,
+    /// 未知語（1文字）
+    Unknown,
+}
+
+impl NodeKind {
+    const ALL: [NodeKind; 3] = [NodeKind::Word, NodeKind::FunctionWord, NodeKind::Unknown];
+
+    fn index(self) -> usize {
+        match self {
+            NodeKind::Word => 0,
+            NodeKind::FunctionWord => 1,
+            NodeKind::Unknown => 2,
+        }
+    }
+
+    fn is_content(self) -> bool {
+        self != NodeKind::FunctionWord
+    }
+}
+
+/// 文節区切りのコストモデル。値が小さいほど選ばれやすい。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostModel {
+    /// 辞書の単語の基本コスト
+    pub word_cost: u32,
+    /// 単語の読み1文字あたりの割引（長い単語ほど選ばれやすくする）
+    pub length_bonus: u32,
+    /// 付属語のコスト
+    pub function_word_cost: u32,
+    /// 未知語1文字あたりのコスト
+    pub unknown_cost: u32,
+    /// 自立語（単語・未知語）同士の連接コスト
+    pub content_to_content: u32,
+    /// 自立語 → 付属語の連接コスト
+    pub content_to_function: u32,
+    /// 付属語 → 自立語の連接コスト
+    pub function_to_content: u32,
+    /// 付属語同士の連接コスト
+    pub function_to_function: u32,
+    /// 文頭が付属語になるコスト
+    pub bos_to_function: u32,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            word_cost: 1000,
+            length_bonus: 100,
+            function_word_cost: 300,
+            unknown_cost: 2000,
+            content_to_content: 200,
+            content_to_function: 0,
+            function_to_content: 100,
+            function_to_function: 50,
+            bos_to_function: 500,
+        }
+    }
+}
+
+impl CostModel {
+    /// ノードのコスト。
+    fn node_cost(&self, kind: NodeKind, len: usize) -> u32 {
+        match kind {
+            NodeKind::Word => self
+                .word_cost
+                .saturating_sub(self.length_bonus.saturating_mul(len as u32)),
+            NodeKind::FunctionWord => self.function_word_cost,
+            NodeKind::Unknown => self.unknown_cost,
+        }
+    }
+
+    /// 連接コスト。`prev` が None のときは文頭。
+    fn connection_cost(&self, prev: Option<NodeKind>, next: NodeKind) -> u32 {
+        match prev {
+            None if next == NodeKind::FunctionWord => self.bos_to_function,
+            None => 0,
+            // 未知語の連続は1つの文節にまとめるためコストをかけない
+            Some(NodeKind::Unknown) if next == NodeKind::Unknown => 0,
+            Some(p) => match (p.is_content(), next.is_content()) {
+                (true, true) => self.content_to_content,
+                (true, false) => self.content_to_function,
+                (false, true) => self.function_to_content,
+                (false, false) => self.function_to_function,
+            },
+        }
+    }
+}
+
+/// 文節。自立語とそれに続く付属語からなる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bunsetsu {
+    /// 自立語の読み
+    pub stem: String,
+    /// 自立語が辞書にあるか
+    pub known: bool,
+    /// 自立語に続く付属語の読み
+    pub suffix: String,
+}
+
+impl Bunsetsu {
+    /// 文節全体の読みを返す。
+    pub fn reading(&self) -> String {
+        format!("{}{}", self.stem, self.suffix)
+    }
+}
+
+/// ラティス上の各位置・ノード種別での最小コストの経路。
+#[derive(Debug, Clone, Copy)]
+struct Best {
+    cost: u32,
+    /// このノードの開始位置
+    start: usize,
+    /// 直前のノードの種類（文頭なら None）
+    prev: Option<NodeKind>,
+}
+
+/// 読みを文節に区切る。`has_word` は読みが辞書にあるかを返す。
+pub fn segment(reading: &str, model: &CostModel, has_word: impl Fn(&str) -> bool) -> Vec<Bunsetsu> {
+    let chars: Vec<char> = reading.chars().collect();
+    let n = chars.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // best[j][kind]: 位置 j で終わる kind のノードまでの最小コスト
+    let mut best: Vec<[Option<Best>; 3]> = vec![[None; 3]; n + 1];
+    for end in 1..=n {
+        for start in end.saturating_sub(MAX_WORD_LEN)..end {
+            let surface: String = chars[start..end].iter().collect();
+            let len = end - start;
+            let kinds = NodeKind::ALL.into_iter().filter(|&kind| match kind {
+                NodeKind::Word => has_word(&surface),
+                NodeKind::FunctionWord => FUNCTION_WORDS.contains(&surface.as_str()),
+                NodeKind::Unknown => len == 1,
+            });
+            for kind in kinds {
+                let node_cost = model.node_cost(kind, len);
+                let prevs: Vec<(Option<NodeKind>, u32)> = if start == 0 {
+                    vec![(None, 0)]
+                } else {
+                    NodeKind::ALL
+                        .into_iter()
+                        .filter_map(|k| best[start][k.index()].map(|b| (Some(k), b.cost)))
+                        .collect()
+                };
+                for (prev, prev_cost) in prevs {
+                    let cost = prev_cost + model.connection_cost(prev, kind) + node_cost;
+                    let slot = &mut best[end][kind.index()];
+                    if slot.is_none_or(|b| cost < b.cost) {
+                        *slot = Some(Best { cost, start, prev });
+                    }
+                }
+            }
+        }
+    }
+
+    // 末尾から最小コストの経路をたどる
+    let mut spans: Vec<(usize, usize, NodeKind)> = Vec::new();
+    let mut end = n;
+    let mut kind = NodeKind::ALL
+        .into_iter()
+        .filter(|k| best[n][k.index()].is_some())
+        .min_by_key(|k| best[n][k.index()].map(|b| b.cost));
+    while let Some(k) = kind {
+        let Some(b) = best[end][k.index()] else {
+            break;
+        };
+        spans.push((b.start, end, k));
+        end = b.start;
+        kind = b.prev;
+    }
+    spans.reverse();
+
+    group_bunsetsu(&chars, &spans)
+}
+
+/// 区切り位置が固定された読みを1つの文節として解析する。
+///
+/// 読み全体が辞書になければ、末尾の付属語を除いた残りを自立語として探す
+/// （長い付属語から順に試す）。どちらもなければ未知語の文節とする。
+pub fn analyze(reading: &str, has_word: impl Fn(&str) -> bool) -> Bunsetsu {
+    if has_word(reading) {
+        return Bunsetsu {
+            stem: reading.to_string(),
+            known: true,
+            suffix: String::new(),
+        };
+    }
+    let mut suffixes: Vec<&str> = FUNCTION_WORDS
+        .iter()
+        .copied()
+        .filter(|fw| reading.len() > fw.len() && reading.ends_with(fw))
+        .collect();
+    suffixes.sort_by_key(|fw| std::cmp::Reverse(fw.len()));
+    for suffix in suffixes {
+        let stem = &reading[..reading.len() - suffix.len()];
+        if has_word(stem) {
+            return Bunsetsu {
+                stem: stem.to_string(),
+                known: true,
+                suffix: suffix.to_string(),
+            };
+        }
+    }
+    Bunsetsu {
+        stem: reading.to_string(),
+        known: false,
+        suffix: String::new(),
+    }
+}
+
+/// 単語列を文節にまとめる。付属語は直前の文節に、連続する未知語は1つの文節にまとめる。
+fn group_bunsetsu(chars: &[char], spans: &[(usize, usize, NodeKind)]) -> Vec<Bunsetsu> {
+    let mut result: Vec<Bunsetsu> = Vec::new();
+    for &(start, end, kind) in spans {
+        let surface: String = chars[start..end].iter().collect();
+        match (kind, result.last_mut()) {
+            (NodeKind::FunctionWord, Some(last)) => last.suffix.push_str(&surface),
+            (NodeKind::Unknown, Some(last)) if !last.known && last.suffix.is_empty() => {
+                last.stem.push_str(&surface);
+            }
+            _ => result.push(Bunsetsu {
+                stem: surface,
+                known: kind == NodeKind::Word,
+                suffix: String::new(),
+            }),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &[&str] = &["わたし", "なまえ", "なかの", "かん", "かんじ", "にほん"];
+
+    fn segment_words(reading: &str) -> Vec<String> {
+        segment(reading, &CostModel::default(), |r| WORDS.contains(&r))
+            .iter()
+            .map(|b| b.reading())
+            .collect()
+    }
+
+    #[test]
+    fn segment_sentence() {
+        assert_eq!(
+            segment_words("わたしのなまえはなかのです"),
+            vec!["わたしの", "なまえは", "なかのです"]
+        );
+    }
+
+    #[test]
+    fn stem_and_suffix() {
+        let result = segment("わたしの", &CostModel::default(), |r| {
+            WORDS.contains(&r)
+        });
+        assert_eq!(
+            result,
+            vec![Bunsetsu {
+                stem: "わたし".to_string(),
+                known: true,
+                suffix: "の".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn longest_word_preferred() {
+        // "かん" + "じ" より "かんじ" を選ぶ
+        assert_eq!(segment_words("かんじ"), vec!["かんじ"]);
+    }
+
+    #[test]
+    fn unknown_run_is_one_bunsetsu() {
+        let result = segment("あいうにほん", &CostModel::default(), |r| {
+            WORDS.contains(&r)
+        });
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].stem, "あいう");
+        assert!(!result[0].known);
+        assert_eq!(result[1].stem, "にほん");
+        assert!(result[1].known);
+    }
+
+    #[test]
+    fn unknown_followed_by_function_word() {
+        let result = segment("ぬぬのにほん", &CostModel::default(), |r| {
+            WORDS.contains(&r)
+        });
+        assert_eq!(result[0].stem, "ぬぬ");
+        assert_eq!(result[0].suffix, "の");
+        assert_eq!(result[1].reading(), "にほん");
+    }
+
+    #[test]
+    fn no_known_words() {
+        let result = segment("あああ", &CostModel::default(), |_| false);
+        assert_eq!(result.len(), 1);
+        assert!(!result[0].known);
+        assert_eq!(result[0].reading(), "あああ");
+    }
+
+    #[test]
+    fn empty_reading() {
+        assert!(segment("", &CostModel::default(), |_| true).is_empty());
+    }
+
+    // === 区切りが固定された文節 ===
+
+    #[test]
+    fn analyze_whole_word() {
+        let b = analyze("なかの", |r| WORDS.contains(&r));
+        assert_eq!(b.stem, "なかの");
+        assert!(b.known);
+        assert_eq!(b.suffix, "");
+    }
+
+    #[test]
+    fn analyze_strips_longest_function_word() {
+        let b = analyze("なかのでした", |r| WORDS.contains(&r));
+        assert_eq!(b.stem, "なかの");
+        assert_eq!(b.suffix, "でした");
+    }
+
+    #[test]
+    fn analyze_unknown() {
+        let b = analyze("わたしのな", |r| WORDS.contains(&r));
+        assert!(!b.known);
+        assert_eq!(b.reading(), "わたしのな");
+    }
+}
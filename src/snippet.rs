@@ -0,0 +1,282 @@
+//! スニペット（定型文）。
+//!
+//! `@@sig` や `あどれす` のような読みを、署名・住所などの複数行の定型文に展開する
+//! `CandidateProvider`。本文のプレースホルダは候補を出すときに展開する。
+//!
+//! ファイル形式（1行1スニペット、`#` で始まる行はコメント）:
+//!
+//! ```text
+//! 読み<TAB>本文<TAB>説明(省略可)
+//! @@sig<TAB>山田太郎\n株式会社サンプル<TAB>署名
+//! あいさつ<TAB>お世話になっております。{cursor}\n{date}
+//! ```
+//!
+//! 本文の `\n` は改行、`\t` はタブ、`\\` は `\` を表す。プレースホルダ:
+//!
+//! - `{date}` / `{time}`: 今日の日付・現在の時刻（日付・時刻の候補の最初の書式）
+//! - `{cursor}`: 確定後のカーソル位置
+//! - `{env:NAME}`: 環境変数 `NAME` の値（なければ空）
+//!
+//! 英字を含む読みはローマ字入力でかなになるため（`@@sig` → `@@しg`）、
+//! 読みはローマ字変換した形でも引けるようにする。
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::candidate::{Candidate, CandidateSource};
+use crate::date_provider::{DateFormats, DateProvider};
+use crate::dictionary::DictionaryError;
+use crate::input_state::InputState;
+use crate::provider::{CandidateProvider, USER_DICTIONARY_PRIORITY};
+
+/// スニペットの候補の優先度。ユーザーが定義したものなので辞書より先に並べる。
+pub const SNIPPET_PRIORITY: i32 = USER_DICTIONARY_PRIORITY + 20;
+
+/// 説明のないスニペットの注釈。
+const SNIPPET_ANNOTATION: &str = "スニペット";
+
+/// 1つのスニペット。
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snippet {
+    body: String,
+    description: Option<String>,
+}
+
+/// スニペットの辞書。
+pub struct SnippetDictionary {
+    snippets: Vec<Snippet>,
+    /// 読み（とそのローマ字変換）→ スニペットの位置
+    index: HashMap<String, Vec<usize>>,
+    dates: DateProvider,
+}
+
+impl SnippetDictionary {
+    /// ファイルからスニペットを読み込む。
+    pub fn load_from_file(path: &Path) -> Result<Self, DictionaryError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text))
+    }
+
+    /// テキストからスニペットをパースする。列が足りない行は無視する。
+    pub fn parse(text: &str) -> Self {
+        let mut dict = Self {
+            snippets: Vec::new(),
+            index: HashMap::new(),
+            dates: DateProvider::new(DateFormats::default()),
+        };
+        let lines = text
+            .lines()
+            .filter(|l| !l.trim().is_empty() && !l.starts_with('#'));
+        for line in lines {
+            let mut fields = line.split('\t');
+            let (Some(reading), Some(body)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let reading = reading.trim();
+            if reading.is_empty() {
+                continue;
+            }
+            let description = fields
+                .next()
+                .map(str::trim)
+                .filter(|d| !d.is_empty())
+                .map(str::to_string);
+            let position = dict.snippets.len();
+            dict.snippets.push(Snippet {
+                body: unescape(body),
+                description,
+            });
+            let typed = romaji_reading(reading);
+            for key in [reading.to_string(), typed] {
+                let entry = dict.index.entry(key).or_default();
+                if !entry.contains(&position) {
+                    entry.push(position);
+                }
+            }
+        }
+        dict
+    }
+
+    /// `{date}` `{time}` の展開に使う日付・時刻の提供元を設定する。
+    pub fn with_dates(mut self, dates: DateProvider) -> Self {
+        self.dates = dates;
+        self
+    }
+
+    /// スニペットの数を返す。
+    pub fn len(&self) -> usize {
+        self.snippets.len()
+    }
+
+    /// スニペットが空かどうか。
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty()
+    }
+
+    /// 本文のプレースホルダを展開し、展開した本文と `{cursor}` の位置（文字単位）を返す。
+    /// 未知のプレースホルダはそのまま残す。
+    fn expand(&self, body: &str) -> (String, Option<usize>) {
+        let mut out = String::new();
+        let mut cursor = None;
+        let mut rest = body;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let Some(close) = rest[open..].find('}') else {
+                out.push_str(&rest[open..]);
+                return (out, cursor);
+            };
+            let name = &rest[open + 1..open + close];
+            match name {
+                "cursor" => {
+                    cursor.get_or_insert(out.chars().count());
+                }
+                "date" => out.push_str(&self.dates.today().unwrap_or_default()),
+                "time" => out.push_str(&self.dates.current_time().unwrap_or_default()),
+                _ => match name.strip_prefix("env:") {
+                    Some(var) => out.push_str(&std::env::var(var).unwrap_or_default()),
+                    None => out.push_str(&rest[open..=open + close]),
+                },
+            }
+            rest = &rest[open + close + 1..];
+        }
+        out.push_str(rest);
+        (out, cursor)
+    }
+}
+
+impl CandidateProvider for SnippetDictionary {
+    fn name(&self) -> &str {
+        "snippet"
+    }
+
+    fn lookup(&self, reading: &str) -> Vec<Candidate> {
+        let Some(positions) = self.index.get(reading) else {
+            return Vec::new();
+        };
+        positions
+            .iter()
+            .map(|&i| {
+                let snippet = &self.snippets[i];
+                let (text, cursor) = self.expand(&snippet.body);
+                let annotation = snippet.description.as_deref().unwrap_or(SNIPPET_ANNOTATION);
+                Candidate::new(&text, reading, CandidateSource::Generated)
+                    .with_annotation(Some(annotation))
+                    .with_cursor(cursor)
+            })
+            .collect()
+    }
+}
+
+/// 読みをローマ字入力したときのかなの読みにする（`@@sig` → `@@しg`）。
+fn romaji_reading(reading: &str) -> String {
+    let mut input = InputState::new();
+    for ch in reading.chars() {
+        input.feed_char(ch);
+    }
+    input.flush();
+    input.output().to_string()
+}
+
+/// 本文のエスケープ（`\n` `\t` `\\`）を戻す。
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => {
+                if other != '\\' {
+                    out.push('\\');
+                }
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date_provider::Clock;
+
+    /// 2026-10-17 14:05:30 (JST) を返す時計。
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> i64 {
+            1_792_213_530
+        }
+    }
+
+    const SAMPLE: &str = "# スニペット\n\
+        @@sig\t山田太郎\\n株式会社サンプル\t署名\n\
+        あどれす\t〒100-0001\\n東京都千代田区\n\
+        あいさつ\tお世話になっております。{cursor}\\n{date}\n\
+        invalid\n";
+
+    fn snippets() -> SnippetDictionary {
+        let dates = DateProvider::with_clock(DateFormats::default(), Box::new(FixedClock));
+        SnippetDictionary::parse(SAMPLE).with_dates(dates)
+    }
+
+    #[test]
+    fn parse_snippets() {
+        let dict = snippets();
+        assert_eq!(dict.len(), 3);
+        let cands = dict.lookup("あどれす");
+        assert_eq!(cands[0].text, "〒100-0001\n東京都千代田区");
+        assert_eq!(cands[0].annotation.as_deref(), Some("スニペット"));
+        assert_eq!(cands[0].cursor, None);
+    }
+
+    #[test]
+    fn ascii_reading_matches_romaji_input() {
+        let dict = snippets();
+        // ローマ字入力では @@sig が「@@しg」になる
+        let cands = dict.lookup("@@しg");
+        assert_eq!(cands[0].text, "山田太郎\n株式会社サンプル");
+        assert_eq!(cands[0].annotation.as_deref(), Some("署名"));
+        // abbrev モードでは入力したまま
+        assert_eq!(dict.lookup("@@sig").len(), 1);
+        assert!(dict.lookup("@@si").is_empty());
+    }
+
+    #[test]
+    fn expand_date_and_cursor() {
+        let dict = snippets();
+        let cands = dict.lookup("あいさつ");
+        assert_eq!(cands[0].text, "お世話になっております。\n2026-10-17");
+        assert_eq!(cands[0].cursor, Some(12));
+    }
+
+    #[test]
+    fn expand_time_env_and_unknown() {
+        let dict = snippets();
+        let (text, cursor) = dict.expand("{time} {env:JAPINPUT_UNDEFINED_VAR}{unknown} {");
+        assert_eq!(text, "14:05 {unknown} {");
+        assert_eq!(cursor, None);
+        let path = std::env::var("PATH").unwrap_or_default();
+        assert_eq!(dict.expand("{env:PATH}").0, path);
+    }
+
+    #[test]
+    fn unescape_sequences() {
+        assert_eq!(unescape(r"a\nb\tc\\d\x\"), "a\nb\tc\\d\\x\\");
+    }
+
+    #[test]
+    fn load_snippet_file() {
+        let path = std::env::temp_dir().join("japinput_snippets_test.txt");
+        std::fs::write(&path, "@@ok\tOK\n").unwrap();
+        let dict = SnippetDictionary::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(dict.lookup("@@ok")[0].text, "OK");
+    }
+}
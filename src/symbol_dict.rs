@@ -0,0 +1,108 @@
+//! 記号・絵文字・顔文字の辞書。
+//!
+//! `にこにこ` → 😊 / (^_^)、`やじるし` → → ← ↑ ↓ ⇒ のような候補を、
+//! システム辞書とは別の提供元として変換候補にマージする。
+//! 辞書は SKK 形式で、候補の注釈をカテゴリ（絵文字 / 顔文字 / 記号）として扱う。
+//! 絵文字の候補を出すかはマージした後にエンジンが決める（`EngineOptions::emoji`）。
+//! 同梱の辞書のほか、同じ形式のファイルを読み込める。
+
+use std::path::Path;
+
+use crate::candidate::{Candidate, CandidateSource};
+use crate::dictionary::{Dictionary, DictionaryError};
+use crate::provider::{CandidateProvider, SYSTEM_DICTIONARY_PRIORITY};
+
+/// 記号の候補の優先度。システム辞書・日付の候補の後に並ぶ。
+pub const SYMBOL_PRIORITY: i32 = SYSTEM_DICTIONARY_PRIORITY - 20;
+
+/// 絵文字のカテゴリ名。`emoji` が無効のときはこのカテゴリの候補を出さない。
+/// ユーザー辞書に学習済みの同じ候補にもマージで注釈が付くので、まとめて除ける。
+pub const EMOJI_CATEGORY: &str = "絵文字";
+
+/// 同梱の記号辞書。
+const BUNDLED_SYMBOLS: &str = include_str!("../dict/symbols.txt");
+
+/// 記号・絵文字・顔文字の辞書。
+pub struct SymbolDictionary {
+    dict: Dictionary,
+}
+
+impl SymbolDictionary {
+    /// 同梱の記号辞書を使う。
+    pub fn bundled() -> Self {
+        Self::new(Dictionary::parse(BUNDLED_SYMBOLS))
+    }
+
+    /// 記号辞書のファイルを読み込む。
+    pub fn load(path: &Path) -> Result<Self, DictionaryError> {
+        Dictionary::load_from_file(path).map(Self::new)
+    }
+
+    fn new(dict: Dictionary) -> Self {
+        Self { dict }
+    }
+}
+
+impl CandidateProvider for SymbolDictionary {
+    fn name(&self) -> &str {
+        "symbol"
+    }
+
+    fn lookup(&self, reading: &str) -> Vec<Candidate> {
+        CandidateProvider::lookup(&self.dict, reading)
+            .into_iter()
+            .map(|c| Candidate {
+                source: CandidateSource::Symbol,
+                ..c
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(dict: &SymbolDictionary, reading: &str) -> Vec<String> {
+        dict.lookup(reading).into_iter().map(|c| c.text).collect()
+    }
+
+    #[test]
+    fn bundled_symbols() {
+        let dict = SymbolDictionary::bundled();
+        let niko = texts(&dict, "にこにこ");
+        assert!(niko.contains(&"😊".to_string()));
+        assert!(niko.contains(&"(^_^)".to_string()));
+        assert_eq!(texts(&dict, "やじるし")[..5], ["→", "←", "↑", "↓", "⇒"]);
+        assert_eq!(texts(&dict, "ほし")[..2], ["★", "☆"]);
+    }
+
+    #[test]
+    fn candidates_carry_category() {
+        let dict = SymbolDictionary::bundled();
+        let cands = dict.lookup("にこにこ");
+        let category = |text: &str| {
+            cands
+                .iter()
+                .find(|c| c.text == text)
+                .and_then(|c| c.annotation.clone())
+        };
+        assert_eq!(category("😊").as_deref(), Some("絵文字"));
+        assert_eq!(category("(^_^)").as_deref(), Some("顔文字"));
+        assert_eq!(dict.lookup("ほし")[0].annotation.as_deref(), Some("記号"));
+        assert!(cands.iter().all(|c| c.source == CandidateSource::Symbol));
+    }
+
+    #[test]
+    fn load_symbol_file() {
+        let path = std::env::temp_dir().join("japinput_symbol_dict_test.txt");
+        std::fs::write(&path, "まる /○;記号/⭕;絵文字/\n").unwrap();
+        let dict = SymbolDictionary::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(texts(&dict, "まる"), ["○", "⭕"]);
+        assert_eq!(
+            dict.lookup("まる")[1].annotation.as_deref(),
+            Some(EMOJI_CATEGORY)
+        );
+    }
+}
@@ -2,18 +2,26 @@
 //!
 //! `ITfTextInputProcessorEx` と `ITfKeyEventSink` を実装し、
 //! Windows の TSF フレームワークと ConversionEngine を接続する。
+//! `ITfDisplayAttributeProvider` で入力中のテキストの表示属性を提供し、
+//! `ITfCompartmentEventSink` で IME のオン/オフを OS（言語バーなど）と同期する。
 
 use std::sync::{Arc, Mutex};
 
 use windows::Win32::Foundation::*;
-use windows::Win32::UI::Input::KeyboardAndMouse::GetKeyState;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyState, MAPVK_VK_TO_VSC, MapVirtualKeyW, ToUnicode,
+};
 use windows::Win32::UI::TextServices::*;
 use windows::core::*;
 
 use crate::config::Config;
+use crate::date_provider::DateProvider;
 use crate::dictionary::Dictionary;
-use crate::engine::{ConversionEngine, EngineOutput};
-use crate::key_mapping::{self, CtrlKeyConfig, Modifiers};
+use crate::display_attribute::{self, DisplayAtoms, EnumDisplayAttributeInfo};
+use crate::engine::{ConversionEngine, EngineCommand, EngineOutput, PreeditSpan};
+use crate::key_mapping::{self, CtrlKeyConfig, Modifiers, ToggleKey};
+use crate::provider::MergeRule;
+use crate::snippet::{SNIPPET_PRIORITY, SnippetDictionary};
 use crate::user_dictionary::UserDictionary;
 
 // === EditSession ===
@@ -23,10 +31,14 @@ enum EditAction {
     /// Composition を開始/更新してテキストを設定する。
     SetText(String),
     /// テキストを確定して Composition を終了する。
-    CommitText(String),
+    /// `caret` があれば確定したテキスト内のその位置（UTF-16 単位）にカーソルを置く。
+    CommitText { text: String, caret: Option<usize> },
     /// テキストを確定して Composition を終了し、直後に新しい Composition を開始する。
     /// Converting 中の InsertChar で候補確定と新規入力を同一セッションで処理する。
     CommitAndCompose { committed: String, display: String },
+    /// カーソルの前にある確定済みのテキストを削除し、新しい Composition を開始する。
+    /// 確定の取り消し (UndoCommit) で確定前の変換に戻る。
+    UndoCommit { undone: String, display: String },
     /// Composition を終了する。
     EndComposition,
 }
@@ -41,6 +53,12 @@ struct EditSession {
     context: ITfContext,
     composition: Arc<Mutex<Option<ITfComposition>>>,
     action: EditAction,
+    /// アクション後の Composition 内のテキストを属性ごとに分けたもの
+    preedit: Vec<PreeditSpan>,
+    /// 表示属性の atom
+    atoms: DisplayAtoms,
+    /// アクション後の Composition 内のカーソル位置（UTF-16 単位）
+    caret: Option<usize>,
 }
 
 impl EditSession {
@@ -78,11 +96,85 @@ impl EditSession {
         Ok(())
     }
 
-    /// Composition を終了し、参照をクリアする。
+    /// Composition の先頭から `caret` の位置にカーソルを置く。
+    fn set_caret(&self, ec: u32, caret: usize) -> Result<()> {
+        let comp = self.composition.lock().unwrap();
+        let Some(ref composition) = *comp else {
+            return Ok(());
+        };
+        unsafe {
+            let range = composition.GetRange()?.Clone()?;
+            range.Collapse(ec, TF_ANCHOR_START)?;
+            let mut shifted = 0;
+            range.ShiftEnd(ec, caret as i32, &mut shifted, std::ptr::null())?;
+            range.Collapse(ec, TF_ANCHOR_END)?;
+            let mut selection = TF_SELECTION {
+                range: std::mem::ManuallyDrop::new(Some(range)),
+                style: TF_SELECTIONSTYLE {
+                    ase: TF_AE_NONE,
+                    fInterimChar: FALSE,
+                },
+            };
+            let result = self
+                .context
+                .SetSelection(ec, std::slice::from_ref(&selection));
+            std::mem::ManuallyDrop::drop(&mut selection.range);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// カーソルの前にある確定済みのテキストを削除する。
+    fn delete_before_selection(&self, ec: u32, text: &str) -> Result<()> {
+        unsafe {
+            let insert: ITfInsertAtSelection = self.context.cast()?;
+            let range = insert.InsertTextAtSelection(ec, TF_IAS_QUERYONLY, &[])?;
+            let len = text.encode_utf16().count() as i32;
+            let mut shifted = 0;
+            range.ShiftStart(ec, -len, &mut shifted, std::ptr::null())?;
+            range.SetText(ec, 0, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Composition 内の各部分に属性に応じた表示属性を設定する。
+    fn apply_attributes(&self, ec: u32) -> Result<()> {
+        let comp = self.composition.lock().unwrap();
+        let Some(ref composition) = *comp else {
+            return Ok(());
+        };
+        unsafe {
+            let property = self.context.GetProperty(&GUID_PROP_ATTRIBUTE)?;
+            let whole = composition.GetRange()?;
+            property.Clear(ec, &whole)?;
+            let mut start = 0;
+            for span in &self.preedit {
+                let len = utf16_offset(&span.text, span.text.chars().count()) as i32;
+                if let Some(atom) = self.atoms.atom(span.attribute) {
+                    let range = whole.Clone()?;
+                    range.Collapse(ec, TF_ANCHOR_START)?;
+                    let mut shifted = 0;
+                    range.ShiftEnd(ec, start + len, &mut shifted, std::ptr::null())?;
+                    range.ShiftStart(ec, start, &mut shifted, std::ptr::null())?;
+                    property.SetValue(ec, &range, &VARIANT::from(atom as i32))?;
+                }
+                start += len;
+            }
+        }
+        Ok(())
+    }
+
+    /// Composition を終了し、参照をクリアする。表示属性も取り除く。
     fn finish_composition(&self, ec: u32) -> Result<()> {
         let mut comp = self.composition.lock().unwrap();
         if let Some(composition) = comp.take() {
             unsafe {
+                if let (Ok(property), Ok(range)) = (
+                    self.context.GetProperty(&GUID_PROP_ATTRIBUTE),
+                    composition.GetRange(),
+                ) {
+                    let _ = property.Clear(ec, &range);
+                }
                 composition.EndComposition(ec)?;
             }
         }
@@ -96,10 +188,14 @@ impl ITfEditSession_Impl for EditSession_Impl {
             EditAction::SetText(text) => {
                 self.ensure_composition(ec)?;
                 self.write_text(ec, text)?;
+                self.apply_attributes(ec)?;
             }
-            EditAction::CommitText(text) => {
+            EditAction::CommitText { text, caret } => {
                 self.ensure_composition(ec)?;
                 self.write_text(ec, text)?;
+                if let Some(caret) = *caret {
+                    self.set_caret(ec, caret)?;
+                }
                 self.finish_composition(ec)?;
             }
             EditAction::CommitAndCompose { committed, display } => {
@@ -110,25 +206,46 @@ impl ITfEditSession_Impl for EditSession_Impl {
                 // 2. 新しい Composition を開始して次の入力を表示
                 self.ensure_composition(ec)?;
                 self.write_text(ec, display)?;
+                self.apply_attributes(ec)?;
+            }
+            EditAction::UndoCommit { undone, display } => {
+                self.finish_composition(ec)?;
+                self.delete_before_selection(ec, undone)?;
+                self.ensure_composition(ec)?;
+                self.write_text(ec, display)?;
+                self.apply_attributes(ec)?;
             }
             EditAction::EndComposition => {
                 self.finish_composition(ec)?;
             }
         }
+        if let Some(caret) = self.caret {
+            self.set_caret(ec, caret)?;
+        }
         Ok(S_OK)
     }
 }
 
 // === TextService ===
 
-#[implement(ITfTextInputProcessorEx, ITfTextInputProcessor, ITfKeyEventSink)]
+#[implement(
+    ITfTextInputProcessorEx,
+    ITfTextInputProcessor,
+    ITfKeyEventSink,
+    ITfDisplayAttributeProvider,
+    ITfCompartmentEventSink
+)]
 pub struct TextService {
     thread_mgr: Mutex<Option<ITfThreadMgr>>,
     client_id: Mutex<u32>,
+    /// オン/オフの compartment の監視を解除するための cookie
+    openclose_cookie: Mutex<u32>,
+    /// 表示属性の atom（Activate のときに登録する）
+    display_atoms: Mutex<DisplayAtoms>,
     engine: Mutex<ConversionEngine>,
-    ime_on: Mutex<bool>,
     composition: Arc<Mutex<Option<ITfComposition>>>,
     ctrl_config: CtrlKeyConfig,
+    toggle_key: ToggleKey,
 }
 
 impl TextService {
@@ -154,13 +271,32 @@ impl TextService {
 
         let ctrl_config = config.keybind.clone();
 
+        let mut engine = ConversionEngine::new_with_user_dict(dict, user_dict);
+        // 動作設定・コンテキスト優先テーブル・記号辞書・日付の候補など
+        config.configure_engine(&mut engine);
+        // スニペット（存在しなければ使用しない）
+        let snippets_path = config
+            .snippets_path
+            .as_ref()
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|| get_appdata_path("snippets.txt"));
+        if let Ok(snippets) = SnippetDictionary::load_from_file(&snippets_path) {
+            let dates = DateProvider::new(config.date_formats.clone());
+            engine.add_provider(
+                Box::new(snippets.with_dates(dates)),
+                MergeRule::new(SNIPPET_PRIORITY),
+            );
+        }
+
         Self {
             thread_mgr: Mutex::new(None),
             client_id: Mutex::new(0),
-            engine: Mutex::new(ConversionEngine::new_with_user_dict(dict, user_dict)),
-            ime_on: Mutex::new(false),
+            openclose_cookie: Mutex::new(TF_INVALID_COOKIE),
+            display_atoms: Mutex::new(DisplayAtoms::default()),
+            engine: Mutex::new(engine),
             composition: Arc::new(Mutex::new(None)),
             ctrl_config,
+            toggle_key: config.toggle_key,
         }
     }
 
@@ -194,18 +330,74 @@ impl TextService {
             .map(|p| p.to_path_buf())
     }
 
+    /// 仮想キーコードを EngineCommand に変換する。
+    ///
+    /// 記号キーはキーボード配列で入力される文字を求めて変換する。
+    fn map_key(&self, engine: &ConversionEngine, vk: u16) -> Option<EngineCommand> {
+        let modifiers = modifiers_from_keyboard_state();
+        key_mapping::map_key(
+            vk,
+            &modifiers,
+            engine.is_enabled(),
+            &self.toggle_key,
+            &self.ctrl_config,
+        )
+        .or_else(|| {
+            let ch = layout_char(vk, &modifiers)?;
+            key_mapping::map_text(ch, &modifiers, engine.is_enabled(), engine.options())
+        })
+    }
+
+    /// フォーカスのあるドキュメントの最上位のコンテキストを返す。
+    fn focused_context(&self) -> Option<ITfContext> {
+        let thread_mgr = self.thread_mgr.lock().unwrap().clone()?;
+        unsafe { thread_mgr.GetFocus().ok()?.GetTop().ok() }
+    }
+
+    /// IME のオン/オフを compartment に書き込む。
+    fn write_openclose(&self, enabled: bool) -> Result<()> {
+        let thread_mgr = self.thread_mgr.lock().unwrap().clone().ok_or(E_FAIL)?;
+        let tid = *self.client_id.lock().unwrap();
+        unsafe { openclose_compartment(&thread_mgr)?.SetValue(tid, &VARIANT::from(enabled as i32)) }
+    }
+
+    /// compartment から IME のオン/オフを読む。
+    fn read_openclose(&self) -> Result<bool> {
+        let thread_mgr = self.thread_mgr.lock().unwrap().clone().ok_or(E_FAIL)?;
+        let value = unsafe { openclose_compartment(&thread_mgr)?.GetValue()? };
+        Ok(i32::try_from(&value).unwrap_or(0) != 0)
+    }
+
     /// EngineOutput に基づいて EditSession を発行し、Composition を更新する。
-    fn update_composition(&self, context: &ITfContext, output: &EngineOutput) -> Result<()> {
-        let action = if !output.committed.is_empty() && !output.display.is_empty() {
+    ///
+    /// キー入力の処理中は同期的に編集する。それ以外（compartment の変更など）
+    /// では同期的に編集できないことがあるので、`sync` を false にして TSF に任せる。
+    fn update_composition(
+        &self,
+        context: &ITfContext,
+        output: &EngineOutput,
+        sync: bool,
+    ) -> Result<()> {
+        let action = if !output.undone_commit.is_empty() {
+            EditAction::UndoCommit {
+                undone: to_crlf(&output.undone_commit),
+                display: to_crlf(&output.display),
+            }
+        } else if !output.committed.is_empty() && !output.display.is_empty() {
             // 候補確定と新規入力が同時に発生（例: Converting 中の InsertChar）
             EditAction::CommitAndCompose {
-                committed: output.committed.clone(),
-                display: output.display.clone(),
+                committed: to_crlf(&output.committed),
+                display: to_crlf(&output.display),
             }
         } else if !output.committed.is_empty() {
-            EditAction::CommitText(output.committed.clone())
+            EditAction::CommitText {
+                text: to_crlf(&output.committed),
+                caret: output
+                    .commit_caret
+                    .map(|c| utf16_offset(&output.committed, c)),
+            }
         } else if !output.display.is_empty() {
-            EditAction::SetText(output.display.clone())
+            EditAction::SetText(to_crlf(&output.display))
         } else {
             // 表示も確定テキストもない場合、Composition がなければ何もしない
             if self.composition.lock().unwrap().is_none() {
@@ -218,13 +410,20 @@ impl TextService {
             context: context.clone(),
             composition: Arc::clone(&self.composition),
             action,
+            preedit: output.preedit.clone(),
+            atoms: *self.display_atoms.lock().unwrap(),
+            caret: output.caret.map(|c| utf16_offset(&output.display, c)),
         }
         .into();
 
         let tid = *self.client_id.lock().unwrap();
+        let flags = if sync {
+            TF_ES_READWRITE | TF_ES_SYNC
+        } else {
+            TF_ES_READWRITE | TF_ES_ASYNCDONTCARE
+        };
         unsafe {
-            let _session_hr =
-                context.RequestEditSession(tid, &session, TF_ES_READWRITE | TF_ES_SYNC)?;
+            let _session_hr = context.RequestEditSession(tid, &session, flags)?;
         }
 
         Ok(())
@@ -243,9 +442,21 @@ impl ITfTextInputProcessorEx_Impl for TextService_Impl {
             keystroke_mgr.AdviseKeyEventSink(tid, &self_sink, TRUE)?;
         }
 
+        // IME のオン/オフを OS と共有する compartment の変更を監視する
+        let compartment = openclose_compartment(&thread_mgr)?;
+        let source: ITfSource = compartment.cast()?;
+        let compartment_sink: ITfCompartmentEventSink = self.cast()?;
+        let cookie =
+            unsafe { source.AdviseSink(&ITfCompartmentEventSink::IID, &compartment_sink)? };
+        *self.openclose_cookie.lock().unwrap() = cookie;
+
         *self.thread_mgr.lock().unwrap() = Some(thread_mgr);
         *self.client_id.lock().unwrap() = tid;
-        *self.ime_on.lock().unwrap() = true;
+        // 有効になった時点のエンジンのオン/オフを OS に知らせる
+        let enabled = self.engine.lock().unwrap().is_enabled();
+        self.write_openclose(enabled)?;
+        // 登録できなくても入力はできるので、表示属性なしで続ける
+        *self.display_atoms.lock().unwrap() = DisplayAtoms::register().unwrap_or_default();
 
         Ok(())
     }
@@ -260,6 +471,19 @@ impl ITfTextInputProcessorEx_Impl for TextService_Impl {
                     let _ = keystroke_mgr.UnadviseKeyEventSink(tid);
                 }
             }
+            let cookie = std::mem::replace(
+                &mut *self.openclose_cookie.lock().unwrap(),
+                TF_INVALID_COOKIE,
+            );
+            if cookie != TF_INVALID_COOKIE {
+                if let Ok(source) =
+                    openclose_compartment(&thread_mgr).and_then(|c| c.cast::<ITfSource>())
+                {
+                    unsafe {
+                        let _ = source.UnadviseSink(cookie);
+                    }
+                }
+            }
         }
 
         // ユーザー辞書の保存
@@ -272,7 +496,6 @@ impl ITfTextInputProcessorEx_Impl for TextService_Impl {
         }
         drop(engine);
 
-        *self.ime_on.lock().unwrap() = false;
         // EditSession なしでは EndComposition(ec) を呼べないため、参照のみ解放する。
         // TSF は TIP の Deactivate 時にアクティブな Composition を自動終了する。
         *self.composition.lock().unwrap() = None;
@@ -292,6 +515,44 @@ impl ITfTextInputProcessor_Impl for TextService_Impl {
     }
 }
 
+// --- ITfDisplayAttributeProvider ---
+
+impl ITfDisplayAttributeProvider_Impl for TextService_Impl {
+    fn EnumDisplayAttributeInfo(&self) -> Result<IEnumTfDisplayAttributeInfo> {
+        Ok(EnumDisplayAttributeInfo::default().into())
+    }
+
+    fn GetDisplayAttributeInfo(&self, guid: *const GUID) -> Result<ITfDisplayAttributeInfo> {
+        let guid = unsafe { guid.as_ref() }.ok_or(E_INVALIDARG)?;
+        display_attribute::info_for_guid(guid).ok_or_else(|| E_INVALIDARG.into())
+    }
+}
+
+// --- ITfCompartmentEventSink ---
+
+impl ITfCompartmentEventSink_Impl for TextService_Impl {
+    /// 言語バーなどで IME のオン/オフが変わったらエンジンを合わせる。
+    fn OnChange(&self, rguid: *const GUID) -> Result<()> {
+        let guid = unsafe { rguid.as_ref() }.ok_or(E_INVALIDARG)?;
+        if *guid != GUID_COMPARTMENT_KEYBOARD_OPENCLOSE {
+            return Ok(());
+        }
+        let open = self.read_openclose()?;
+        let mut engine = self.engine.lock().unwrap();
+        if engine.is_enabled() == open {
+            return Ok(());
+        }
+        // オフにしたときは入力中の内容を確定または破棄する
+        let output = engine.set_enabled(open);
+        drop(engine);
+
+        if let Some(context) = self.focused_context() {
+            self.update_composition(&context, &output, false)?;
+        }
+        Ok(())
+    }
+}
+
 // --- ITfKeyEventSink ---
 
 impl ITfKeyEventSink_Impl for TextService_Impl {
@@ -305,31 +566,33 @@ impl ITfKeyEventSink_Impl for TextService_Impl {
         wparam: WPARAM,
         _lparam: LPARAM,
     ) -> Result<BOOL> {
-        let ime_on = *self.ime_on.lock().unwrap();
-        let modifiers = modifiers_from_keyboard_state();
-        let vk = wparam.0 as u16;
-
-        match key_mapping::map_key(vk, &modifiers, ime_on, &self.ctrl_config) {
-            Some(_) => Ok(TRUE),
-            None => Ok(FALSE),
+        let engine = self.engine.lock().unwrap();
+        match self.map_key(&engine, wparam.0 as u16) {
+            Some(command) if engine.accepts(&command) => Ok(TRUE),
+            _ => Ok(FALSE),
         }
     }
 
     fn OnKeyDown(&self, pic: Option<&ITfContext>, wparam: WPARAM, _lparam: LPARAM) -> Result<BOOL> {
-        let ime_on = *self.ime_on.lock().unwrap();
-        let modifiers = modifiers_from_keyboard_state();
-        let vk = wparam.0 as u16;
-
-        let Some(command) = key_mapping::map_key(vk, &modifiers, ime_on, &self.ctrl_config) else {
+        let mut engine = self.engine.lock().unwrap();
+        let Some(command) = self.map_key(&engine, wparam.0 as u16) else {
             return Ok(FALSE);
         };
-
-        let mut engine = self.engine.lock().unwrap();
+        if !engine.accepts(&command) {
+            engine.forget_last_commit();
+            return Ok(FALSE);
+        }
+        let was_enabled = engine.is_enabled();
         let output = engine.process(command);
+        let enabled = engine.is_enabled();
         drop(engine);
 
         if let Some(context) = pic {
-            self.update_composition(context, &output)?;
+            self.update_composition(context, &output, true)?;
+        }
+        // 切り替えキーでのオン/オフを OS に知らせる
+        if enabled != was_enabled {
+            self.write_openclose(enabled)?;
         }
 
         Ok(TRUE)
@@ -349,6 +612,21 @@ impl ITfKeyEventSink_Impl for TextService_Impl {
     }
 }
 
+/// 確定テキスト・入力中のテキストの改行を、アプリケーションに渡す CRLF にする。
+fn to_crlf(text: &str) -> String {
+    text.replace('\n', "\r\n")
+}
+
+/// 文字単位の位置を、`to_crlf` したテキスト内の UTF-16 単位の位置に変換する。
+///
+/// 改行は CR と LF の2単位として数える。
+fn utf16_offset(text: &str, chars: usize) -> usize {
+    text.chars()
+        .take(chars)
+        .map(|c| if c == '\n' { 2 } else { c.len_utf16() })
+        .sum()
+}
+
 /// %APPDATA%\japinput\ 以下のパスを返す。
 fn get_appdata_path(filename: &str) -> std::path::PathBuf {
     let appdata = std::env::var("APPDATA").unwrap_or_else(|_| ".".to_string());
@@ -357,6 +635,32 @@ fn get_appdata_path(filename: &str) -> std::path::PathBuf {
         .join(filename)
 }
 
+/// スレッドの IME のオン/オフ (`GUID_COMPARTMENT_KEYBOARD_OPENCLOSE`) の compartment を返す。
+fn openclose_compartment(thread_mgr: &ITfThreadMgr) -> Result<ITfCompartment> {
+    let compartment_mgr: ITfCompartmentMgr = thread_mgr.cast()?;
+    unsafe { compartment_mgr.GetCompartment(&GUID_COMPARTMENT_KEYBOARD_OPENCLOSE) }
+}
+
+/// 現在のキーボード配列で `vk` を押したときに入力される文字を返す。
+///
+/// Shift 以外の修飾キーと CapsLock は無視する。文字にならないキーは None。
+fn layout_char(vk: u16, modifiers: &Modifiers) -> Option<char> {
+    let mut state = [0u8; 256];
+    if modifiers.shift {
+        state[key_mapping::VK_SHIFT as usize] = 0x80;
+    }
+    let mut buf = [0u16; 4];
+    let len = unsafe {
+        let scan = MapVirtualKeyW(vk as u32, MAPVK_VK_TO_VSC);
+        // 0x4: デッドキーなどのキーボードの状態を変えない
+        ToUnicode(vk as u32, scan, Some(&state), &mut buf, 0x4)
+    };
+    if len != 1 {
+        return None;
+    }
+    char::from_u32(buf[0] as u32)
+}
+
 /// キーボードの現在の修飾キー状態を取得する。
 fn modifiers_from_keyboard_state() -> Modifiers {
     unsafe {
@@ -0,0 +1,198 @@
+//! タイポ補正テーブル。
+//!
+//! 打鍵したローマ字の打ち間違い（`konnnichiha` の「ん」の打ちすぎ、
+//! `ca` の h の抜けなど）をテーブルのルールで補正した別の入力を作る。
+//! 隣り合う2文字の打鍵順の入れ替わり（`kaiig`）はテーブルに書かなくても補正する。
+//! エンジンは入力どおりの読みに候補のない文節があるときだけ補正した読みを試すため、
+//! `konna` の `nn` のような正しい入力は補正されない。
+//!
+//! ファイル形式（1行1ルール、`#` で始まる行はコメント）:
+//!
+//! ```text
+//! 誤入力<TAB>正しい入力
+//! nnn<TAB>nn
+//! toukouy<TAB>toukyou
+//! ```
+
+use std::path::Path;
+
+use crate::dictionary::DictionaryError;
+
+/// 補正した候補の注釈。
+pub const CORRECTED_ANNOTATION: &str = "補正";
+
+/// 同梱のタイポ補正テーブル。
+const BUNDLED_CORRECTIONS: &str = include_str!("../dict/typo_corrections.txt");
+
+/// タイポ補正テーブルの1ルール。
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    wrong: String,
+    right: String,
+}
+
+/// タイポ補正テーブル。
+#[derive(Debug, Clone, Default)]
+pub struct TypoCorrection {
+    rules: Vec<Rule>,
+}
+
+impl TypoCorrection {
+    /// 同梱のテーブルを使う。
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_CORRECTIONS)
+    }
+
+    /// ファイルからテーブルを読み込む。
+    pub fn load_from_file(path: &Path) -> Result<Self, DictionaryError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&text))
+    }
+
+    /// テキストからテーブルをパースする。列が足りない行・誤入力と同じ行は無視する。
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .filter_map(|line| {
+                let (wrong, right) = line.split_once('\t')?;
+                let (wrong, right) = (wrong.trim(), right.trim());
+                if wrong.is_empty() || wrong == right {
+                    return None;
+                }
+                Some(Rule {
+                    wrong: wrong.to_string(),
+                    right: right.to_string(),
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// ルール数を返す。
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// ルールが空かどうか。
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 打鍵したローマ字を補正した入力を、試す順に返す。
+    ///
+    /// 当てはまるルールを1つずつ適用したものを先に、すべて適用したものを最後に並べる。
+    /// どのルールも当てはまらなければ空。
+    pub fn correct(&self, romaji: &str) -> Vec<String> {
+        let mut alternatives: Vec<String> = Vec::new();
+        let mut all = romaji.to_string();
+        for rule in &self.rules {
+            if romaji.contains(&rule.wrong) {
+                let corrected = romaji.replace(&rule.wrong, &rule.right);
+                if !alternatives.contains(&corrected) {
+                    alternatives.push(corrected);
+                }
+            }
+            all = all.replace(&rule.wrong, &rule.right);
+        }
+        if all != romaji && !alternatives.contains(&all) {
+            alternatives.push(all);
+        }
+        alternatives
+    }
+
+    /// 補正した入力を試す順に返す。テーブルのルールによる補正の後に、
+    /// 隣り合う2文字を入れ替えたものを前から順に並べる。
+    pub fn alternatives(&self, romaji: &str) -> Vec<String> {
+        let mut alternatives = self.correct(romaji);
+        for swapped in transpositions(romaji) {
+            if !alternatives.contains(&swapped) {
+                alternatives.push(swapped);
+            }
+        }
+        alternatives
+    }
+}
+
+/// 隣り合う2文字を入れ替えた入力を、前から順に返す。
+///
+/// 英字どうしで、異なる文字の組だけを入れ替える。
+fn transpositions(romaji: &str) -> Vec<String> {
+    let chars: Vec<char> = romaji.chars().collect();
+    chars
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| {
+            pair[0] != pair[1] && pair[0].is_ascii_alphabetic() && pair[1].is_ascii_alphabetic()
+        })
+        .map(|(i, _)| {
+            let mut swapped = chars.clone();
+            swapped.swap(i, i + 1);
+            swapped.into_iter().collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "# コメント\nnnn\tnn\nca\tcha\nkaiig\tkaigi\n\ninvalid\nsame\tsame\n";
+
+    #[test]
+    fn parse_rules() {
+        let table = TypoCorrection::parse(SAMPLE);
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn correct_applies_matching_rule() {
+        let table = TypoCorrection::parse(SAMPLE);
+        assert_eq!(table.correct("konnnichiha"), ["konnichiha"]);
+        assert_eq!(table.correct("kaiig"), ["kaigi"]);
+    }
+
+    #[test]
+    fn correct_without_match_is_empty() {
+        let table = TypoCorrection::parse(SAMPLE);
+        assert!(table.correct("kanji").is_empty());
+        assert!(table.correct("").is_empty());
+    }
+
+    #[test]
+    fn legitimate_nn_is_not_corrected() {
+        let table = TypoCorrection::bundled();
+        assert!(table.correct("konnichiha").is_empty());
+        assert!(table.correct("konna").is_empty());
+        assert!(table.correct("konnyaku").is_empty());
+        // nn の後に子音が続くと「んん」になるため補正する
+        assert_eq!(table.correct("kannji"), ["kanji"]);
+    }
+
+    #[test]
+    fn correct_each_rule_then_all() {
+        let table = TypoCorrection::parse(SAMPLE);
+        assert_eq!(table.correct("cannna"), ["canna", "channna", "channa"]);
+    }
+
+    #[test]
+    fn alternatives_add_adjacent_transpositions() {
+        let table = TypoCorrection::parse(SAMPLE);
+        assert_eq!(transpositions("kaiig"), ["akiig", "kiaig", "kaigi"]);
+        // テーブルの補正が先、重複は除く
+        assert_eq!(table.alternatives("kaiig"), ["kaigi", "akiig", "kiaig"]);
+        // 同じ文字の組と英字以外は入れ替えない
+        assert_eq!(transpositions("nn1a"), Vec::<String>::new());
+        assert!(TypoCorrection::default().alternatives("").is_empty());
+    }
+
+    #[test]
+    fn load_table_file() {
+        let path = std::env::temp_dir().join("japinput_typo_corrections_test.txt");
+        std::fs::write(&path, "toukouy\ttoukyou\n").unwrap();
+        let table = TypoCorrection::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(table.correct("toukouy"), ["toukyou"]);
+    }
+}
@@ -0,0 +1,366 @@
+//! 他の IME のユーザー辞書形式との相互変換。
+//!
+//! Microsoft IME・Google 日本語入力・ATOK がエクスポートする
+//! タブ区切りのテキスト辞書（読み・単語・品詞・コメント）を
+//! `UserDictionary` に取り込み、また同じ形式で書き出す。
+
+use crate::user_dictionary::{UserDictionary, UserWord};
+
+/// ユーザー辞書のテキスト形式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserDictFormat {
+    /// Microsoft IME（UTF-16LE, `!` で始まるヘッダー行）
+    MsIme,
+    /// Google 日本語入力（UTF-8）
+    Google,
+    /// ATOK（UTF-16LE, `!!` で始まるヘッダー行）
+    Atok,
+}
+
+impl UserDictFormat {
+    /// 形式名 (`msime` / `google` / `atok`) から形式を返す。
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "msime" => Some(UserDictFormat::MsIme),
+            "google" => Some(UserDictFormat::Google),
+            "atok" => Some(UserDictFormat::Atok),
+            _ => None,
+        }
+    }
+
+    /// ヘッダー・コメント行かどうか。
+    fn is_header(&self, line: &str) -> bool {
+        match self {
+            UserDictFormat::MsIme | UserDictFormat::Atok => line.starts_with('!'),
+            UserDictFormat::Google => line.starts_with('#'),
+        }
+    }
+
+    /// 書き出し時のヘッダー行。
+    fn header(&self) -> &'static [&'static str] {
+        match self {
+            UserDictFormat::MsIme => &["!Microsoft IME Dictionary Tool", "!Format:WORDLIST", ""],
+            UserDictFormat::Google => &[],
+            UserDictFormat::Atok => &["!!ATOK_TANGO_TEXT_HEADER_1", ""],
+        }
+    }
+}
+
+/// インポートで読み飛ばした行。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLine {
+    /// 1 始まりの行番号
+    pub line: usize,
+    pub content: String,
+    pub reason: String,
+}
+
+/// エクスポートで書き出さなかった単語。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedWord {
+    pub word: UserWord,
+    pub reason: String,
+}
+
+/// インポート結果。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// 新たに登録した単語数
+    pub imported: usize,
+    /// 既に登録済みだった単語数（品詞・コメントのみ更新）
+    pub updated: usize,
+    /// 読み飛ばした行
+    pub skipped: Vec<SkippedLine>,
+}
+
+/// テキスト辞書をパースし、単語と読み飛ばした行を返す。
+///
+/// 文字コードは BOM から判定し、BOM がなければ UTF-8、
+/// UTF-8 として不正なら Shift_JIS（古い MS-IME / ATOK の既定）とみなす。
+pub fn parse(bytes: &[u8], format: UserDictFormat) -> (Vec<UserWord>, Vec<SkippedLine>) {
+    let text = decode(bytes);
+    let mut words = Vec::new();
+    let mut skipped = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = raw.trim_end_matches('\r');
+        if line.trim().is_empty() || format.is_header(line) {
+            continue;
+        }
+        match parse_line(line) {
+            Ok(word) => words.push(word),
+            Err(reason) => skipped.push(SkippedLine {
+                line: i + 1,
+                content: line.to_string(),
+                reason,
+            }),
+        }
+    }
+    (words, skipped)
+}
+
+/// テキスト辞書を `UserDictionary` に取り込む。
+pub fn import(ud: &mut UserDictionary, bytes: &[u8], format: UserDictFormat) -> ImportReport {
+    let (words, skipped) = parse(bytes, format);
+    let mut report = ImportReport {
+        skipped,
+        ..ImportReport::default()
+    };
+    for word in &words {
+        if ud.add_word(word) {
+            report.imported += 1;
+        } else {
+            report.updated += 1;
+        }
+    }
+    report
+}
+
+/// `UserDictionary` の全単語を指定の形式で書き出し、書き出さなかった単語を返す。
+///
+/// MS-IME / ATOK は BOM 付き UTF-16LE・CRLF、Google は UTF-8・LF で出力する。
+/// 品詞が未設定の単語は "名詞" として書き出す。
+/// 送りありの読み (`くr`) や英字の読み（abbrev）は他の IME で使えないため書き出さない。
+pub fn export(ud: &UserDictionary, format: UserDictFormat) -> (Vec<u8>, Vec<SkippedWord>) {
+    let mut lines: Vec<String> = format.header().iter().map(|s| s.to_string()).collect();
+    let mut skipped = Vec::new();
+    for w in ud.words() {
+        if let Some(reason) = unexportable_reason(&w.reading) {
+            skipped.push(SkippedWord {
+                word: w,
+                reason: reason.to_string(),
+            });
+            continue;
+        }
+        let pos = w.part_of_speech.as_deref().unwrap_or("名詞");
+        let line = match &w.comment {
+            Some(comment) => format!("{}\t{}\t{}\t{}", w.reading, w.word, pos, comment),
+            None => format!("{}\t{}\t{}", w.reading, w.word, pos),
+        };
+        lines.push(line);
+    }
+    let bytes = match format {
+        UserDictFormat::Google => (lines.join("\n") + "\n").into_bytes(),
+        UserDictFormat::MsIme | UserDictFormat::Atok => {
+            let text = lines.join("\r\n") + "\r\n";
+            let mut bytes = vec![0xFF, 0xFE];
+            for unit in text.encode_utf16() {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+            bytes
+        }
+    };
+    (bytes, skipped)
+}
+
+/// 他の IME に書き出せない読みなら理由を返す。
+fn unexportable_reason(reading: &str) -> Option<&'static str> {
+    if !reading.contains(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    if reading.starts_with(|c: char| !c.is_ascii()) {
+        Some("送りありの読みは他の IME で使えません")
+    } else {
+        Some("英字の読みは他の IME で使えません")
+    }
+}
+
+/// BOM と内容から文字コードを判定してデコードする。
+fn decode(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let (cow, _) = encoding_rs::UTF_16LE.decode_without_bom_handling(rest);
+        return cow.into_owned();
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let (cow, _) = encoding_rs::UTF_16BE.decode_without_bom_handling(rest);
+        return cow.into_owned();
+    }
+    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => {
+            let (cow, _, _) = encoding_rs::SHIFT_JIS.decode(bytes);
+            cow.into_owned()
+        }
+    }
+}
+
+/// 1行（読み・単語・品詞・コメント）をパースする。失敗時は理由を返す。
+fn parse_line(line: &str) -> Result<UserWord, String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() < 2 {
+        return Err("タブ区切りの列が不足しています".to_string());
+    }
+    let reading = fields[0].trim();
+    let word = fields[1].trim();
+    if reading.is_empty() || word.is_empty() {
+        return Err("読みまたは単語が空です".to_string());
+    }
+    if reading.contains(char::is_whitespace) {
+        return Err("読みに空白を含む単語は登録できません".to_string());
+    }
+    let optional = |i: usize| {
+        fields
+            .get(i)
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    Ok(UserWord {
+        reading: reading.to_string(),
+        word: word.to_string(),
+        part_of_speech: optional(2),
+        comment: optional(3),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le_with_bom(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    // === 形式名 ===
+
+    #[test]
+    fn format_from_name() {
+        assert_eq!(
+            UserDictFormat::from_name("msime"),
+            Some(UserDictFormat::MsIme)
+        );
+        assert_eq!(
+            UserDictFormat::from_name("google"),
+            Some(UserDictFormat::Google)
+        );
+        assert_eq!(
+            UserDictFormat::from_name("atok"),
+            Some(UserDictFormat::Atok)
+        );
+        assert_eq!(UserDictFormat::from_name("skk"), None);
+    }
+
+    // === インポート ===
+
+    #[test]
+    fn import_google_utf8() {
+        let text = "# Google 日本語入力\nなかの\t中野\t地名\t東京都\nかいぎ\t会議\t名詞\n";
+        let mut ud = UserDictionary::new();
+        let report = import(&mut ud, text.as_bytes(), UserDictFormat::Google);
+        assert_eq!(report.imported, 2);
+        assert!(report.skipped.is_empty());
+        assert_eq!(ud.lookup("なかの").unwrap(), &["中野"]);
+        assert_eq!(ud.part_of_speech("なかの", "中野"), Some("地名"));
+        assert_eq!(ud.annotation("なかの", "中野"), Some("東京都"));
+    }
+
+    #[test]
+    fn import_msime_utf16_with_header() {
+        let text = "!Microsoft IME Dictionary Tool\r\n!Version:\r\n!Format:WORDLIST\r\n\r\nしゃちょう\t社長\t名詞\r\n";
+        let mut ud = UserDictionary::new();
+        let report = import(&mut ud, &utf16le_with_bom(text), UserDictFormat::MsIme);
+        assert_eq!(report.imported, 1);
+        assert!(report.skipped.is_empty());
+        assert_eq!(ud.lookup("しゃちょう").unwrap(), &["社長"]);
+    }
+
+    #[test]
+    fn import_atok_shift_jis() {
+        let text = "!!ATOK_TANGO_TEXT_HEADER_1\r\nえきまえ\t駅前\t名詞\r\n";
+        let (encoded, _, _) = encoding_rs::SHIFT_JIS.encode(text);
+        let mut ud = UserDictionary::new();
+        let report = import(&mut ud, &encoded, UserDictFormat::Atok);
+        assert_eq!(report.imported, 1);
+        assert_eq!(ud.lookup("えきまえ").unwrap(), &["駅前"]);
+    }
+
+    #[test]
+    fn import_reports_skipped_lines() {
+        let text = "かいぎ\t会議\t名詞\nこわれた行\n\t空読み\t名詞\nよ み\t読み\t名詞\n";
+        let mut ud = UserDictionary::new();
+        let report = import(&mut ud, text.as_bytes(), UserDictFormat::Google);
+        assert_eq!(report.imported, 1);
+        let lines: Vec<usize> = report.skipped.iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+        assert_eq!(report.skipped[0].content, "こわれた行");
+    }
+
+    #[test]
+    fn import_word_with_separators() {
+        let text = "えぬえー\tN/A\t名詞\nせみ\ta;b\t名詞\n";
+        let mut ud = UserDictionary::new();
+        let report = import(&mut ud, text.as_bytes(), UserDictFormat::Google);
+        assert_eq!(report.imported, 2);
+        assert!(report.skipped.is_empty());
+        assert_eq!(ud.lookup("えぬえー").unwrap(), &["N/A"]);
+        assert_eq!(ud.lookup("せみ").unwrap(), &["a;b"]);
+    }
+
+    #[test]
+    fn import_existing_word_counts_as_updated() {
+        let mut ud = UserDictionary::new();
+        ud.record("かいぎ", "会議");
+        let report = import(
+            &mut ud,
+            "かいぎ\t会議\t名詞\n".as_bytes(),
+            UserDictFormat::Google,
+        );
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.updated, 1);
+        assert_eq!(ud.part_of_speech("かいぎ", "会議"), Some("名詞"));
+    }
+
+    // === エクスポート ===
+
+    #[test]
+    fn export_google_roundtrip() {
+        let text = "なかの\t中野\t地名\t東京都\nかいぎ\t会議\t名詞\n";
+        let mut ud = UserDictionary::new();
+        import(&mut ud, text.as_bytes(), UserDictFormat::Google);
+        let (bytes, skipped) = export(&ud, UserDictFormat::Google);
+        assert!(skipped.is_empty());
+        let exported = String::from_utf8(bytes).unwrap();
+        assert_eq!(exported, "かいぎ\t会議\t名詞\nなかの\t中野\t地名\t東京都\n");
+    }
+
+    #[test]
+    fn export_msime_is_utf16_with_header() {
+        let mut ud = UserDictionary::new();
+        ud.record("かんじ", "漢字");
+        let (bytes, _) = export(&ud, UserDictFormat::MsIme);
+        assert_eq!(&bytes[..2], &[0xFF, 0xFE]);
+        let text = decode(&bytes);
+        assert!(text.starts_with("!Microsoft IME Dictionary Tool\r\n"));
+        assert!(text.ends_with("かんじ\t漢字\t名詞\r\n"));
+
+        // 書き出した内容を再度インポートできる
+        let mut other = UserDictionary::new();
+        let report = import(&mut other, &bytes, UserDictFormat::MsIme);
+        assert_eq!(report.imported, 1);
+        assert!(report.skipped.is_empty());
+    }
+
+    #[test]
+    fn export_atok_header() {
+        let ud = UserDictionary::new();
+        let text = decode(&export(&ud, UserDictFormat::Atok).0);
+        assert_eq!(text, "!!ATOK_TANGO_TEXT_HEADER_1\r\n\r\n");
+    }
+
+    #[test]
+    fn export_skips_okuri_ari_and_abbrev_readings() {
+        let mut ud = UserDictionary::new();
+        ud.record("かんじ", "漢字");
+        ud.record("おおきi", "大き");
+        ud.record("tel", "電話");
+        let (bytes, skipped) = export(&ud, UserDictFormat::Google);
+        assert_eq!(String::from_utf8(bytes).unwrap(), "かんじ\t漢字\t名詞\n");
+        let readings: Vec<&str> = skipped.iter().map(|s| s.word.reading.as_str()).collect();
+        assert_eq!(readings, vec!["tel", "おおきi"]);
+        assert_eq!(skipped[1].reason, "送りありの読みは他の IME で使えません");
+    }
+}
@@ -1,41 +1,163 @@
 //! ユーザー辞書の管理。
 //!
 //! ユーザーが変換で確定した結果を学習し、次回以降の変換で
-//! 候補の優先順位を変更する。SKK 形式で保存・読み込みする。
+//! 候補の優先順位を変更する。SKK の個人辞書 (`~/.skk-jisyo`) と
+//! 互換の形式で保存・読み込みする。
 
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::ops::Bound;
 use std::path::Path;
 
-use crate::dictionary::DictionaryError;
+use crate::dictionary::{self, DictEncoding, DictionaryError, parse_skk_candidates};
+
+/// 送りありエントリの開始を示す SKK のセクション行。
+const OKURI_ARI_MARKER: &str = ";; okuri-ari entries.";
+/// 送りなしエントリの開始を示す SKK のセクション行。
+const OKURI_NASI_MARKER: &str = ";; okuri-nasi entries.";
+/// japinput 独自の付加情報（品詞など）を保存するコメント行の接頭辞。
+///
+/// SKK のコメント行として書き出すため、他の SKK 実装からは無視される。
+/// 形式: `;; japinput:<種別>\t<フィールド>\t...`
+const META_PREFIX: &str = ";; japinput:";
+
+/// 1つの単語（読みと候補の組）と付加情報。
+///
+/// 他の IME の辞書形式との相互変換に使う。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserWord {
+    pub reading: String,
+    pub word: String,
+    /// 品詞（"名詞", "固有名詞" など。書式はインポート元の IME に依存）
+    pub part_of_speech: Option<String>,
+    /// コメント。SKK の注釈として保存する。
+    pub comment: Option<String>,
+}
+
+/// 1つの単語に続く単語として記録する数の上限。
+const MAX_SUCCESSORS: usize = 16;
+
+/// 直前に確定した単語に続いて確定した単語（bigram）の記録。
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Successor {
+    word: String,
+    count: u32,
+    last_used: u64,
+}
+
+/// 学習を取り消すための、変更前のエントリと bigram の記録。
+///
+/// `begin_checkpoint` から `finish_checkpoint` までの学習で変更された読み・
+/// 直前の単語ごとに、最初に変更される前の内容を保持する。
+#[derive(Debug, Clone, Default)]
+pub struct Checkpoint {
+    entries: HashMap<String, Option<UserEntry>>,
+    bigrams: HashMap<String, Option<Vec<Successor>>>,
+}
+
+/// ユーザー辞書の大きさの上限。`None` は無制限。
+///
+/// 上限は単語を記録したときに適用し、読み込んだ辞書を後から削ることはしない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UserDictLimits {
+    /// 1つの読みあたりの候補数の上限
+    pub max_candidates_per_reading: Option<usize>,
+    /// 読みの総数の上限。文脈の学習 (bigram) で記録する直前の単語の数にも同じ上限を適用する。
+    pub max_readings: Option<usize>,
+}
+
+/// 1つの読みに対応するユーザー辞書エントリ。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct UserEntry {
+    /// 候補。先頭が最も優先度の高い候補。
+    candidates: Vec<String>,
+    /// 候補ごとの注釈（SKK の `/候補;注釈/`）。
+    annotations: HashMap<String, String>,
+    /// 候補ごとの品詞。
+    parts_of_speech: HashMap<String, String>,
+    /// 送り仮名ごとのサブエントリ（SKK の `[る/来/]`）。
+    okuri_blocks: Vec<(String, Vec<String>)>,
+    /// 固定された候補。上限を超えても削除されない。
+    pinned: HashSet<String>,
+    /// 送りありセクションのエントリかどうか。
+    okuri_ari: bool,
+    /// 最後に使われた時刻（単調増加カウンタ）。大きいほど新しい。
+    last_used: u64,
+}
 
 /// ユーザー辞書。確定結果を学習し、候補の優先順位を変更する。
 ///
-/// SKK 形式で保存・読み込みする。
-/// エントリは HashMap<読み, Vec<候補>> で管理し、
-/// Vec の先頭が最も優先度の高い候補。
+/// SKK の個人辞書形式で保存・読み込みする。
+/// 送りあり・送りなしのセクション、`[く/来/]` 形式の送り仮名ブロック、
+/// 注釈、EUC-JP / UTF-8 の文字コードに対応する。
+/// 保存時は各セクション内で最近使ったエントリから順に書き出す。
+///
+/// 上限 (`UserDictLimits`) を設定すると、超過時に最も長く使われていない
+/// 候補・読みから削除する。固定 (`pin`) された候補は削除しない。
 pub struct UserDictionary {
-    entries: HashMap<String, Vec<String>>,
+    /// 読み → エントリ。前方一致検索のため読みの順に並べる。
+    entries: BTreeMap<String, UserEntry>,
+    /// 直前の単語 → 続いて確定した単語（文脈による候補の優先に使う）
+    bigrams: HashMap<String, Vec<Successor>>,
+    limits: UserDictLimits,
+    /// 読み込んだファイルの文字コード。保存時も同じ文字コードを使う。
+    encoding: DictEncoding,
+    /// 最近使った順を管理するためのカウンタ。
+    clock: u64,
     dirty: bool,
+    /// 記録中のチェックポイント（`begin_checkpoint` から `finish_checkpoint` まで）
+    checkpoint: Option<Checkpoint>,
 }
 
 impl UserDictionary {
     /// 空のユーザー辞書を作成する。
     pub fn new() -> Self {
         Self {
-            entries: HashMap::new(),
+            entries: BTreeMap::new(),
+            bigrams: HashMap::new(),
+            limits: UserDictLimits::default(),
+            encoding: DictEncoding::Utf8,
+            clock: 0,
             dirty: false,
+            checkpoint: None,
         }
     }
 
     /// ファイルからユーザー辞書を読み込む。ファイルが存在しない場合は空の辞書を返す。
+    ///
+    /// UTF-8 / EUC-JP を自動判定する。ファイル内の並び順を新しい順とみなす。
     pub fn load(path: &Path) -> Result<Self, DictionaryError> {
         if !path.exists() {
             return Ok(Self::new());
         }
-        let text = std::fs::read_to_string(path)?;
-        let mut entries = HashMap::new();
-        for line in text.lines() {
-            let line = line.trim();
+        let bytes = std::fs::read(path)?;
+        let (text, encoding) = DictEncoding::detect_and_decode(&bytes);
+        let mut ud = Self::parse(&text);
+        ud.encoding = encoding;
+        Ok(ud)
+    }
+
+    /// SKK 個人辞書のテキストをパースする。
+    fn parse(text: &str) -> Self {
+        let mut ud = Self::new();
+        // セクション行がない古い形式では、読みの形から送りあり/なしを判定する
+        let mut section: Option<bool> = None;
+        let mut meta_lines: Vec<&str> = Vec::new();
+        let lines: Vec<&str> = text.lines().map(|l| l.trim()).collect();
+        let total = lines.len() as u64;
+        for (i, line) in lines.into_iter().enumerate() {
+            if line == OKURI_ARI_MARKER {
+                section = Some(true);
+                continue;
+            }
+            if line == OKURI_NASI_MARKER {
+                section = Some(false);
+                continue;
+            }
+            if let Some(meta) = line.strip_prefix(META_PREFIX) {
+                meta_lines.push(meta);
+                continue;
+            }
             if line.is_empty() || line.starts_with(';') {
                 continue;
             }
@@ -43,59 +165,569 @@ impl UserDictionary {
                 continue;
             };
             let reading = &line[..split_pos];
-            let rest = line[split_pos..].trim_start();
-            let candidates: Vec<String> = rest
-                .split('/')
-                .filter(|s| !s.is_empty())
-                .map(|s| s.to_string())
-                .collect();
-            if !candidates.is_empty() {
-                entries.insert(reading.to_string(), candidates);
+            let parsed = parse_skk_candidates(&line[split_pos..]);
+            if parsed.candidates.is_empty() {
+                continue;
+            }
+            let mut entry = UserEntry {
+                okuri_ari: section.unwrap_or_else(|| is_okuri_ari_reading(reading)),
+                // 先頭の行ほど新しい
+                last_used: total - i as u64,
+                okuri_blocks: parsed.okuri_blocks,
+                ..UserEntry::default()
+            };
+            for (word, annotation) in parsed.candidates {
+                if entry.candidates.contains(&word) {
+                    continue;
+                }
+                if let Some(annotation) = annotation {
+                    entry.annotations.insert(word.clone(), annotation);
+                }
+                entry.candidates.push(word);
             }
+            // 同じ読みが複数回現れた場合は先に現れた（新しい）方を優先する
+            ud.entries.entry(reading.to_string()).or_insert(entry);
         }
-        Ok(Self {
-            entries,
-            dirty: false,
-        })
+        ud.clock = total;
+        for meta in meta_lines {
+            ud.apply_meta_line(meta);
+        }
+        ud
+    }
+
+    /// 付加情報の行（`META_PREFIX` 以降）を適用する。未知の種別は無視する。
+    fn apply_meta_line(&mut self, meta: &str) {
+        let fields: Vec<&str> = meta.split('\t').collect();
+        match fields.as_slice() {
+            ["pos", reading, word, pos] => {
+                if let Some(entry) = self.entry_with_word(reading, word) {
+                    entry
+                        .parts_of_speech
+                        .insert(word.to_string(), pos.to_string());
+                }
+            }
+            ["pin", reading, word] => {
+                if let Some(entry) = self.entry_with_word(reading, word) {
+                    entry.pinned.insert(word.to_string());
+                }
+            }
+            ["bigram", prev, word, count] => {
+                if let Ok(count) = count.parse::<u32>() {
+                    self.clock += 1;
+                    let successors = self.bigrams.entry(prev.to_string()).or_default();
+                    successors.push(Successor {
+                        word: word.to_string(),
+                        count,
+                        last_used: self.clock,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 指定の候補を含むエントリを返す。
+    fn entry_with_word(&mut self, reading: &str, word: &str) -> Option<&mut UserEntry> {
+        self.entries
+            .get_mut(reading)
+            .filter(|e| e.candidates.iter().any(|c| c == word))
+    }
+
+    /// 付加情報の行を生成する。
+    fn meta_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (reading, entry) in &self.entries {
+            for (word, pos) in &entry.parts_of_speech {
+                lines.push(format!("{META_PREFIX}pos\t{reading}\t{word}\t{pos}"));
+            }
+            for word in &entry.pinned {
+                lines.push(format!("{META_PREFIX}pin\t{reading}\t{word}"));
+            }
+        }
+        lines.sort();
+        // bigram は読み込み時に記録順を復元できるよう、古い順に書き出す
+        let mut bigrams: Vec<(&String, &Successor)> = self
+            .bigrams
+            .iter()
+            .flat_map(|(prev, ss)| ss.iter().map(move |s| (prev, s)))
+            .collect();
+        bigrams.sort_by(|a, b| a.1.last_used.cmp(&b.1.last_used).then(a.0.cmp(b.0)));
+        for (prev, s) in bigrams {
+            lines.push(format!(
+                "{META_PREFIX}bigram\t{prev}\t{}\t{}",
+                s.word, s.count
+            ));
+        }
+        lines
     }
 
     /// ユーザー辞書をファイルに保存する。
+    ///
+    /// 送りあり・送りなしのセクションに分け、それぞれ最近使った順に書き出す。
+    /// 読み込んだときの文字コードで表せない候補（EUC-JP の辞書に絵文字など）があれば、
+    /// coding 宣言ごと UTF-8 に切り替えて保存する。
     pub fn save(&mut self, path: &Path) -> Result<(), DictionaryError> {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
+        let bytes = match self.encoding.encode(&self.to_skk_text()) {
+            Some(bytes) => bytes,
+            None => {
+                self.encoding = DictEncoding::Utf8;
+                self.to_skk_text().into_bytes()
+            }
+        };
+        std::fs::write(path, bytes)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// SKK 個人辞書形式のテキストを生成する。
+    fn to_skk_text(&self) -> String {
         let mut lines: Vec<String> = Vec::new();
-        lines.push(";; japinput ユーザー辞書".to_string());
-        let mut sorted_keys: Vec<&String> = self.entries.keys().collect();
-        sorted_keys.sort();
-        for reading in sorted_keys {
-            let candidates = &self.entries[reading];
-            let cands = candidates
+        lines.push(format!(
+            ";; -*- mode: fundamental; coding: {} -*-",
+            self.encoding.coding_name()
+        ));
+        for (marker, okuri_ari) in [(OKURI_ARI_MARKER, true), (OKURI_NASI_MARKER, false)] {
+            lines.push(marker.to_string());
+            let mut readings: Vec<(&String, &UserEntry)> = self
+                .entries
                 .iter()
-                .map(|s| s.as_str())
-                .collect::<Vec<_>>()
-                .join("/");
-            lines.push(format!("{reading} /{cands}/"));
+                .filter(|(_, e)| e.okuri_ari == okuri_ari && !e.candidates.is_empty())
+                .collect();
+            // 新しい順。同時刻の場合は読みで安定させる
+            readings.sort_by(|a, b| b.1.last_used.cmp(&a.1.last_used).then(a.0.cmp(b.0)));
+            for (reading, entry) in readings {
+                lines.push(format!("{reading} {}", format_entry(entry)));
+            }
         }
-        std::fs::write(path, lines.join("\n") + "\n")?;
-        self.dirty = false;
-        Ok(())
+        lines.extend(self.meta_lines());
+        lines.join("\n") + "\n"
     }
 
     /// 学習: 読みと候補を記録する。既存エントリの場合は先頭に移動（優先度上げ）。
     pub fn record(&mut self, reading: &str, candidate: &str) {
-        let entry = self.entries.entry(reading.to_string()).or_default();
-        entry.retain(|c| c != candidate);
-        entry.insert(0, candidate.to_string());
+        self.save_entry(reading);
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self
+            .entries
+            .entry(reading.to_string())
+            .or_insert_with(|| UserEntry {
+                okuri_ari: is_okuri_ari_reading(reading),
+                ..UserEntry::default()
+            });
+        entry.candidates.retain(|c| c != candidate);
+        entry.candidates.insert(0, candidate.to_string());
+        entry.last_used = clock;
         self.dirty = true;
+        self.enforce_limits_for(reading);
+    }
+
+    /// 送りありの学習: 読み (`くr`)・送り仮名 (`る`)・候補 (`来`) を記録する。
+    ///
+    /// 候補は読みの候補と送り仮名ブロックの両方で先頭に移動する。
+    pub fn record_okuri(&mut self, reading: &str, okuri: &str, candidate: &str) {
+        self.record(reading, candidate);
+        let Some(entry) = self.entries.get_mut(reading) else {
+            return;
+        };
+        entry.okuri_ari = true;
+        let pos = entry.okuri_blocks.iter().position(|(o, _)| o == okuri);
+        let mut block = match pos {
+            Some(pos) => entry.okuri_blocks.remove(pos),
+            None => (okuri.to_string(), Vec::new()),
+        };
+        block.1.retain(|c| c != candidate);
+        block.1.insert(0, candidate.to_string());
+        entry.okuri_blocks.insert(0, block);
+    }
+
+    /// 単語を登録する（他の IME の辞書からのインポート用）。
+    ///
+    /// 既に学習済みの候補の順位は変えず、未登録の候補は末尾に追加する。
+    /// 品詞とコメントは付加情報として保持する。新たに追加した場合は true を返す。
+    pub fn add_word(&mut self, word: &UserWord) -> bool {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self
+            .entries
+            .entry(word.reading.clone())
+            .or_insert_with(|| UserEntry {
+                okuri_ari: is_okuri_ari_reading(&word.reading),
+                last_used: clock,
+                ..UserEntry::default()
+            });
+        let added = !entry.candidates.contains(&word.word);
+        if added {
+            entry.candidates.push(word.word.clone());
+        }
+        if let Some(ref pos) = word.part_of_speech {
+            entry.parts_of_speech.insert(word.word.clone(), pos.clone());
+        }
+        if let Some(ref comment) = word.comment {
+            entry.annotations.insert(word.word.clone(), comment.clone());
+        }
+        self.dirty = true;
+        self.enforce_limits_for(&word.reading);
+        added
+    }
+
+    /// 登録されている全単語を読み順に返す（他の IME 形式へのエクスポート用）。
+    ///
+    /// 同じ読みの候補は優先度の高い順に並ぶ。
+    pub fn words(&self) -> Vec<UserWord> {
+        let mut readings: Vec<&String> = self.entries.keys().collect();
+        readings.sort();
+        let mut words = Vec::new();
+        for reading in readings {
+            let entry = &self.entries[reading];
+            for c in &entry.candidates {
+                words.push(UserWord {
+                    reading: reading.clone(),
+                    word: c.clone(),
+                    part_of_speech: entry.parts_of_speech.get(c).cloned(),
+                    comment: entry.annotations.get(c).cloned(),
+                });
+            }
+        }
+        words
+    }
+
+    /// 候補の品詞を返す。
+    pub fn part_of_speech(&self, reading: &str, candidate: &str) -> Option<&str> {
+        self.entries
+            .get(reading)?
+            .parts_of_speech
+            .get(candidate)
+            .map(|s| s.as_str())
     }
 
     /// 読みから候補を検索する。
     pub fn lookup(&self, reading: &str) -> Option<&[String]> {
         self.entries
             .get(reading)
+            .map(|e| e.candidates.as_slice())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// 前方一致検索。プレフィクスで始まる送りなしの読みとその候補を、
+    /// 最近使った順に返す。
+    pub fn lookup_prefix(&self, prefix: &str) -> Vec<(&str, &[String])> {
+        self.lookup_prefix_with_limit(prefix, usize::MAX)
+    }
+
+    /// 前方一致検索。最近使った順に最大 `limit` 件までを返す。
+    ///
+    /// プレフィクスに一致する範囲だけを走査し、上位 `limit` 件だけを保持する。
+    pub fn lookup_prefix_with_limit(&self, prefix: &str, limit: usize) -> Vec<(&str, &[String])> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        let matches = match dictionary::prefix_end_bound(prefix) {
+            Some(end) => self
+                .entries
+                .range::<str, _>((Bound::Included(prefix), Bound::Excluded(end.as_str()))),
+            None => self
+                .entries
+                .range::<str, _>((Bound::Included(prefix), Bound::Unbounded)),
+        }
+        .filter(|(reading, e)| {
+            reading.starts_with(prefix) && !e.okuri_ari && !e.candidates.is_empty()
+        });
+        // 最も古いものを先頭に置くヒープで、新しい順の上位 limit 件を残す
+        let mut top: BinaryHeap<(Reverse<u64>, &String)> = BinaryHeap::new();
+        for (reading, e) in matches {
+            top.push((Reverse(e.last_used), reading));
+            if top.len() > limit {
+                top.pop();
+            }
+        }
+        top.into_sorted_vec()
+            .into_iter()
+            .map(|(_, reading)| {
+                let e = &self.entries[reading];
+                (reading.as_str(), e.candidates.as_slice())
+            })
+            .collect()
+    }
+
+    /// 送りありの読みと送り仮名から候補を検索する。
+    pub fn lookup_okuri(&self, reading: &str, okuri: &str) -> Option<&[String]> {
+        self.entries
+            .get(reading)?
+            .okuri_blocks
+            .iter()
+            .find(|(o, _)| o == okuri)
+            .map(|(_, c)| c.as_slice())
             .filter(|v| !v.is_empty())
-            .map(|v| v.as_slice())
+    }
+
+    /// 候補の注釈を返す。
+    pub fn annotation(&self, reading: &str, candidate: &str) -> Option<&str> {
+        self.entries
+            .get(reading)?
+            .annotations
+            .get(candidate)
+            .map(|s| s.as_str())
+    }
+
+    /// 文脈の学習: 直前に確定した単語 `prev` に続いて `word` を確定したことを記録する。
+    ///
+    /// タブ・改行を含む単語は付加情報の行に書き出せないため記録しない。
+    pub fn record_bigram(&mut self, prev: &str, word: &str) {
+        let storable = |s: &str| !s.is_empty() && !s.contains(['\t', '\n', '\r']);
+        if !storable(prev) || !storable(word) {
+            return;
+        }
+        self.save_bigrams(prev);
+        self.clock += 1;
+        let clock = self.clock;
+        let successors = self.bigrams.entry(prev.to_string()).or_default();
+        match successors.iter_mut().find(|s| s.word == word) {
+            Some(s) => {
+                s.count = s.count.saturating_add(1);
+                s.last_used = clock;
+            }
+            None => successors.push(Successor {
+                word: word.to_string(),
+                count: 1,
+                last_used: clock,
+            }),
+        }
+        if successors.len() > MAX_SUCCESSORS {
+            // 最も長く使われていないものから削除
+            successors.sort_by_key(|s| std::cmp::Reverse(s.last_used));
+            successors.truncate(MAX_SUCCESSORS);
+        }
+        if let Some(max) = self.limits.max_readings {
+            while self.bigrams.len() > max {
+                let oldest = self
+                    .bigrams
+                    .iter()
+                    .filter(|(p, _)| p.as_str() != prev)
+                    .min_by_key(|(_, ss)| ss.iter().map(|s| s.last_used).max().unwrap_or(0))
+                    .map(|(p, _)| p.clone());
+                match oldest {
+                    Some(p) => {
+                        self.save_bigrams(&p);
+                        self.bigrams.remove(&p)
+                    }
+                    None => break,
+                };
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// `prev` に続いて `word` を確定した回数を返す。
+    pub fn bigram_count(&self, prev: &str, word: &str) -> u32 {
+        self.bigrams
+            .get(prev)
+            .and_then(|ss| ss.iter().find(|s| s.word == word))
+            .map_or(0, |s| s.count)
+    }
+
+    /// `prev` に続いて確定した単語を、回数の多い順（同数なら新しい順）に返す。
+    pub fn successors(&self, prev: &str) -> Vec<&str> {
+        let Some(ss) = self.bigrams.get(prev) else {
+            return Vec::new();
+        };
+        let mut sorted: Vec<&Successor> = ss.iter().collect();
+        sorted.sort_by(|a, b| b.count.cmp(&a.count).then(b.last_used.cmp(&a.last_used)));
+        sorted.into_iter().map(|s| s.word.as_str()).collect()
+    }
+
+    /// 候補を固定する。固定された候補は上限を超えても削除されない。
+    ///
+    /// 候補が未登録の場合は末尾に登録してから固定する。
+    pub fn pin(&mut self, reading: &str, candidate: &str) {
+        // 上限に達していても追い出されないよう、候補を追加する前に固定する
+        let clock = self.clock;
+        self.entries
+            .entry(reading.to_string())
+            .or_insert_with(|| UserEntry {
+                okuri_ari: is_okuri_ari_reading(reading),
+                last_used: clock,
+                ..UserEntry::default()
+            })
+            .pinned
+            .insert(candidate.to_string());
+        self.dirty = true;
+        if self.entry_with_word(reading, candidate).is_none() {
+            self.add_word(&UserWord {
+                reading: reading.to_string(),
+                word: candidate.to_string(),
+                part_of_speech: None,
+                comment: None,
+            });
+        }
+    }
+
+    /// 候補の固定を解除する。
+    pub fn unpin(&mut self, reading: &str, candidate: &str) {
+        if let Some(entry) = self.entries.get_mut(reading)
+            && entry.pinned.remove(candidate)
+        {
+            self.dirty = true;
+        }
+    }
+
+    /// 候補が固定されているかどうか。
+    pub fn is_pinned(&self, reading: &str, candidate: &str) -> bool {
+        self.entries
+            .get(reading)
+            .is_some_and(|e| e.pinned.contains(candidate))
+    }
+
+    /// 上限を設定する。
+    ///
+    /// 既に登録されている単語はこの時点では削除せず、以降に記録したときに上限を適用する。
+    /// 既存の辞書を読み込んだ直後に設定しても、そのまま保存して内容が失われることはない。
+    pub fn set_limits(&mut self, limits: UserDictLimits) {
+        self.limits = limits;
+    }
+
+    /// 現在の上限を返す。
+    pub fn limits(&self) -> UserDictLimits {
+        self.limits
+    }
+
+    /// 登録されている読みの数を返す。
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 読みが1つも登録されていないかどうか。
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 記録した読みについて上限を適用する。記録した読み自体は削除しない。
+    fn enforce_limits_for(&mut self, reading: &str) {
+        self.evict_candidates(reading);
+        while self.evict_oldest_reading(Some(reading)) {}
+    }
+
+    /// 読みの候補数が上限を超えていれば、末尾（最も古い）の固定されていない候補から削除する。
+    fn evict_candidates(&mut self, reading: &str) {
+        let Some(max) = self.limits.max_candidates_per_reading else {
+            return;
+        };
+        let Some(entry) = self.entries.get_mut(reading) else {
+            return;
+        };
+        let mut i = entry.candidates.len();
+        while entry.candidates.len() > max && i > 0 {
+            i -= 1;
+            if entry.pinned.contains(&entry.candidates[i]) {
+                continue;
+            }
+            let removed = entry.candidates.remove(i);
+            entry.annotations.remove(&removed);
+            entry.parts_of_speech.remove(&removed);
+            for (_, cands) in entry.okuri_blocks.iter_mut() {
+                cands.retain(|c| *c != removed);
+            }
+            entry.okuri_blocks.retain(|(_, cands)| !cands.is_empty());
+            self.dirty = true;
+        }
+    }
+
+    /// 読みの総数が上限を超えていれば、最も長く使われていない読みを1つ削除する。
+    ///
+    /// 固定された候補を持つ読みと `keep` は削除しない。削除した場合は true を返す。
+    fn evict_oldest_reading(&mut self, keep: Option<&str>) -> bool {
+        let Some(max) = self.limits.max_readings else {
+            return false;
+        };
+        if self.entries.len() <= max {
+            return false;
+        }
+        let oldest = self
+            .entries
+            .iter()
+            .filter(|(r, e)| e.pinned.is_empty() && Some(r.as_str()) != keep)
+            .min_by(|a, b| a.1.last_used.cmp(&b.1.last_used).then(a.0.cmp(b.0)))
+            .map(|(r, _)| r.clone());
+        match oldest {
+            Some(reading) => {
+                self.save_entry(&reading);
+                self.entries.remove(&reading);
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 学習を取り消せるよう、これ以降の変更の記録を開始する。
+    ///
+    /// 記録中のチェックポイントがあれば破棄して新しく始める。
+    pub fn begin_checkpoint(&mut self) {
+        self.checkpoint = Some(Checkpoint::default());
+    }
+
+    /// 変更の記録を終了し、`begin_checkpoint` 以降の変更を取り消すための記録を返す。
+    pub fn finish_checkpoint(&mut self) -> Option<Checkpoint> {
+        self.checkpoint.take()
+    }
+
+    /// チェックポイントまで学習を取り消す。
+    ///
+    /// 記録されたエントリと bigram を変更前の内容に戻す。
+    /// 最近使った順を管理するカウンタは戻さない。
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        if checkpoint.entries.is_empty() && checkpoint.bigrams.is_empty() {
+            return;
+        }
+        for (reading, entry) in checkpoint.entries {
+            match entry {
+                Some(entry) => self.entries.insert(reading, entry),
+                None => self.entries.remove(&reading),
+            };
+        }
+        for (prev, successors) in checkpoint.bigrams {
+            match successors {
+                Some(successors) => self.bigrams.insert(prev, successors),
+                None => self.bigrams.remove(&prev),
+            };
+        }
+        self.dirty = true;
+    }
+
+    /// 記録中のチェックポイントに、変更前の読みのエントリを保存する（最初の変更時のみ）。
+    fn save_entry(&mut self, reading: &str) {
+        if let Some(ref mut cp) = self.checkpoint
+            && !cp.entries.contains_key(reading)
+        {
+            cp.entries
+                .insert(reading.to_string(), self.entries.get(reading).cloned());
+        }
+    }
+
+    /// 記録中のチェックポイントに、変更前の `prev` に続く単語を保存する（最初の変更時のみ）。
+    fn save_bigrams(&mut self, prev: &str) {
+        if let Some(ref mut cp) = self.checkpoint
+            && !cp.bigrams.contains_key(prev)
+        {
+            cp.bigrams
+                .insert(prev.to_string(), self.bigrams.get(prev).cloned());
+        }
+    }
+
+    /// 保存時に使う文字コードを返す。
+    pub fn encoding(&self) -> DictEncoding {
+        self.encoding
+    }
+
+    /// 保存時に使う文字コードを設定する。
+    pub fn set_encoding(&mut self, encoding: DictEncoding) {
+        if self.encoding != encoding {
+            self.encoding = encoding;
+            self.dirty = true;
+        }
     }
 
     /// 保存が必要かどうか。
@@ -110,6 +742,44 @@ impl Default for UserDictionary {
     }
 }
 
+/// SKK の規則で送りありの読みかどうかを判定する。
+///
+/// 読みが非 ASCII 文字で始まり ASCII 小文字で終わる場合（`くr` など）は送りあり。
+/// `tel` のような ASCII のみの読み（abbrev）は送りなし。
+fn is_okuri_ari_reading(reading: &str) -> bool {
+    let mut chars = reading.chars();
+    match (chars.next(), reading.chars().last()) {
+        (Some(first), Some(last)) => !first.is_ascii() && last.is_ascii_lowercase(),
+        _ => false,
+    }
+}
+
+/// エントリの候補部分を SKK 形式 (`/候補;注釈/[送り/候補/]/`) で書き出す。
+///
+/// 候補・注釈に区切り文字の `/` `;` があれば `(concat "...")` 形式にする。
+fn format_entry(entry: &UserEntry) -> String {
+    let mut out = String::from("/");
+    for c in &entry.candidates {
+        out.push_str(&dictionary::escape(c));
+        if let Some(annotation) = entry.annotations.get(c) {
+            out.push(';');
+            out.push_str(&dictionary::escape(annotation));
+        }
+        out.push('/');
+    }
+    for (okuri, cands) in &entry.okuri_blocks {
+        out.push('[');
+        out.push_str(okuri);
+        out.push('/');
+        for c in cands {
+            out.push_str(&dictionary::escape(c));
+            out.push('/');
+        }
+        out.push_str("]/");
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,6 +811,34 @@ mod tests {
         assert_eq!(result, &["感じ", "漢字"]);
     }
 
+    // === 前方一致検索 ===
+
+    #[test]
+    fn lookup_prefix_most_recent_first() {
+        let mut ud = UserDictionary::new();
+        ud.record("とうきょうと", "東京都");
+        ud.record("とうきょう", "東京");
+        ud.record("かんじ", "漢字");
+        ud.record_okuri("とうr", "る", "通");
+        let result = ud.lookup_prefix("とう");
+        let readings: Vec<&str> = result.iter().map(|(r, _)| *r).collect();
+        assert_eq!(readings, vec!["とうきょう", "とうきょうと"]);
+        assert_eq!(result[0].1, &["東京"]);
+    }
+
+    #[test]
+    fn lookup_prefix_with_limit_keeps_most_recent() {
+        let mut ud = UserDictionary::new();
+        ud.record("とうきょうと", "東京都");
+        ud.record("とうきょう", "東京");
+        ud.record("とうほく", "東北");
+        ud.record("かんじ", "漢字");
+        let result = ud.lookup_prefix_with_limit("とう", 2);
+        let readings: Vec<&str> = result.iter().map(|(r, _)| *r).collect();
+        assert_eq!(readings, vec!["とうほく", "とうきょう"]);
+        assert!(ud.lookup_prefix_with_limit("とう", 0).is_empty());
+    }
+
     // === 学習（優先度変更） ===
 
     #[test]
@@ -215,4 +913,386 @@ mod tests {
         let ud = UserDictionary::new();
         assert!(ud.lookup("そんざいしない").is_none());
     }
+
+    // === SKK 個人辞書互換 ===
+
+    const SKK_JISYO: &str = ";; -*- mode: fundamental; coding: euc-jp -*-
+;; okuri-ari entries.
+くr /来/[る/来/]/[れ/来/]/
+おおきi /大き/[い/大き/]/
+;; okuri-nasi entries.
+かんじ /幹事;会の世話役/漢字/
+にほん /日本/
+";
+
+    fn write_eucjp(name: &str, text: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join("japinput_test_ud");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let (encoded, _, _) = encoding_rs::EUC_JP.encode(text);
+        std::fs::write(&path, &*encoded).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_skk_jisyo_eucjp() {
+        let path = write_eucjp("skk_jisyo_load.txt", SKK_JISYO);
+        let ud = UserDictionary::load(&path).unwrap();
+        assert_eq!(ud.encoding(), DictEncoding::EucJp);
+        // 注釈は候補から除去される
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["幹事", "漢字"]);
+        assert_eq!(ud.annotation("かんじ", "幹事"), Some("会の世話役"));
+        // 送りありブロックは候補に混ざらない
+        assert_eq!(ud.lookup("くr").unwrap(), &["来"]);
+        assert_eq!(ud.lookup_okuri("くr", "る").unwrap(), &["来"]);
+        assert_eq!(ud.lookup_okuri("くr", "れ").unwrap(), &["来"]);
+        assert!(ud.lookup_okuri("くr", "き").is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn skk_jisyo_roundtrip_keeps_sections_and_encoding() {
+        let path = write_eucjp("skk_jisyo_roundtrip.txt", SKK_JISYO);
+        let mut ud = UserDictionary::load(&path).unwrap();
+        ud.save(&path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(
+            std::str::from_utf8(&bytes).is_err(),
+            "EUC-JP のまま保存される"
+        );
+        let (text, _, _) = encoding_rs::EUC_JP.decode(&bytes);
+        assert_eq!(text, SKK_JISYO);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn eucjp_falls_back_to_utf8_for_unmappable_candidates() {
+        let path = write_eucjp("skk_jisyo_emoji.txt", SKK_JISYO);
+        let mut ud = UserDictionary::load(&path).unwrap();
+        ud.record("にこにこ", "😊");
+        ud.save(&path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let text = std::str::from_utf8(&bytes).expect("UTF-8 で保存される");
+        assert!(text.starts_with(";; -*- mode: fundamental; coding: utf-8 -*-"));
+        let reloaded = UserDictionary::load(&path).unwrap();
+        assert_eq!(reloaded.encoding(), DictEncoding::Utf8);
+        assert_eq!(reloaded.lookup("にこにこ").unwrap(), &["😊"]);
+        assert_eq!(reloaded.lookup("かんじ").unwrap(), &["幹事", "漢字"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn save_orders_entries_by_recency() {
+        let mut ud = UserDictionary::parse(SKK_JISYO);
+        ud.record("にほん", "二本");
+        ud.record_okuri("おおきi", "く", "大き");
+        let text = ud.to_skk_text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], OKURI_ARI_MARKER);
+        assert_eq!(lines[2], "おおきi /大き/[く/大き/]/[い/大き/]/");
+        assert_eq!(lines[3], "くr /来/[る/来/]/[れ/来/]/");
+        assert_eq!(lines[4], OKURI_NASI_MARKER);
+        assert_eq!(lines[5], "にほん /二本/日本/");
+        assert_eq!(lines[6], "かんじ /幹事;会の世話役/漢字/");
+    }
+
+    #[test]
+    fn record_new_okuri_reading_goes_to_okuri_ari_section() {
+        let mut ud = UserDictionary::new();
+        ud.record("かんじ", "漢字");
+        ud.record("はしr", "走");
+        ud.record("tel", "℡");
+        let text = ud.to_skk_text();
+        let ari = text.find(OKURI_ARI_MARKER).unwrap();
+        let nasi = text.find(OKURI_NASI_MARKER).unwrap();
+        let hashi = text.find("はしr ").unwrap();
+        assert!(ari < hashi && hashi < nasi);
+        // ASCII のみの読み (abbrev) は送りなし
+        assert!(text.find("tel ").unwrap() > nasi);
+        assert!(text.find("かんじ ").unwrap() > nasi);
+    }
+
+    #[test]
+    fn load_legacy_file_without_sections() {
+        // セクション行のない旧形式（UTF-8）も読める
+        let ud = UserDictionary::parse(";; japinput ユーザー辞書\nかんじ /感じ/漢字/\n");
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["感じ", "漢字"]);
+        assert_eq!(ud.encoding(), DictEncoding::Utf8);
+    }
+
+    // === 単語登録・付加情報 ===
+
+    fn word(reading: &str, word: &str, pos: Option<&str>) -> UserWord {
+        UserWord {
+            reading: reading.to_string(),
+            word: word.to_string(),
+            part_of_speech: pos.map(|p| p.to_string()),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn add_word_keeps_learned_order() {
+        let mut ud = UserDictionary::new();
+        ud.record("かんじ", "感じ");
+        assert!(ud.add_word(&word("かんじ", "漢字", Some("名詞"))));
+        assert!(!ud.add_word(&word("かんじ", "感じ", Some("形容詞"))));
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["感じ", "漢字"]);
+        assert_eq!(ud.part_of_speech("かんじ", "漢字"), Some("名詞"));
+        assert_eq!(ud.part_of_speech("かんじ", "感じ"), Some("形容詞"));
+    }
+
+    #[test]
+    fn part_of_speech_survives_save_and_load() {
+        let mut ud = UserDictionary::new();
+        ud.add_word(&word("なかの", "中野", Some("地名")));
+        let loaded = UserDictionary::parse(&ud.to_skk_text());
+        assert_eq!(loaded.part_of_speech("なかの", "中野"), Some("地名"));
+        assert_eq!(loaded.lookup("なかの").unwrap(), &["中野"]);
+    }
+
+    #[test]
+    fn words_lists_all_entries_with_metadata() {
+        let mut ud = UserDictionary::new();
+        ud.add_word(&UserWord {
+            comment: Some("東京都".to_string()),
+            ..word("なかの", "中野", Some("地名"))
+        });
+        ud.record("かんじ", "漢字");
+        let words = ud.words();
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0], word("かんじ", "漢字", None));
+        assert_eq!(words[1].comment.as_deref(), Some("東京都"));
+        assert_eq!(words[1].part_of_speech.as_deref(), Some("地名"));
+    }
+
+    #[test]
+    fn comment_with_separators_roundtrips() {
+        let mut ud = UserDictionary::new();
+        ud.add_word(&UserWord {
+            comment: Some("東京都/中野区;駅".to_string()),
+            ..word("なかの", "中野", None)
+        });
+        let text = ud.to_skk_text();
+        assert!(text.contains(r#"なかの /中野;(concat "東京都\057中野区\073駅")/"#));
+        let loaded = UserDictionary::parse(&text);
+        assert_eq!(loaded.lookup("なかの").unwrap(), &["中野"]);
+        assert_eq!(
+            loaded.annotation("なかの", "中野"),
+            Some("東京都/中野区;駅")
+        );
+    }
+
+    #[test]
+    fn candidate_with_separators_roundtrips() {
+        let mut ud = UserDictionary::new();
+        ud.record("えぬえー", "N/A;x");
+        ud.record_okuri("わk", "き", "分/;き");
+        let text = ud.to_skk_text();
+        assert!(text.contains(r#"えぬえー /(concat "N\057A\073x")/"#));
+        let loaded = UserDictionary::parse(&text);
+        assert_eq!(loaded.lookup("えぬえー").unwrap(), &["N/A;x"]);
+        assert_eq!(loaded.lookup_okuri("わk", "き").unwrap(), &["分/;き"]);
+        assert_eq!(loaded.to_skk_text(), text);
+    }
+
+    // === 上限と追い出し ===
+
+    fn limits(per_reading: Option<usize>, readings: Option<usize>) -> UserDictLimits {
+        UserDictLimits {
+            max_candidates_per_reading: per_reading,
+            max_readings: readings,
+        }
+    }
+
+    #[test]
+    fn candidate_limit_evicts_least_recent() {
+        let mut ud = UserDictionary::new();
+        ud.set_limits(limits(Some(2), None));
+        ud.record("かんじ", "漢字");
+        ud.record("かんじ", "感じ");
+        ud.record("かんじ", "幹事");
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["幹事", "感じ"]);
+    }
+
+    #[test]
+    fn reading_limit_evicts_least_recently_used() {
+        let mut ud = UserDictionary::new();
+        ud.set_limits(limits(None, Some(2)));
+        ud.record("かんじ", "漢字");
+        ud.record("にほん", "日本");
+        ud.record("かんじ", "感じ"); // かんじ を最近使った状態にする
+        ud.record("とうきょう", "東京");
+        assert!(ud.lookup("にほん").is_none());
+        assert!(ud.lookup("かんじ").is_some());
+        assert!(ud.lookup("とうきょう").is_some());
+        assert_eq!(ud.len(), 2);
+    }
+
+    #[test]
+    fn eviction_never_removes_pinned_candidate() {
+        let mut ud = UserDictionary::new();
+        ud.set_limits(limits(Some(1), None));
+        ud.pin("かんじ", "漢字");
+        ud.record("かんじ", "感じ");
+        ud.record("かんじ", "幹事");
+        // 固定された "漢字" は残り、固定されていない候補は上限まで削除される
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["漢字"]);
+        assert!(ud.is_pinned("かんじ", "漢字"));
+    }
+
+    #[test]
+    fn pin_new_candidate_at_limit() {
+        let mut ud = UserDictionary::new();
+        ud.set_limits(limits(Some(2), None));
+        ud.record("かんじ", "感じ");
+        ud.record("かんじ", "漢字");
+        ud.pin("かんじ", "幹事");
+        // 固定した候補は残り、最も古い固定されていない候補が削除される
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["漢字", "幹事"]);
+        assert!(ud.is_pinned("かんじ", "幹事"));
+    }
+
+    #[test]
+    fn eviction_never_removes_reading_with_pinned_candidate() {
+        let mut ud = UserDictionary::new();
+        ud.set_limits(limits(None, Some(1)));
+        ud.pin("かいしゃ", "会社");
+        ud.record("かんじ", "漢字");
+        ud.record("にほん", "日本");
+        assert_eq!(ud.lookup("かいしゃ").unwrap(), &["会社"]);
+        assert_eq!(ud.lookup("にほん").unwrap(), &["日本"]);
+        assert!(ud.lookup("かんじ").is_none());
+    }
+
+    #[test]
+    fn set_limits_keeps_loaded_dictionary() {
+        let text = "かんじ /幹事/感じ/漢字/\nにほん /日本/\n";
+        let mut ud = UserDictionary::parse(text);
+        ud.set_limits(limits(Some(1), Some(1)));
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["幹事", "感じ", "漢字"]);
+        assert_eq!(ud.lookup("にほん").unwrap(), &["日本"]);
+        assert!(!ud.is_dirty());
+    }
+
+    #[test]
+    fn pin_survives_save_and_load() {
+        let mut ud = UserDictionary::new();
+        ud.record("かんじ", "漢字");
+        ud.pin("かんじ", "漢字");
+        let loaded = UserDictionary::parse(&ud.to_skk_text());
+        assert!(loaded.is_pinned("かんじ", "漢字"));
+
+        let mut ud = loaded;
+        ud.unpin("かんじ", "漢字");
+        assert!(!ud.is_pinned("かんじ", "漢字"));
+    }
+
+    // === 学習の取り消し ===
+
+    #[test]
+    fn rollback_restores_entries_and_bigrams() {
+        let mut ud = UserDictionary::new();
+        ud.record("かんじ", "漢字");
+        ud.record("かんじ", "感じ");
+        ud.record_bigram("会議", "感じ");
+        ud.begin_checkpoint();
+        ud.record("かんじ", "漢字");
+        ud.record("にほん", "日本");
+        ud.record_bigram("会議", "漢字");
+        let cp = ud.finish_checkpoint().unwrap();
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["漢字", "感じ"]);
+
+        ud.rollback(cp);
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["感じ", "漢字"]);
+        assert!(ud.lookup("にほん").is_none());
+        assert_eq!(ud.bigram_count("会議", "漢字"), 0);
+        assert_eq!(ud.bigram_count("会議", "感じ"), 1);
+    }
+
+    #[test]
+    fn rollback_restores_evicted_reading() {
+        let mut ud = UserDictionary::new();
+        ud.set_limits(UserDictLimits {
+            max_readings: Some(1),
+            ..UserDictLimits::default()
+        });
+        ud.record("かんじ", "漢字");
+        ud.begin_checkpoint();
+        ud.record("にほん", "日本");
+        assert!(ud.lookup("かんじ").is_none());
+        let cp = ud.finish_checkpoint().unwrap();
+        ud.rollback(cp);
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["漢字"]);
+        assert!(ud.lookup("にほん").is_none());
+    }
+
+    #[test]
+    fn changes_outside_checkpoint_are_kept() {
+        let mut ud = UserDictionary::new();
+        ud.begin_checkpoint();
+        let cp = ud.finish_checkpoint().unwrap();
+        ud.record("かんじ", "漢字");
+        ud.rollback(cp);
+        assert_eq!(ud.lookup("かんじ").unwrap(), &["漢字"]);
+    }
+
+    // === bigram（文脈の学習） ===
+
+    #[test]
+    fn record_bigram_counts() {
+        let mut ud = UserDictionary::new();
+        ud.record_bigram("会議", "幹事");
+        ud.record_bigram("会議", "幹事");
+        ud.record_bigram("会議", "資料");
+        assert_eq!(ud.bigram_count("会議", "幹事"), 2);
+        assert_eq!(ud.bigram_count("会議", "漢字"), 0);
+        assert_eq!(ud.successors("会議"), vec!["幹事", "資料"]);
+        assert!(ud.is_dirty());
+    }
+
+    #[test]
+    fn successors_tie_prefers_recent() {
+        let mut ud = UserDictionary::new();
+        ud.record_bigram("東京", "都");
+        ud.record_bigram("東京", "駅");
+        assert_eq!(ud.successors("東京"), vec!["駅", "都"]);
+    }
+
+    #[test]
+    fn bigrams_survive_save_and_load() {
+        let mut ud = UserDictionary::new();
+        ud.record_bigram("東京", "都");
+        ud.record_bigram("東京", "駅");
+        ud.record_bigram("東京", "都");
+        let loaded = UserDictionary::parse(&ud.to_skk_text());
+        assert_eq!(loaded.bigram_count("東京", "都"), 2);
+        assert_eq!(loaded.successors("東京"), vec!["都", "駅"]);
+    }
+
+    #[test]
+    fn bigram_with_tab_or_newline_is_not_recorded() {
+        let mut ud = UserDictionary::new();
+        ud.record_bigram("署名", "山田\n株式会社");
+        ud.record_bigram("a\tb", "c");
+        ud.record_bigram("東京", "駅");
+        assert!(ud.successors("署名").is_empty());
+        assert!(ud.successors("a\tb").is_empty());
+        let loaded = UserDictionary::parse(&ud.to_skk_text());
+        assert_eq!(loaded.successors("東京"), vec!["駅"]);
+        assert_eq!(loaded.to_skk_text(), ud.to_skk_text());
+    }
+
+    #[test]
+    fn successors_capped() {
+        let mut ud = UserDictionary::new();
+        for i in 0..(MAX_SUCCESSORS + 4) {
+            ud.record_bigram("前", &format!("語{i}"));
+        }
+        assert_eq!(ud.successors("前").len(), MAX_SUCCESSORS);
+        // 古いものから削除される
+        assert_eq!(ud.bigram_count("前", "語0"), 0);
+    }
 }